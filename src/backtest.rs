@@ -0,0 +1,183 @@
+use crate::bot::{BotBuilder, BotInfo};
+use crate::connector::simulator;
+use crate::model::context::Context;
+use crate::model::{indicator, result, storage, Strategy};
+use crate::strategy;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+struct Exposure {
+    max_drawdown: f64,
+    capital_locked: f64,
+    value: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct CycleReport {
+    pub cycle: u64,
+    pub entry: Option<result::Trade>,
+    pub margin_buys: Vec<result::Trade>,
+    pub exit: Option<result::Trade>,
+    pub pnl: f64,
+    pub max_drawdown: f64,
+    pub capital_locked: f64,
+}
+
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` (UTC) or a unix timestamp in seconds.
+pub fn parse_time(value: &str) -> Result<u64, chrono::ParseError> {
+    if let Ok(timestamp) = value.parse::<u64>() {
+        return Ok(timestamp);
+    }
+
+    match NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        Ok(datetime) => Ok(datetime.and_utc().timestamp() as u64),
+        Err(_) => {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")?;
+            Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as u64)
+        }
+    }
+}
+
+/// Quote needed to fill the entry and every margin level of a cycle.
+pub fn required_capital(config: &result::Bot) -> f64 {
+    let ratios: f64 = config
+        .margin
+        .margin_configuration
+        .iter()
+        .map(|margin| margin.amount_ratio)
+        .sum();
+
+    config.parameters.first_buy_in * (1.0 + ratios)
+}
+
+/// Stored 1m tickers of the bot's pair between `from` and `to`, carrying the
+/// MFI over the bot's `mfi_period` collected with them.
+pub fn tickers(path: &str, config: &result::Bot, from: u64, to: u64) -> Vec<result::Ticker> {
+    let mfis = storage::get_mfi_between(path, &config.pair, config.parameters.mfi_period, from, to);
+
+    storage::get_tickers_between(path, &config.pair, from, to)
+        .into_iter()
+        .map(|ticker| {
+            let mut ticker = ticker.unwrap();
+            ticker.mfi = mfis.get(&ticker.timestamp).copied();
+            ticker
+        })
+        .collect()
+}
+
+/// Replays the tickers against a private in-memory database, so nothing is
+/// written to the bot's own history. The MFI is the one the tickers carry,
+/// see [`tickers`], and the bot's indicators are computed on the replayed 1m
+/// tickers merged into klines of their interval. An order the simulator
+/// refuses, e.g. for lack of balance, ends the replay with an error.
+pub fn run(
+    config: &result::Bot,
+    tickers: &[result::Ticker],
+    capital: f64,
+    fee: f64,
+) -> Result<Vec<CycleReport>, String> {
+    // Reports are built from entry and exit cycles, which grid trades don't
+    // have.
+    if config.strategy == strategy::grid::NAME {
+        return Err(format!(
+            "backtests don't support the `{}` strategy",
+            strategy::grid::NAME
+        ));
+    }
+
+    let ctx = &Context::in_memory();
+
+    let connector = simulator::Connector::new(&config.base, &config.quote, capital, fee);
+    let strategy = strategy::build(config)?;
+    let bot = BotBuilder::<simulator::Connector, Box<dyn Strategy>>::new()
+        .with_info(BotInfo {
+            id: config.id,
+            platform: String::from(simulator::PLATFORM),
+            account: config.account(),
+            pair: config.pair.clone(),
+            base: config.base.clone(),
+            quote: config.quote.clone(),
+            mfi_period: config.parameters.mfi_period,
+            indicators: config.indicators.clone(),
+        })
+        .with_context(ctx.clone())
+        .with_strategy(strategy)
+        .with_connector(connector.clone())
+        .build();
+
+    let mut exposures: HashMap<u64, Exposure> = HashMap::new();
+    let mut pipelines = indicator::Resampled::by_interval(&config.indicators)?;
+    let mut indicators: HashMap<String, f64> = HashMap::new();
+    let mut prev_mfi: Option<f64> = None;
+    for ticker in tickers.iter() {
+        connector.set_market(ticker.close, ticker.timestamp);
+        let values: Vec<(String, f64)> = pipelines
+            .iter_mut()
+            .flat_map(|pipeline| pipeline.next(ticker))
+            .collect();
+        indicators.extend(values);
+
+        let mfi = ticker
+            .mfi
+            .map(|latest| [latest, prev_mfi.unwrap_or(latest)]);
+        bot.update_with(ticker.close, mfi, ticker, &indicators, ticker.timestamp)
+            .map_err(|err| format!("{} at {}: {}", config.pair, ticker.timestamp, err))?;
+        prev_mfi = ticker.mfi;
+
+        let trade = storage::get_latest_trade(ctx, config.id).unwrap();
+        let exposure = exposures.entry(trade.cycle).or_default();
+        let cost = connector.cost();
+        let value = connector.position() * ticker.close * (1.0 - connector.fee());
+
+        exposure.capital_locked = exposure.capital_locked.max(cost);
+        exposure.max_drawdown = exposure.max_drawdown.max(cost - value);
+        exposure.value = value;
+    }
+
+    let mut reports: Vec<CycleReport> = Vec::new();
+    for trade in storage::get_trades(ctx, config.id) {
+        let trade = trade.unwrap();
+
+        if reports.last().map(|report| report.cycle) != Some(trade.cycle) {
+            reports.push(CycleReport {
+                cycle: trade.cycle,
+                ..Default::default()
+            });
+        }
+
+        let report = reports.last_mut().unwrap();
+        match trade.status.as_str() {
+            "OPEN" => {
+                report.pnl -= trade.quote_qty + trade.quote_fee();
+                if report.entry.is_none() {
+                    report.entry = Some(trade);
+                } else {
+                    report.margin_buys.push(trade);
+                }
+            }
+            "CLOSE" | "STOPPED" => {
+                report.pnl += trade.quote_qty - trade.quote_fee();
+                report.exit = Some(trade);
+            }
+            "SELL" => {
+                report.pnl += trade.quote_qty - trade.quote_fee();
+            }
+            _ => {}
+        }
+    }
+
+    reports.retain(|report| report.entry.is_some());
+    for report in reports.iter_mut() {
+        if let Some(exposure) = exposures.get(&report.cycle) {
+            if report.exit.is_none() {
+                report.pnl += exposure.value;
+            }
+
+            report.max_drawdown = exposure.max_drawdown;
+            report.capital_locked = exposure.capital_locked;
+        }
+    }
+
+    Ok(reports)
+}
@@ -1,83 +1,253 @@
+use super::model::context::Context;
 use super::model::{result, storage};
-use super::model::{BotCommand, Exchange, Initial, Session, Strategy};
+use super::model::{BotCommand, Exchange, ExchangeError, Initial, Session, Strategy};
+use log::{error, warn};
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct BotInfo {
+    pub id: u64,
     pub platform: String,
+    pub account: String,
     pub pair: String,
     pub base: String,
     pub quote: String,
+    pub mfi_period: usize,
+    pub indicators: Vec<result::Indicator>,
 }
 
 #[derive(Debug)]
 pub struct Bot<T: Exchange, S: Strategy> {
     pub info: Option<BotInfo>,
+    pub context: Option<Context>,
     strategy: Option<S>,
     connector: Option<T>,
 }
 
 impl<T: Exchange, S: Strategy> Bot<T, S> {
+    /// Runs one tick on the latest stored tickers. Exchange errors never
+    /// escape: transient ones are retried on the next tick, rejected orders
+    /// are skipped and an unknown symbol pauses the bot.
     pub fn update(&self, price: f64) {
+        let ctx = self.context.as_ref().unwrap();
+        let info = self.info.as_ref().unwrap();
+        let mfi = storage::get_latest_mfi1m(ctx, &info.pair, info.mfi_period);
+        let ticker = storage::get_latest_price(&ctx.ticker_path("1m"), &info.pair);
+
+        let mut intervals: Vec<&str> = info
+            .indicators
+            .iter()
+            .map(|config| config.interval.as_str())
+            .collect();
+        intervals.sort();
+        intervals.dedup();
+        let indicators: HashMap<String, f64> = intervals
+            .into_iter()
+            .flat_map(|interval| {
+                storage::get_latest_indicators(&ctx.ticker_path(interval), &info.pair, interval)
+            })
+            .collect();
+
+        let updated = self.update_with(
+            price,
+            mfi,
+            &ticker,
+            &indicators,
+            chrono::offset::Utc::now().timestamp() as u64,
+        );
+        if let Err(err) = updated {
+            self.handle_error(err);
+        }
+    }
+
+    /// Runs one tick on the given market data and returns the exchange error
+    /// that stopped it, if any.
+    pub fn update_with(
+        &self,
+        price: f64,
+        mfi: Option<[f64; 2]>,
+        ticker: &result::Ticker,
+        indicators: &HashMap<String, f64>,
+        timestamp: u64,
+    ) -> Result<(), ExchangeError> {
+        self.step(price, mfi, ticker, indicators, timestamp)
+    }
+
+    /// Writes the records of one decision in a single transaction, so a
+    /// crash cannot leave a trade without the state that goes with it. The
+    /// exchange is called outside of it, so an order that went through is
+    /// always recorded and the database isn't locked across round-trips.
+    fn record(&self, writes: impl FnOnce()) {
+        let ctx = self.context.as_ref().unwrap();
+
+        let _ = ctx.transaction(|| {
+            writes();
+            Ok::<(), Infallible>(())
+        });
+    }
+
+    /// Stores the free quote balance, kept as is when it cannot be read.
+    fn refresh_wallet(&self) {
+        let ctx = self.context.as_ref().unwrap();
+        let connector = self.connector.as_ref().unwrap();
+        let info = self.info.as_ref().unwrap();
+
+        match connector.get_balance(info.quote.clone()) {
+            Ok(capital) => storage::update_wallet(ctx, &info.account, &info.quote, capital.free),
+            Err(err) => warn!(
+                "{} {}: {}, wallet not refreshed",
+                info.platform, info.pair, err
+            ),
+        }
+    }
+
+    fn handle_error(&self, err: ExchangeError) {
+        let ctx = self.context.as_ref().unwrap();
+        let info = self.info.as_ref().unwrap();
+
+        match err {
+            ExchangeError::InvalidSymbol(_) => {
+                error!("{} {}: {}, pausing bot", info.platform, info.pair, err);
+                storage::pause_bot(ctx, info.id);
+            }
+            err if err.is_transient() => {
+                warn!(
+                    "{} {}: {}, retrying next tick",
+                    info.platform, info.pair, err
+                );
+            }
+            err => {
+                error!("{} {}: {}, skipping", info.platform, info.pair, err);
+            }
+        }
+    }
+
+    fn step(
+        &self,
+        price: f64,
+        mfi: Option<[f64; 2]>,
+        ticker: &result::Ticker,
+        indicators: &HashMap<String, f64>,
+        timestamp: u64,
+    ) -> Result<(), ExchangeError> {
+        let ctx = self.context.as_ref().unwrap();
         let connector = self.connector.as_ref().unwrap();
         let info = self.info.as_ref().unwrap();
 
-        let trade = storage::get_latest_trade(&info.platform, &info.pair).unwrap();
-        let state = storage::get_latest_state(&info.platform, &info.pair).unwrap();
-        let avg_price = storage::get_avg_price(&info.platform, &info.pair, state.cycle);
-        let mfi = storage::get_latest_mfi1m(&info.pair);
-        let mfi_dir = if mfi[0] > mfi[1] {
-            "UP".to_string()
-        } else {
-            "DOWN".to_string()
+        let trade = storage::get_latest_trade(ctx, info.id).unwrap();
+        let mut state = storage::get_latest_state(ctx, info.id).unwrap();
+        let avg_price = storage::get_avg_price(ctx, info.id, state.cycle);
+        let mfi_dir = match mfi {
+            Some([latest, previous]) if latest > previous => "UP".to_string(),
+            _ => "DOWN".to_string(),
         };
 
-        if state.id == 0 || trade.status == "CLOSE" {
-            storage::create_trade(result::Trade {
-                pair: info.pair.clone(),
-                cycle: trade.cycle + 1,
-                price: 0.0,
-                qty: 0.0,
-                platform: info.platform.clone(),
-                status: String::from("WAIT"),
-                timestamp: chrono::offset::Utc::now().timestamp() as u64,
+        if state.id == 0 || trade.status == "CLOSE" || trade.status == "STOPPED" {
+            self.record(|| {
+                storage::create_trade(
+                    ctx,
+                    result::Trade {
+                        pair: info.pair.clone(),
+                        cycle: trade.cycle + 1,
+                        price: 0.0,
+                        qty: 0.0,
+                        platform: info.platform.clone(),
+                        status: String::from("WAIT"),
+                        timestamp,
+                        bot_id: info.id,
+                        ..Default::default()
+                    },
+                );
+
+                storage::create_bot_state(
+                    ctx,
+                    result::BotState {
+                        id: 0,
+                        pair: info.pair.clone(),
+                        cycle: trade.cycle + 1,
+                        margin_position: 0,
+                        top_price: price,
+                        bottom_price: price,
+                        bottom_mfi: 80.0,
+                        platform: info.platform.clone(),
+                        timestamp,
+                        take_profit_order_id: 0,
+                        bot_id: info.id,
+                        peak_price: 0.0,
+                    },
+                );
             });
 
-            storage::create_bot_state(result::BotState {
-                id: 0,
-                pair: info.pair.clone(),
-                cycle: trade.cycle + 1,
-                margin_position: 0,
-                top_price: price,
-                bottom_price: price,
-                bottom_mfi: 80.0,
-                platform: info.platform.clone(),
-                timestamp: chrono::offset::Utc::now().timestamp() as u64,
-            });
+            return Ok(());
+        }
 
-            return;
+        if state.take_profit_order_id != 0 {
+            let order = connector.get_order(info.pair.clone(), state.take_profit_order_id)?;
+
+            match order.status.as_str() {
+                "FILLED" => {
+                    self.record(|| {
+                        storage::create_trade(
+                            ctx,
+                            self.trade_from_order(&order, state.cycle, "CLOSE", timestamp),
+                        );
+                        storage::update_take_profit_order(ctx, state.id, 0);
+                    });
+                    self.refresh_wallet();
+
+                    println!("Take profit filled {}: {}", &info.base, order.executed_qty);
+
+                    return Ok(());
+                }
+                "CANCELED" | "EXPIRED" | "REJECTED" => {
+                    self.record(|| {
+                        self.record_partial_fill(&order, state.cycle, timestamp);
+                        storage::update_take_profit_order(ctx, state.id, 0);
+                    });
+                    state.take_profit_order_id = 0;
+                }
+                _ => {}
+            }
         }
 
-        self.update_price_level(
-            state.id,
-            price,
-            avg_price,
-            state.top_price,
-            state.bottom_price,
-        );
+        self.record(|| {
+            self.update_price_level(
+                state.id,
+                price,
+                avg_price,
+                state.top_price,
+                state.bottom_price,
+            );
+
+            if trade.status == "OPEN" && price > state.peak_price {
+                storage::update_peak_price(ctx, state.id, price);
+                state.peak_price = price;
+            }
 
-        self.update_mfi_level(state.id, mfi[0], state.bottom_mfi);
+            if let Some([latest, _]) = mfi {
+                self.update_mfi_level(state.id, latest, state.bottom_mfi);
+            }
+        });
 
         let session = Session::<Initial> {
             avg_price,
             top_price: state.top_price,
             bottom_price: state.bottom_price,
+            peak_price: state.peak_price,
             status: trade.status,
             margin_position: state.margin_position,
-            mfi: mfi[0],
+            mfi: mfi.map(|[latest, _]| latest),
             mfi_dir,
             bottom_mfi: state.bottom_mfi,
+            opened_at: storage::get_opened_at(ctx, info.id, state.cycle),
+            timestamp,
+            take_profit_order_id: state.take_profit_order_id,
+            grid_levels: storage::get_grid_levels(ctx, info.id),
+            ticker: ticker.clone(),
+            indicators: indicators.clone(),
             phantom: PhantomData,
         };
 
@@ -86,75 +256,254 @@ impl<T: Exchange, S: Strategy> Bot<T, S> {
         match command {
             BotCommand::Pause() => {}
             BotCommand::Entry(amount) => {
-                let quote_balance = connector.get_balance(info.quote.clone());
+                let quote_balance = connector.get_balance(info.quote.clone())?;
 
                 if quote_balance.free >= amount {
                     let transaction =
-                        connector.market_buy_using_quote_quantity(info.pair.clone(), amount);
-
-                    storage::create_trade(result::Trade {
-                        pair: info.pair.clone(),
-                        cycle: trade.cycle,
-                        price: transaction.price,
-                        qty: transaction.qty,
-                        platform: info.platform.clone(),
-                        status: String::from("OPEN"),
-                        timestamp: chrono::offset::Utc::now().timestamp() as u64,
+                        connector.market_buy_using_quote_quantity(info.pair.clone(), amount)?;
+
+                    self.record(|| {
+                        storage::update_peak_price(ctx, state.id, transaction.price);
+                        storage::create_trade(
+                            ctx,
+                            self.trade_from(
+                                transaction,
+                                trade.cycle,
+                                String::from("OPEN"),
+                                timestamp,
+                            ),
+                        );
                     });
-
-                    let capital = connector.get_balance(info.quote.clone());
-                    storage::update_wallet(&info.quote, capital.free);
+                    self.refresh_wallet();
 
                     println!("Entry signal {}", amount);
                 }
             }
             BotCommand::Buy(amount) => {
-                let quote_balance = connector.get_balance(info.quote.clone());
+                let quote_balance = connector.get_balance(info.quote.clone())?;
 
                 if quote_balance.free >= amount {
-                    let transaction =
-                        connector.market_buy_using_quote_quantity(info.pair.clone(), amount);
+                    self.cancel_take_profit(&state, timestamp)?;
 
-                    storage::create_trade(result::Trade {
-                        pair: info.pair.clone(),
-                        cycle: state.cycle,
-                        price: transaction.price,
-                        qty: transaction.qty,
-                        platform: info.platform.clone(),
-                        status: String::from("OPEN"),
-                        timestamp: chrono::offset::Utc::now().timestamp() as u64,
+                    let transaction =
+                        connector.market_buy_using_quote_quantity(info.pair.clone(), amount)?;
+
+                    self.record(|| {
+                        storage::create_trade(
+                            ctx,
+                            self.trade_from(
+                                transaction,
+                                state.cycle,
+                                String::from("OPEN"),
+                                timestamp,
+                            ),
+                        );
+
+                        storage::update_margin_position(ctx, state.id, state.margin_position + 1);
+                        if let Some([latest, _]) = mfi {
+                            storage::update_bottom_mfi(ctx, state.id, latest);
+                        }
                     });
+                    self.refresh_wallet();
 
-                    storage::update_margin_position(state.id, state.margin_position + 1);
-                    storage::update_bottom_mfi(state.id, mfi[0]);
+                    println!("Buy signal {}", amount);
+                }
+            }
+            BotCommand::Sell(exit) => {
+                self.cancel_take_profit(&state, timestamp)?;
+
+                let qty = self.position(state.cycle)?;
+                let adj_qty = connector.adjust_quantity(info.pair.clone(), qty)?;
+                let transaction = connector.market_sell(info.pair.clone(), adj_qty)?;
+
+                storage::create_trade(
+                    ctx,
+                    self.trade_from(
+                        transaction,
+                        state.cycle,
+                        String::from(exit.status()),
+                        timestamp,
+                    ),
+                );
+                self.refresh_wallet();
+
+                println!("{:?} signal {}: {}", exit, &info.base, adj_qty);
+            }
+            BotCommand::LimitSell(price) => {
+                let qty = self.position(state.cycle)?;
+                let adj_qty = connector.adjust_quantity(info.pair.clone(), qty)?;
+                let order = connector.limit_sell(info.pair.clone(), adj_qty, price)?;
 
-                    let capital = connector.get_balance(info.quote.clone());
-                    storage::update_wallet(&info.quote, capital.free);
+                storage::update_take_profit_order(ctx, state.id, order.order_id);
 
-                    println!("Buy signal {}", amount);
+                println!(
+                    "Take profit order {}: {} at {}",
+                    &info.base, adj_qty, order.price
+                );
+            }
+            BotCommand::GridBuy(level, amount) => {
+                let quote_balance = connector.get_balance(info.quote.clone())?;
+
+                if quote_balance.free >= amount {
+                    let transaction =
+                        connector.market_buy_using_quote_quantity(info.pair.clone(), amount)?;
+                    let trade =
+                        self.trade_from(transaction, state.cycle, String::from("BUY"), timestamp);
+
+                    self.record(|| {
+                        storage::fill_grid_level(
+                            ctx,
+                            info.id,
+                            result::GridLevel {
+                                level,
+                                price: trade.price,
+                                qty: trade.qty - trade.base_fee(),
+                            },
+                            timestamp,
+                        );
+                        storage::create_trade(ctx, trade);
+                    });
+                    self.refresh_wallet();
+
+                    println!("Grid buy {} at level {}", amount, level);
                 }
             }
-            BotCommand::Sell() => {
-                let qty = connector.get_balance(info.base.clone());
-                let adj_qty = connector.adjust_quantity(info.pair.clone(), qty.free);
-                let transaction = connector.market_sell(info.pair.clone(), adj_qty);
-
-                storage::create_trade(result::Trade {
-                    pair: info.pair.clone(),
-                    cycle: state.cycle,
-                    price: transaction.price,
-                    qty: transaction.qty,
-                    platform: info.platform.clone(),
-                    status: String::from("CLOSE"),
-                    timestamp: chrono::offset::Utc::now().timestamp() as u64,
+            BotCommand::GridSell(level) => {
+                let held = match storage::get_grid_levels(ctx, info.id)
+                    .into_iter()
+                    .find(|held| held.level == level && held.qty > 0.0)
+                {
+                    Some(held) => held.qty,
+                    None => return Ok(()),
+                };
+                let balance = connector.get_balance(info.base.clone())?;
+                let adj_qty =
+                    connector.adjust_quantity(info.pair.clone(), held.min(balance.free))?;
+                let transaction = connector.market_sell(info.pair.clone(), adj_qty)?;
+                let left = held - transaction.qty;
+
+                self.record(|| {
+                    storage::create_trade(
+                        ctx,
+                        self.trade_from(transaction, state.cycle, String::from("SELL"), timestamp),
+                    );
+
+                    // Whatever rounding to the lot size kept back stays on
+                    // the level for its next sell.
+                    if left > 0.0 {
+                        storage::update_grid_level_qty(ctx, info.id, level, left);
+                    } else {
+                        storage::clear_grid_level(ctx, info.id, level);
+                    }
                 });
+                self.refresh_wallet();
 
-                let capital = connector.get_balance(info.quote.clone());
-                storage::update_wallet(&info.quote, capital.free);
-
-                println!("Sell signal {}: {}", &info.base, adj_qty);
+                println!("Grid sell {}: {} at level {}", &info.base, adj_qty, level);
             }
         };
+
+        Ok(())
+    }
+
+    fn trade_from(
+        &self,
+        transaction: result::Transaction,
+        cycle: u64,
+        status: String,
+        timestamp: u64,
+    ) -> result::Trade {
+        let info = self.info.as_ref().unwrap();
+
+        result::Trade {
+            id: 0,
+            pair: info.pair.clone(),
+            cycle,
+            price: transaction.price,
+            qty: transaction.qty,
+            platform: info.platform.clone(),
+            status,
+            timestamp,
+            order_id: transaction.order_id,
+            quote_qty: transaction.quote_qty,
+            commission: transaction.commission,
+            commission_asset: transaction.commission_asset,
+            bot_id: info.id,
+        }
+    }
+
+    /// Base bought by this bot in the cycle, capped at the free balance so
+    /// other bots on the pair keep theirs.
+    fn position(&self, cycle: u64) -> Result<f64, ExchangeError> {
+        let ctx = self.context.as_ref().unwrap();
+        let connector = self.connector.as_ref().unwrap();
+        let info = self.info.as_ref().unwrap();
+
+        let (_, position) = storage::get_position(ctx, info.id, cycle);
+        let balance = connector.get_balance(info.base.clone())?;
+
+        Ok(position.min(balance.free))
+    }
+
+    fn trade_from_order(
+        &self,
+        order: &result::Order,
+        cycle: u64,
+        status: &str,
+        timestamp: u64,
+    ) -> result::Trade {
+        let info = self.info.as_ref().unwrap();
+
+        result::Trade {
+            id: 0,
+            pair: info.pair.clone(),
+            cycle,
+            price: order.quote_qty / order.executed_qty,
+            qty: order.executed_qty,
+            platform: info.platform.clone(),
+            status: String::from(status),
+            timestamp,
+            order_id: order.order_id,
+            quote_qty: order.quote_qty,
+            commission: order.commission,
+            commission_asset: order.commission_asset.clone(),
+            bot_id: info.id,
+        }
+    }
+
+    /// Records the part of a take profit that sold before it was cancelled
+    /// as a `SELL` trade, which leaves the cycle open with a smaller position.
+    fn record_partial_fill(&self, order: &result::Order, cycle: u64, timestamp: u64) {
+        if order.executed_qty > 0.0 {
+            let ctx = self.context.as_ref().unwrap();
+
+            storage::create_trade(ctx, self.trade_from_order(order, cycle, "SELL", timestamp));
+        }
+    }
+
+    fn cancel_take_profit(
+        &self,
+        state: &result::BotState,
+        timestamp: u64,
+    ) -> Result<(), ExchangeError> {
+        if state.take_profit_order_id == 0 {
+            return Ok(());
+        }
+
+        let ctx = self.context.as_ref().unwrap();
+        let connector = self.connector.as_ref().unwrap();
+        let info = self.info.as_ref().unwrap();
+
+        connector.cancel_order(info.pair.clone(), state.take_profit_order_id)?;
+        // Should this fail, the order is found cancelled on the next tick and
+        // its partial fill recorded then.
+        let order = connector.get_order(info.pair.clone(), state.take_profit_order_id)?;
+
+        self.record(|| {
+            self.record_partial_fill(&order, state.cycle, timestamp);
+            storage::update_take_profit_order(ctx, state.id, 0);
+        });
+
+        Ok(())
     }
 
     fn update_price_level(
@@ -165,22 +514,26 @@ impl<T: Exchange, S: Strategy> Bot<T, S> {
         top_price: f64,
         bottom_price: f64,
     ) {
+        let ctx = self.context.as_ref().unwrap();
+
         if price > top_price {
-            storage::update_top_price(id, price);
+            storage::update_top_price(ctx, id, price);
         } else if price < avg_price && top_price != avg_price {
-            storage::update_top_price(id, avg_price);
+            storage::update_top_price(ctx, id, avg_price);
         }
 
         if price < bottom_price {
-            storage::update_bottom_price(id, price);
+            storage::update_bottom_price(ctx, id, price);
         } else if price > avg_price && bottom_price != avg_price && avg_price != 0.0 {
-            storage::update_bottom_price(id, avg_price);
+            storage::update_bottom_price(ctx, id, avg_price);
         }
     }
 
     fn update_mfi_level(&self, id: u64, mfi: f64, bottom_mfi: f64) {
+        let ctx = self.context.as_ref().unwrap();
+
         if bottom_mfi > mfi || mfi > 50.0 {
-            storage::update_bottom_mfi(id, mfi);
+            storage::update_bottom_mfi(ctx, id, mfi);
         }
     }
 }
@@ -194,6 +547,7 @@ impl<T: Exchange, S: Strategy> BotBuilder<T, S> {
         Self {
             bot: Bot {
                 info: None,
+                context: None,
                 strategy: None,
                 connector: None,
             },
@@ -213,6 +567,15 @@ impl<T: Exchange, S: Strategy> BotBuilder<T, S> {
         }
     }
 
+    pub fn with_context(self, context: Context) -> Self {
+        Self {
+            bot: Bot {
+                context: Some(context),
+                ..self.bot
+            },
+        }
+    }
+
     pub fn with_strategy(self, strategy: S) -> Self {
         Self {
             bot: Bot {
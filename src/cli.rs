@@ -1,6 +1,7 @@
 use comfy_table::Table;
 
 use crate::model;
+use crate::{backtest, optimize, reconcile, status};
 
 pub fn display_bot(bot: model::result::Bot) {
     let mut table = Table::new();
@@ -11,17 +12,28 @@ pub fn display_bot(bot: model::result::Bot) {
         .add_row(vec!["Base", &bot.base])
         .add_row(vec!["Quote", &bot.quote])
         .add_row(vec!["Platform", &bot.platform])
+        .add_row(vec!["Account", &bot.account()])
         .add_row(vec!["Strategy", &bot.strategy])
         .add_row(vec!["Cycle", &bot.parameters.cycle])
         .add_row(vec![
             "First Buy In",
             &format!("{}", bot.parameters.first_buy_in),
         ])
+        .add_row(vec![
+            "MFI Period",
+            &format!("{}", bot.parameters.mfi_period),
+        ])
         .add_row(vec!["Entry", &format!("{:?}", bot.parameters.entry)])
         .add_row(vec![
             "Take Profit",
             &format!("{:?}", bot.parameters.take_profit),
-        ]);
+        ])
+        .add_row(vec![
+            "Stop Loss",
+            &format!("{:?}", bot.parameters.stop_loss),
+        ])
+        .add_row(vec!["Grid", &format!("{:?}", bot.parameters.grid)])
+        .add_row(vec!["Indicators", &format!("{:?}", bot.indicators)]);
 
     println!("{}", table);
 
@@ -36,17 +48,56 @@ pub fn display_bot(bot: model::result::Bot) {
     println!("{}", margin_table);
 }
 
+pub fn display_status(bot: &model::result::Bot, status: status::Status) {
+    let mut table = Table::new();
+    table
+        .set_header(vec!["Name", "Value"])
+        .add_row(vec!["Title", &bot.title])
+        .add_row(vec!["Pair", &bot.pair])
+        .add_row(vec!["Account", &bot.account()])
+        .add_row(vec!["Bot Status", &bot.status])
+        .add_row(vec!["Cycle", &format!("{}", status.cycle)])
+        .add_row(vec!["Trade Status", &status.trade_status])
+        .add_row(vec!["Price", &format!("{:.4}", status.price)])
+        .add_row(vec!["AVG", &format!("{:.4}", status.avg_price)])
+        .add_row(vec![
+            "Position",
+            &format!(
+                "{:.4} {} ({:.4} {})",
+                status.position, bot.base, status.cost, bot.quote
+            ),
+        ])
+        .add_row(vec![
+            "Margin",
+            &format!("{} / {}", status.margin_used, status.margin_available),
+        ])
+        .add_row(vec!["T.Price", &format!("{:.4}", status.top_price)])
+        .add_row(vec!["B.Price", &format!("{:.4}", status.bottom_price)])
+        .add_row(vec!["B.MFI", &format!("{:.4}", status.bottom_mfi)])
+        .add_row(vec![
+            "Unrealized PnL",
+            &format!("{:.4} {}", status.unrealized_pnl, bot.quote),
+        ])
+        .add_row(vec![
+            "Capital Needed",
+            &format!("{:.4} {}", status.capital_needed, bot.quote),
+        ]);
+
+    println!("{}", table);
+}
+
 pub fn display_bots(bots: Vec<crate::model::result::Bot>) {
     let mut table = Table::new();
     table.set_header(vec![
-        "Title", "Pair", "Platform", "Strategy", "Cycle", "Status",
+        "Title", "Pair", "Account", "Strategy", "Cycle", "Status",
     ]);
 
     for bot in bots {
+        let account = bot.account();
         table.add_row(vec![
             bot.title,
             bot.pair,
-            bot.platform,
+            account,
             bot.strategy,
             bot.parameters.cycle,
             bot.status,
@@ -59,10 +110,50 @@ pub fn display_bots(bots: Vec<crate::model::result::Bot>) {
 pub fn display_bind(api: crate::model::result::ApiCredential) {
     let mut table = Table::new();
     table
-        .set_header(vec!["Platform", "Api Key", "Secret Key"])
-        .add_row(vec![api.platform, api.api, api.secret]);
+        .set_header(vec!["Account", "Environment", "Api Key", "Secret Key"])
+        .add_row(vec![
+            format!("{}:{}", api.platform, api.name),
+            api.environment,
+            model::secret::mask(&api.api),
+            String::from("encrypted"),
+        ]);
+
+    println!("{}", table);
+}
+
+pub fn display_bindings(bindings: Vec<crate::model::result::ApiCredential>) {
+    let mut table = Table::new();
+    table.set_header(vec!["Account", "Environment", "Api Key", "Secret Key"]);
+
+    for binding in bindings {
+        let secret = if model::secret::is_encrypted(&binding.secret) {
+            "encrypted"
+        } else {
+            "plaintext"
+        };
+
+        table.add_row(vec![
+            format!("{}:{}", binding.platform, binding.name),
+            binding.environment,
+            model::secret::mask(&binding.api),
+            String::from(secret),
+        ]);
+    }
+
+    println!("{}", table);
+}
+
+pub fn display_paper_account(account: crate::model::result::PaperAccount) {
+    let mut table = Table::new();
+    table
+        .set_header(vec!["Taker Fee", "Slippage"])
+        .add_row(vec![
+            format!("{}%", account.taker_fee),
+            format!("{}%", account.slippage),
+        ]);
 
     println!("{}", table);
+    display_balances(account.balances);
 }
 
 pub fn display_balances(balances: Vec<crate::model::result::Balance>) {
@@ -75,3 +166,192 @@ pub fn display_balances(balances: Vec<crate::model::result::Balance>) {
 
     println!("{}", table);
 }
+
+pub fn display_orders(orders: Vec<crate::model::result::Order>) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Order Id", "Pair", "Side", "Price", "Quantity", "Executed", "Status",
+    ]);
+
+    for o in orders {
+        table.add_row(vec![
+            format!("{}", o.order_id),
+            o.pair,
+            o.side,
+            format!("{}", o.price),
+            format!("{}", o.orig_qty),
+            format!("{}", o.executed_qty),
+            o.status,
+        ]);
+    }
+
+    println!("{}", table);
+}
+
+pub fn display_reconciliation(discrepancies: &[reconcile::Discrepancy]) {
+    let fill = |trade: &crate::model::result::Trade| {
+        format!(
+            "{:.8} @ {:.8} (fee {} {})",
+            trade.qty, trade.price, trade.commission, trade.commission_asset
+        )
+    };
+
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Issue", "Order Id", "Cycle", "Status", "Local", "Exchange",
+    ]);
+
+    for d in discrepancies {
+        match d {
+            reconcile::Discrepancy::Missing(trade) => table.add_row(vec![
+                String::from("MISSING"),
+                format!("{}", trade.order_id),
+                format!("{}", trade.cycle),
+                trade.status.clone(),
+                String::from("-"),
+                fill(trade),
+            ]),
+            reconcile::Discrepancy::Duplicated(trade) => table.add_row(vec![
+                String::from("DUPLICATED"),
+                format!("{}", trade.order_id),
+                format!("{}", trade.cycle),
+                trade.status.clone(),
+                fill(trade),
+                String::from("-"),
+            ]),
+            reconcile::Discrepancy::Mismatched { local, exchange } => table.add_row(vec![
+                String::from("MISMATCHED"),
+                format!("{}", exchange.order_id),
+                format!("{}", local.cycle),
+                local.status.clone(),
+                fill(local),
+                fill(exchange),
+            ]),
+        };
+    }
+
+    println!("{}", table);
+}
+
+pub fn display_backtest(reports: Vec<backtest::CycleReport>) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Cycle",
+        "Opened",
+        "Entry",
+        "Margin Buys",
+        "Exit",
+        "PnL",
+        "Max DD",
+        "Capital",
+    ]);
+
+    let mut total_pnl = 0.0;
+    let mut max_drawdown: f64 = 0.0;
+    let mut max_capital: f64 = 0.0;
+    for report in reports.iter() {
+        let entry = report.entry.as_ref().unwrap();
+        let opened = chrono::DateTime::from_timestamp(entry.timestamp as i64, 0)
+            .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let margin_buys: Vec<String> = report
+            .margin_buys
+            .iter()
+            .map(|trade| format!("{:.4} x {:.4}", trade.price, trade.qty))
+            .collect();
+        let exit = match &report.exit {
+            Some(trade) if trade.status == "STOPPED" => format!("{:.4} STOPPED", trade.price),
+            Some(trade) => format!("{:.4}", trade.price),
+            None => String::from("OPEN"),
+        };
+
+        table.add_row(vec![
+            format!("{}", report.cycle),
+            opened,
+            format!("{:.4}", entry.price),
+            margin_buys.join("\n"),
+            exit,
+            format!("{:.4}", report.pnl),
+            format!("{:.4}", report.max_drawdown),
+            format!("{:.4}", report.capital_locked),
+        ]);
+
+        total_pnl += report.pnl;
+        max_drawdown = max_drawdown.max(report.max_drawdown);
+        max_capital = max_capital.max(report.capital_locked);
+    }
+
+    println!("{}", table);
+    println!(
+        "Cycles: {}, PnL: {:.4}, Max DD: {:.4}, Max Capital: {:.4}",
+        reports.len(),
+        total_pnl,
+        max_drawdown,
+        max_capital
+    );
+}
+
+pub fn display_optimization(outcomes: &[optimize::Outcome], limit: usize) {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Rank",
+        "First Buy In",
+        "Entry Below",
+        "Take Profit",
+        "MFI Below",
+        "Ratios",
+        "Cycles",
+        "PnL",
+        "Max DD",
+        "Capital",
+    ]);
+
+    for (i, outcome) in outcomes.iter().take(limit).enumerate() {
+        let parameters = &outcome.config.parameters;
+        let ratios: Vec<String> = outcome
+            .config
+            .margin
+            .margin_configuration
+            .iter()
+            .map(|margin| format!("{}", margin.amount_ratio))
+            .collect();
+
+        table.add_row(vec![
+            format!("{}", i + 1),
+            format!("{}", parameters.first_buy_in),
+            format!("{}", parameters.entry.price_change_below),
+            format!("{}", parameters.take_profit.price_change_above),
+            format!("{}", parameters.entry.mfi_below),
+            ratios.join(", "),
+            format!("{}", outcome.cycles),
+            format!("{:.4}", outcome.pnl),
+            format!("{:.4}", outcome.max_drawdown),
+            format!("{:.4}", outcome.capital),
+        ]);
+    }
+
+    println!("{}", table);
+}
+
+pub fn display_migrations(path: &str, migrations: Vec<model::migration::Applied>) {
+    let mut table = Table::new();
+    table.set_header(vec!["Version", "Description", "Applied"]);
+
+    for migration in migrations {
+        let applied = match migration.applied_at {
+            Some(timestamp) => chrono::DateTime::from_timestamp(timestamp as i64, 0)
+                .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default(),
+            None => String::from("PENDING"),
+        };
+
+        table.add_row(vec![
+            format!("{}", migration.version),
+            migration.description,
+            applied,
+        ]);
+    }
+
+    println!("{}", path);
+    println!("{}", table);
+}
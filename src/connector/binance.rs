@@ -1,26 +1,228 @@
+use crate::connector::round_to_step;
 use crate::model;
-use crate::model::result;
+use crate::model::{bind, result, ExchangeError};
 use binance::account;
 use binance::api::*;
+use binance::config::Config;
+use binance::errors::ErrorKind;
 use binance::general;
 use binance::market;
-use binance::model::TradeHistory;
+use binance::model::{FillInfo, Filters, KlineSummaries, Order, Symbol, TradeHistory, Transaction};
 use chrono::{Duration, Utc};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::Instant;
 
 pub const PLATFORM: &str = "binance";
 
+const STREAM_HOST: &str = "wss://stream.binance.com:9443";
+const TESTNET_STREAM_HOST: &str = "wss://testnet.binance.vision";
+
+/// How long symbol filters are trusted before `exchangeInfo` is fetched again.
+const FILTER_REFRESH: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 pub struct Connector {
     account: account::Account,
     general: general::General,
     market: market::Market,
+    filters: Mutex<FilterCache>,
+}
+
+#[derive(Default)]
+struct FilterCache {
+    symbols: HashMap<String, SymbolFilters>,
+    fetched_at: Option<Instant>,
+}
+
+/// Trading rules of a symbol, as listed in `exchangeInfo`. Bounds that are
+/// not set are zero.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolFilters {
+    pub tick_size: f64,
+    pub min_price: f64,
+    pub max_price: f64,
+    pub step_size: f64,
+    pub min_qty: f64,
+    pub max_qty: f64,
+    pub market_min_qty: f64,
+    pub market_max_qty: f64,
+    pub min_notional: f64,
+    pub quote_precision: u64,
+}
+
+impl SymbolFilters {
+    fn from_symbol(symbol: &Symbol) -> Self {
+        let mut filters = SymbolFilters {
+            quote_precision: symbol.quote_precision,
+            ..Default::default()
+        };
+
+        for filter in symbol.filters.iter() {
+            match filter {
+                Filters::PriceFilter {
+                    min_price,
+                    max_price,
+                    tick_size,
+                } => {
+                    filters.min_price = parse(min_price);
+                    filters.max_price = parse(max_price);
+                    filters.tick_size = parse(tick_size);
+                }
+                Filters::LotSize {
+                    min_qty,
+                    max_qty,
+                    step_size,
+                } => {
+                    filters.min_qty = parse(min_qty);
+                    filters.max_qty = parse(max_qty);
+                    filters.step_size = parse(step_size);
+                }
+                Filters::MarketLotSize {
+                    min_qty, max_qty, ..
+                } => {
+                    filters.market_min_qty = parse(min_qty);
+                    filters.market_max_qty = parse(max_qty);
+                }
+                Filters::MinNotional { min_notional, .. }
+                | Filters::Notional { min_notional, .. } => {
+                    filters.min_notional = min_notional.as_deref().map(parse).unwrap_or(0.0);
+                }
+                _ => {}
+            }
+        }
+
+        filters
+    }
+
+    /// Price rounded to the nearest tick, within the price filter.
+    pub fn price(&self, pair: &str, price: f64) -> Result<f64, ExchangeError> {
+        let price = round_to_step(price, self.tick_size, f64::round);
+
+        if price < self.min_price || (self.max_price > 0.0 && price > self.max_price) {
+            return Err(ExchangeError::Rejected(format!(
+                "{} price {} outside {} - {}",
+                pair, price, self.min_price, self.max_price
+            )));
+        }
+
+        Ok(price)
+    }
+
+    /// Quantity rounded down to the lot step, within the lot size. Market
+    /// orders also have to fit the market lot size.
+    pub fn quantity(&self, pair: &str, qty: f64, market: bool) -> Result<f64, ExchangeError> {
+        let qty = round_to_step(qty, self.step_size, f64::floor);
+        let mut bounds = vec![(self.min_qty, self.max_qty)];
+        if market {
+            bounds.push((self.market_min_qty, self.market_max_qty));
+        }
+
+        for (min_qty, max_qty) in bounds {
+            if qty <= 0.0 || qty < min_qty {
+                return Err(ExchangeError::LotSize(format!(
+                    "{} quantity {} below {}",
+                    pair, qty, min_qty
+                )));
+            }
+            if max_qty > 0.0 && qty > max_qty {
+                return Err(ExchangeError::LotSize(format!(
+                    "{} quantity {} above {}",
+                    pair, qty, max_qty
+                )));
+            }
+        }
+
+        Ok(qty)
+    }
+
+    /// Quote amount rounded down to the quote precision, e.g. for a market buy.
+    pub fn quote(&self, qty: f64) -> f64 {
+        round_to_step(qty, 10f64.powi(-(self.quote_precision as i32)), f64::floor)
+    }
+
+    pub fn check_notional(&self, pair: &str, notional: f64) -> Result<(), ExchangeError> {
+        if notional < self.min_notional {
+            return Err(ExchangeError::MinNotional(format!(
+                "{} order of {} below {}",
+                pair, notional, self.min_notional
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn parse(value: &str) -> f64 {
+    value.parse().unwrap_or(0.0)
+}
+
+/// Endpoints of the environment, Binance Spot Testnet for `testnet`. The
+/// endpoints can be pointed elsewhere, e.g. at a mock server, with
+/// `SPEARMINT_BINANCE_URL` and `SPEARMINT_BINANCE_STREAM_URL`.
+pub fn config(environment: &str) -> Config {
+    let mut config = if environment == bind::TESTNET {
+        Config::testnet()
+    } else {
+        Config::default()
+    };
+
+    if let Ok(url) = env::var("SPEARMINT_BINANCE_URL") {
+        config = config.set_rest_api_endpoint(url);
+    }
+    if let Ok(url) = env::var("SPEARMINT_BINANCE_STREAM_URL") {
+        config = config.set_ws_endpoint(url);
+    }
+
+    config
+}
+
+/// Websocket endpoint and subscription combining several streams, e.g.
+/// `dogeusdt@kline_1m`, on the environment's stream host.
+pub fn combined_streams(environment: &str, streams: &[String]) -> (Config, String) {
+    let host = env::var("SPEARMINT_BINANCE_STREAM_URL").unwrap_or_else(|_| {
+        String::from(if environment == bind::TESTNET {
+            TESTNET_STREAM_HOST
+        } else {
+            STREAM_HOST
+        })
+    });
+
+    (
+        Config::default().set_ws_endpoint(host),
+        format!("stream?streams={}", streams.join("/")),
+    )
 }
 
 impl Connector {
     pub fn from_credential(api_key: String, api_secret: String) -> Self {
+        Self::with_config(api_key, api_secret, &Config::default())
+    }
+
+    pub fn with_config(api_key: String, api_secret: String, config: &Config) -> Self {
         Self {
-            account: Binance::new(Some(api_key.clone()), Some(api_secret.clone())),
-            general: Binance::new(Some(api_key.clone()), Some(api_secret.clone())),
-            market: Binance::new(Some(api_key), Some(api_secret)),
+            account: Binance::new_with_config(
+                Some(api_key.clone()),
+                Some(api_secret.clone()),
+                config,
+            ),
+            general: Binance::new_with_config(
+                Some(api_key.clone()),
+                Some(api_secret.clone()),
+                config,
+            ),
+            market: Binance::new_with_config(Some(api_key), Some(api_secret), config),
+            filters: Mutex::new(FilterCache::default()),
+        }
+    }
+
+    /// Connector without keys, enough for market data such as symbol filters.
+    pub fn public(config: &Config) -> Self {
+        Self {
+            account: Binance::new_with_config(None, None, config),
+            general: Binance::new_with_config(None, None, config),
+            market: Binance::new_with_config(None, None, config),
+            filters: Mutex::new(FilterCache::default()),
         }
     }
 
@@ -82,6 +284,151 @@ impl Connector {
         pnl
     }
 
+    /// Filters of the pair, from a copy of `exchangeInfo` refreshed every
+    /// `FILTER_REFRESH`.
+    pub fn symbol_filters(&self, pair: &str) -> Result<SymbolFilters, ExchangeError> {
+        let mut cache = self.filters.lock().unwrap();
+
+        if !matches!(cache.fetched_at, Some(fetched_at) if fetched_at.elapsed() < FILTER_REFRESH) {
+            let exchange_info = self.general.exchange_info()?;
+            cache.symbols = exchange_info
+                .symbols
+                .iter()
+                .map(|symbol| (symbol.symbol.clone(), SymbolFilters::from_symbol(symbol)))
+                .collect();
+            cache.fetched_at = Some(Instant::now());
+        }
+
+        cache
+            .symbols
+            .get(pair)
+            .cloned()
+            .ok_or_else(|| ExchangeError::InvalidSymbol(String::from(pair)))
+    }
+
+    /// Closed klines of the pair, oldest first, at most `limit` of them.
+    pub fn get_klines(
+        &self,
+        pair: &str,
+        interval: &str,
+        limit: u16,
+    ) -> Result<Vec<result::Ticker>, ExchangeError> {
+        // The last kline is still open, so one more is asked for.
+        let KlineSummaries::AllKlineSummaries(klines) =
+            self.market
+                .get_klines(pair, interval, limit.saturating_add(1), None, None)?;
+        let now = Utc::now().timestamp_millis();
+
+        Ok(klines
+            .into_iter()
+            .filter(|kline| kline.close_time < now)
+            .map(|kline| result::Ticker {
+                pair: String::from(pair),
+                timestamp: (kline.open_time / 1000) as u64,
+                open: parse(&kline.open),
+                high: parse(&kline.high),
+                low: parse(&kline.low),
+                close: parse(&kline.close),
+                volume: parse(&kline.volume),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn fill_commission(fills: &[FillInfo]) -> (f64, String) {
+        let commission_asset = fills
+            .first()
+            .map(|fill| fill.commission_asset.clone())
+            .unwrap_or_default();
+        let commission: f64 = fills
+            .iter()
+            .filter(|fill| fill.commission_asset == commission_asset)
+            .map(|fill| fill.commission)
+            .sum();
+
+        (commission, commission_asset)
+    }
+
+    fn transaction_from(transaction: Transaction) -> result::Transaction {
+        let (commission, commission_asset) =
+            Self::fill_commission(&transaction.fills.unwrap_or_default());
+
+        let price = if transaction.executed_qty > 0.0 {
+            transaction.cummulative_quote_qty / transaction.executed_qty
+        } else {
+            0.0
+        };
+
+        result::Transaction {
+            order_id: transaction.order_id,
+            pair: transaction.symbol,
+            price,
+            qty: transaction.executed_qty,
+            quote_qty: transaction.cummulative_quote_qty,
+            commission,
+            commission_asset,
+            platform: String::from(PLATFORM),
+            timestamp: transaction.transact_time,
+        }
+    }
+
+    /// Commission paid on an order's fills, looked up in the account's trade history.
+    fn order_commission(&self, pair: &str, order_id: u64) -> Result<(f64, String), ExchangeError> {
+        let trades: Vec<TradeHistory> = self
+            .account
+            .trade_history(pair)?
+            .into_iter()
+            .filter(|trade| trade.order_id == order_id)
+            .collect();
+
+        let commission_asset = trades
+            .first()
+            .map(|trade| trade.commission_asset.clone())
+            .unwrap_or_default();
+        let commission: f64 = trades
+            .iter()
+            .filter(|trade| trade.commission_asset == commission_asset)
+            .map(|trade| trade.commission.parse::<f64>().unwrap_or(0.0))
+            .sum();
+
+        Ok((commission, commission_asset))
+    }
+
+    fn order_from_transaction(transaction: Transaction) -> result::Order {
+        let (commission, commission_asset) =
+            Self::fill_commission(&transaction.fills.unwrap_or_default());
+
+        result::Order {
+            pair: transaction.symbol,
+            order_id: transaction.order_id,
+            side: transaction.side,
+            price: transaction.price,
+            orig_qty: transaction.orig_qty,
+            executed_qty: transaction.executed_qty,
+            quote_qty: transaction.cummulative_quote_qty,
+            commission,
+            commission_asset,
+            status: transaction.status,
+            timestamp: transaction.transact_time,
+        }
+    }
+
+    fn order_from_status(order: Order) -> result::Order {
+        result::Order {
+            pair: order.symbol,
+            order_id: order.order_id,
+            side: order.side,
+            price: order.price,
+            orig_qty: order.orig_qty.parse().unwrap_or(0.0),
+            executed_qty: order.executed_qty.parse().unwrap_or(0.0),
+            quote_qty: order.cummulative_quote_qty.parse().unwrap_or(0.0),
+            commission: 0.0,
+            commission_asset: String::new(),
+            status: order.status,
+            timestamp: order.update_time,
+        }
+    }
+
     pub fn fetch_server_time(&self) {
         match self.general.get_server_time() {
             Ok(server_time) => println!("Binance Server Time: {}", server_time.server_time),
@@ -90,104 +437,158 @@ impl Connector {
     }
 }
 
+impl From<binance::errors::Error> for ExchangeError {
+    fn from(err: binance::errors::Error) -> Self {
+        match err.0 {
+            ErrorKind::BinanceError(content) => match content.code {
+                -2010 if content.msg.to_lowercase().contains("insufficient balance") => {
+                    ExchangeError::InsufficientBalance(content.msg)
+                }
+                -1013 if content.msg.contains("NOTIONAL") => {
+                    ExchangeError::MinNotional(content.msg)
+                }
+                -1003 | -1015 => ExchangeError::RateLimited(content.msg),
+                -1021 => ExchangeError::Timestamp(content.msg),
+                -1001 | -1007 => ExchangeError::Network(content.msg),
+                -1121 => ExchangeError::InvalidSymbol(content.msg),
+                -1013 | -2010 | -2011 | -2013 => ExchangeError::Rejected(content.msg),
+                _ => ExchangeError::Other(format!("{}: {}", content.code, content.msg)),
+            },
+            ErrorKind::ReqError(err) => ExchangeError::Network(err.to_string()),
+            kind => ExchangeError::Other(kind.to_string()),
+        }
+    }
+}
+
 impl model::Exchange for Connector {
-    fn get_balances(&self) -> Vec<result::Balance> {
-        let account_information = self.account.get_account().unwrap();
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError> {
+        let account_information = self.account.get_account()?;
         let mut balances: Vec<result::Balance> = Vec::new();
 
         for b in account_information.balances.iter() {
-            if b.free.parse::<f32>().unwrap() == 0.0 {
+            let free = b.free.parse::<f64>().unwrap_or(0.0);
+            if free == 0.0 {
                 continue;
             }
             balances.push(result::Balance {
                 asset: b.asset.clone(),
-                free: b.free.parse::<f64>().unwrap(),
+                free,
             });
         }
 
-        balances
+        Ok(balances)
     }
 
-    fn market_buy_using_quote_quantity(&self, pair: String, qty: f64) -> result::Transaction {
-        let transaction = self
-            .account
-            .market_buy_using_quote_quantity(pair, qty)
-            .unwrap();
-
-        let price: f64 = match transaction.fills {
-            None => 0.0,
-            Some(fills) => {
-                let sum: f64 = fills.iter().map(|x| x.price).sum();
-                sum / fills.len() as f64
-            }
-        };
+    fn market_buy_using_quote_quantity(
+        &self,
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        let filters = self.symbol_filters(&pair)?;
+        let qty = filters.quote(qty);
+        filters.check_notional(&pair, qty)?;
 
-        result::Transaction {
-            order_id: transaction.order_id,
-            pair: transaction.symbol,
-            price: price,
-            qty: transaction.executed_qty,
-            platform: String::from(PLATFORM),
-            timestamp: transaction.transact_time,
-        }
+        let transaction = self.account.market_buy_using_quote_quantity(pair, qty)?;
+
+        Ok(Self::transaction_from(transaction))
     }
 
-    fn market_sell(&self, pair: String, qty: f64) -> result::Transaction {
-        let transaction = self.account.market_sell(pair, qty).unwrap();
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError> {
+        let filters = self.symbol_filters(&pair)?;
+        let qty = filters.quantity(&pair, qty, true)?;
+        if let Some(price) = self.get_price(&pair) {
+            filters.check_notional(&pair, qty * price)?;
+        }
 
-        let price: f64 = match transaction.fills {
-            None => 0.0,
-            Some(fills) => {
-                let sum: f64 = fills.iter().map(|x| x.price).sum();
-                sum / fills.len() as f64
-            }
-        };
+        let transaction = self.account.market_sell(pair, qty)?;
 
-        result::Transaction {
-            order_id: transaction.order_id,
-            pair: transaction.symbol,
-            price: price,
-            qty: transaction.executed_qty,
-            platform: String::from(PLATFORM),
-            timestamp: transaction.transact_time,
-        }
+        Ok(Self::transaction_from(transaction))
     }
 
-    fn get_balance(&self, asset: String) -> result::Balance {
-        let balance = self.account.get_balance(asset).unwrap();
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError> {
+        let balance = self.account.get_balance(asset)?;
 
-        result::Balance {
+        Ok(result::Balance {
             asset: balance.asset,
-            free: balance.free.parse::<f64>().unwrap(),
+            free: balance.free.parse::<f64>().unwrap_or(0.0),
+        })
+    }
+
+    fn adjust_quantity(&self, pair: String, qty: f64) -> Result<f64, ExchangeError> {
+        self.symbol_filters(&pair)?.quantity(&pair, qty, false)
+    }
+
+    fn limit_buy(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let filters = self.symbol_filters(&pair)?;
+        let price = filters.price(&pair, price)?;
+        let qty = filters.quantity(&pair, qty, false)?;
+        filters.check_notional(&pair, qty * price)?;
+
+        let transaction = self.account.limit_buy(pair, qty, price)?;
+
+        Ok(Self::order_from_transaction(transaction))
+    }
+
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let filters = self.symbol_filters(&pair)?;
+        let price = filters.price(&pair, price)?;
+        let qty = filters.quantity(&pair, qty, false)?;
+        filters.check_notional(&pair, qty * price)?;
+
+        let transaction = self.account.limit_sell(pair, qty, price)?;
+
+        Ok(Self::order_from_transaction(transaction))
+    }
+
+    fn get_order(&self, pair: String, order_id: u64) -> Result<result::Order, ExchangeError> {
+        let mut order = Self::order_from_status(self.account.order_status(pair.clone(), order_id)?);
+
+        if order.executed_qty > 0.0 {
+            let (commission, commission_asset) = self.order_commission(&pair, order_id)?;
+            order.commission = commission;
+            order.commission_asset = commission_asset;
         }
+
+        Ok(order)
     }
 
-    fn adjust_quantity(&self, pair: String, qty: f64) -> f64 {
-        let exchange_info = self.general.exchange_info().unwrap();
+    fn cancel_order(&self, pair: String, order_id: u64) -> Result<(), ExchangeError> {
+        self.account.cancel_order(pair, order_id)?;
 
-        let symbol_info = exchange_info
-            .symbols
-            .into_iter()
-            .find(|s| s.symbol == pair)
-            .expect("Pair not found");
+        Ok(())
+    }
 
-        let lot_size_filter = symbol_info
-            .filters
-            .iter()
-            .find_map(|filter| match filter {
-                binance::model::Filters::LotSize {
-                    min_qty, step_size, ..
-                } => Some((min_qty, step_size)),
-                _ => None,
-            })
-            .expect("Lot size filter not found");
+    fn get_open_orders(&self, pair: String) -> Result<Vec<result::Order>, ExchangeError> {
+        let orders = self.account.get_open_orders(pair)?;
 
-        let step_size: f64 = lot_size_filter.1.parse().unwrap();
-        let precision = step_size.log10().abs().round() as u32;
+        Ok(orders.into_iter().map(Self::order_from_status).collect())
+    }
 
-        let adjusted_qty =
-            (qty * 10f64.powi(precision as i32)).floor() / 10f64.powi(precision as i32);
+    fn get_fills(&self, pair: String) -> Result<Vec<result::Fill>, ExchangeError> {
+        let trades = self.account.trade_history(pair)?;
 
-        adjusted_qty
+        Ok(trades
+            .into_iter()
+            .map(|trade| result::Fill {
+                order_id: trade.order_id,
+                pair: trade.symbol,
+                price: trade.price,
+                qty: trade.qty,
+                commission: trade.commission.parse::<f64>().unwrap_or(0.0),
+                commission_asset: trade.commission_asset,
+                is_buyer: trade.is_buyer,
+                timestamp: trade.time,
+            })
+            .collect())
     }
 }
@@ -0,0 +1,682 @@
+use crate::connector::round_to_step;
+use crate::model;
+use crate::model::{result, Exchange, ExchangeError};
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+pub const PLATFORM: &str = "bybit";
+
+const REST_URL: &str = "https://api.bybit.com";
+const STREAM_URL: &str = "wss://stream.bybit.com/v5/public/spot";
+const RECV_WINDOW: &str = "5000";
+/// How often a market order is polled until it's filled, and for how long.
+const FILL_POLL: Duration = Duration::from_millis(250);
+const FILL_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an instrument's precisions are trusted before they're fetched again.
+const INSTRUMENT_REFRESH: Duration = Duration::from_secs(60 * 60);
+
+/// Spot trading on Bybit's unified account through the v5 REST API. The
+/// endpoints can be pointed elsewhere, e.g. at a mock server, with
+/// `SPEARMINT_BYBIT_URL` and `SPEARMINT_BYBIT_STREAM_URL`.
+pub struct Connector {
+    api_key: String,
+    api_secret: String,
+    base_url: String,
+    client: Client,
+    precisions: Mutex<HashMap<String, (Instant, Precisions)>>,
+}
+
+/// `basePrecision`, `quotePrecision` and `tickSize` of an instrument.
+type Precisions = (f64, f64, f64);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    ret_code: i64,
+    ret_msg: String,
+    #[serde(default)]
+    result: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrderStatus {
+    order_id: String,
+    symbol: String,
+    side: String,
+    #[serde(default)]
+    price: String,
+    #[serde(default)]
+    qty: String,
+    #[serde(default)]
+    cum_exec_qty: String,
+    #[serde(default)]
+    cum_exec_value: String,
+    order_status: String,
+    #[serde(default)]
+    updated_time: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Execution {
+    order_id: String,
+    symbol: String,
+    side: String,
+    exec_price: String,
+    exec_qty: String,
+    #[serde(default)]
+    exec_fee: String,
+    #[serde(default)]
+    fee_currency: String,
+    exec_time: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Coin {
+    coin: String,
+    #[serde(default)]
+    wallet_balance: String,
+    #[serde(default)]
+    locked: String,
+}
+
+/// Kline update pushed by the public spot stream. Updates keep coming while
+/// the candle is open; `confirm` is set on its last one.
+#[derive(Debug, Deserialize)]
+pub struct Kline {
+    #[serde(skip)]
+    pub symbol: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: String,
+    pub confirm: bool,
+}
+
+impl Connector {
+    pub fn from_credential(api_key: String, api_secret: String) -> Self {
+        Self {
+            api_key,
+            api_secret,
+            base_url: env::var("SPEARMINT_BYBIT_URL").unwrap_or_else(|_| String::from(REST_URL)),
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            precisions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn sign(&self, timestamp: &str, payload: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.api_secret.as_bytes()).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(self.api_key.as_bytes());
+        mac.update(RECV_WINDOW.as_bytes());
+        mac.update(payload.as_bytes());
+
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Value, ExchangeError> {
+        let query = query
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>()
+            .join("&");
+        let timestamp = timestamp().to_string();
+
+        let response = self
+            .client
+            .get(format!("{}{}?{}", self.base_url, path, query))
+            .header("X-BAPI-API-KEY", &self.api_key)
+            .header("X-BAPI-SIGN", self.sign(&timestamp, &query))
+            .header("X-BAPI-TIMESTAMP", &timestamp)
+            .header("X-BAPI-RECV-WINDOW", RECV_WINDOW)
+            .send();
+
+        Self::result_from(response)
+    }
+
+    fn post(&self, path: &str, body: Value) -> Result<Value, ExchangeError> {
+        let body = body.to_string();
+        let timestamp = timestamp().to_string();
+
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, path))
+            .header("Content-Type", "application/json")
+            .header("X-BAPI-API-KEY", &self.api_key)
+            .header("X-BAPI-SIGN", self.sign(&timestamp, &body))
+            .header("X-BAPI-TIMESTAMP", &timestamp)
+            .header("X-BAPI-RECV-WINDOW", RECV_WINDOW)
+            .body(body)
+            .send();
+
+        Self::result_from(response)
+    }
+
+    fn result_from(
+        response: reqwest::Result<reqwest::blocking::Response>,
+    ) -> Result<Value, ExchangeError> {
+        let response = response.map_err(|err| ExchangeError::Network(err.to_string()))?;
+        let status = response.status();
+        let response: Response = response
+            .json()
+            .map_err(|err| ExchangeError::Network(format!("{}: {}", status, err)))?;
+
+        match response.ret_code {
+            0 => Ok(response.result),
+            10006 | 10018 => Err(ExchangeError::RateLimited(response.ret_msg)),
+            10002 => Err(ExchangeError::Timestamp(response.ret_msg)),
+            10000 | 10016 => Err(ExchangeError::Network(response.ret_msg)),
+            170131 => Err(ExchangeError::InsufficientBalance(response.ret_msg)),
+            170136 | 170140 => Err(ExchangeError::MinNotional(response.ret_msg)),
+            10001 if response.ret_msg.to_lowercase().contains("symbol") => {
+                Err(ExchangeError::InvalidSymbol(response.ret_msg))
+            }
+            170121 => Err(ExchangeError::InvalidSymbol(response.ret_msg)),
+            110001 | 170213 => Err(ExchangeError::Rejected(response.ret_msg)),
+            code => Err(ExchangeError::Other(format!(
+                "{}: {}",
+                code, response.ret_msg
+            ))),
+        }
+    }
+
+    fn list<T: for<'de> Deserialize<'de>>(result: Value) -> Result<Vec<T>, ExchangeError> {
+        serde_json::from_value(result["list"].clone())
+            .map_err(|err| ExchangeError::Other(format!("unexpected response: {}", err)))
+    }
+
+    /// Precisions of the pair, fetched once every `INSTRUMENT_REFRESH`.
+    fn get_precisions(&self, pair: &str) -> Result<Precisions, ExchangeError> {
+        let mut cache = self.precisions.lock().unwrap();
+
+        match cache.get(pair) {
+            Some((fetched_at, precisions)) if fetched_at.elapsed() < INSTRUMENT_REFRESH => {
+                Ok(*precisions)
+            }
+            _ => {
+                let precisions = self.fetch_precisions(pair)?;
+                cache.insert(String::from(pair), (Instant::now(), precisions));
+
+                Ok(precisions)
+            }
+        }
+    }
+
+    fn fetch_precisions(&self, pair: &str) -> Result<Precisions, ExchangeError> {
+        let result = self.get(
+            "/v5/market/instruments-info",
+            &[("category", "spot"), ("symbol", pair)],
+        )?;
+        let instrument = &result["list"][0];
+        if instrument.is_null() {
+            return Err(ExchangeError::InvalidSymbol(String::from(pair)));
+        }
+
+        let precision = |value: &Value| value.as_str().unwrap_or("0").parse().unwrap_or(0.0);
+
+        Ok((
+            precision(&instrument["lotSizeFilter"]["basePrecision"]),
+            precision(&instrument["lotSizeFilter"]["quotePrecision"]),
+            precision(&instrument["priceFilter"]["tickSize"]),
+        ))
+    }
+
+    fn executions(
+        &self,
+        pair: &str,
+        order_id: Option<&str>,
+    ) -> Result<Vec<Execution>, ExchangeError> {
+        let mut query = vec![("category", "spot"), ("symbol", pair)];
+        if let Some(order_id) = order_id {
+            query.push(("orderId", order_id));
+        }
+
+        Self::list(self.get("/v5/execution/list", &query)?)
+    }
+
+    /// Commission paid on an order's fills, looked up in the execution history.
+    fn order_commission(&self, pair: &str, order_id: &str) -> Result<(f64, String), ExchangeError> {
+        let executions = self.executions(pair, Some(order_id))?;
+
+        let commission_asset = executions
+            .first()
+            .map(|execution| execution.fee_currency.clone())
+            .unwrap_or_default();
+        let commission: f64 = executions
+            .iter()
+            .filter(|execution| execution.fee_currency == commission_asset)
+            .map(|execution| execution.exec_fee.parse::<f64>().unwrap_or(0.0))
+            .sum();
+
+        Ok((commission, commission_asset))
+    }
+
+    fn place_order(
+        &self,
+        pair: &str,
+        side: &str,
+        order_type: &str,
+        qty: f64,
+        price: Option<f64>,
+    ) -> Result<String, ExchangeError> {
+        let mut body = json!({
+            "category": "spot",
+            "symbol": pair,
+            "side": side,
+            "orderType": order_type,
+            "qty": qty.to_string(),
+        });
+        if let Some(price) = price {
+            body["price"] = json!(price.to_string());
+            body["timeInForce"] = json!("GTC");
+        } else if side == "Buy" {
+            body["marketUnit"] = json!("quoteCoin");
+        } else {
+            body["marketUnit"] = json!("baseCoin");
+        }
+
+        let result = self.post("/v5/order/create", body)?;
+
+        result["orderId"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| ExchangeError::Other(String::from("order id missing from response")))
+    }
+
+    fn order_status(&self, pair: &str, order_id: &str) -> Result<OrderStatus, ExchangeError> {
+        let query = [
+            ("category", "spot"),
+            ("symbol", pair),
+            ("orderId", order_id),
+        ];
+        let mut orders: Vec<OrderStatus> = Self::list(self.get("/v5/order/realtime", &query)?)?;
+        if orders.is_empty() {
+            orders = Self::list(self.get("/v5/order/history", &query)?)?;
+        }
+
+        orders
+            .into_iter()
+            .next()
+            .ok_or_else(|| ExchangeError::Other(format!("order {} not found", order_id)))
+    }
+
+    fn order_from_status(order: OrderStatus) -> result::Order {
+        result::Order {
+            pair: order.symbol,
+            order_id: order.order_id.parse().unwrap_or(0),
+            side: order.side.to_uppercase(),
+            price: order.price.parse().unwrap_or(0.0),
+            orig_qty: order.qty.parse().unwrap_or(0.0),
+            executed_qty: order.cum_exec_qty.parse().unwrap_or(0.0),
+            quote_qty: order.cum_exec_value.parse().unwrap_or(0.0),
+            commission: 0.0,
+            commission_asset: String::new(),
+            status: Self::status_from(&order.order_status),
+            timestamp: order.updated_time.parse().unwrap_or(0),
+        }
+    }
+
+    /// Order statuses as Binance names them, which is what the bot checks for.
+    fn status_from(status: &str) -> String {
+        String::from(match status {
+            "New" | "Untriggered" => "NEW",
+            "PartiallyFilled" => "PARTIALLY_FILLED",
+            "Filled" => "FILLED",
+            "Cancelled" | "PartiallyFilledCanceled" => "CANCELED",
+            "Rejected" => "REJECTED",
+            "Deactivated" => "EXPIRED",
+            other => other,
+        })
+    }
+
+    /// Polls a placed order until it's filled or cancelled. An order that
+    /// is still open after `FILL_TIMEOUT` is an error, not an empty fill.
+    fn wait_for_fill(&self, pair: &str, order_id: &str) -> Result<(), ExchangeError> {
+        let started = Instant::now();
+
+        loop {
+            let order = self.order_status(pair, order_id)?;
+            match order.order_status.as_str() {
+                "Filled" => return Ok(()),
+                "Cancelled" | "PartiallyFilledCanceled"
+                    if order.cum_exec_qty.parse::<f64>().unwrap_or(0.0) > 0.0 =>
+                {
+                    return Ok(())
+                }
+                "Cancelled" | "PartiallyFilledCanceled" | "Rejected" | "Deactivated" => {
+                    return Err(ExchangeError::Rejected(format!(
+                        "order {} {} without fills",
+                        order_id,
+                        order.order_status.to_lowercase()
+                    )))
+                }
+                _ if started.elapsed() >= FILL_TIMEOUT => {
+                    return Err(ExchangeError::Other(format!(
+                        "order {} not filled after {}s",
+                        order_id,
+                        FILL_TIMEOUT.as_secs()
+                    )))
+                }
+                _ => thread::sleep(FILL_POLL),
+            }
+        }
+    }
+
+    /// Waits for a market order's fill and returns it as a transaction.
+    fn market_order(
+        &self,
+        pair: String,
+        side: &str,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        let order_id = self.place_order(&pair, side, "Market", qty, None)?;
+        self.wait_for_fill(&pair, &order_id)?;
+        let order = self.get_order(pair, order_id.parse().unwrap_or(0))?;
+
+        let price = if order.executed_qty > 0.0 {
+            order.quote_qty / order.executed_qty
+        } else {
+            0.0
+        };
+
+        Ok(result::Transaction {
+            order_id: order.order_id,
+            pair: order.pair,
+            price,
+            qty: order.executed_qty,
+            quote_qty: order.quote_qty,
+            commission: order.commission,
+            commission_asset: order.commission_asset,
+            platform: String::from(PLATFORM),
+            timestamp: order.timestamp,
+        })
+    }
+}
+
+impl model::Exchange for Connector {
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError> {
+        let result = self.get("/v5/account/wallet-balance", &[("accountType", "UNIFIED")])?;
+        let coins: Vec<Coin> =
+            serde_json::from_value(result["list"][0]["coin"].clone()).unwrap_or_default();
+
+        Ok(coins
+            .into_iter()
+            .map(|coin| result::Balance {
+                asset: coin.coin,
+                free: coin.wallet_balance.parse::<f64>().unwrap_or(0.0)
+                    - coin.locked.parse::<f64>().unwrap_or(0.0),
+            })
+            .filter(|balance| balance.free != 0.0)
+            .collect())
+    }
+
+    fn market_buy_using_quote_quantity(
+        &self,
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        let (_, quote_precision, _) = self.get_precisions(&pair)?;
+
+        self.market_order(pair, "Buy", round_to_step(qty, quote_precision, f64::floor))
+    }
+
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError> {
+        self.market_order(pair, "Sell", qty)
+    }
+
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError> {
+        let result = self.get(
+            "/v5/account/wallet-balance",
+            &[("accountType", "UNIFIED"), ("coin", &asset)],
+        )?;
+        let coins: Vec<Coin> =
+            serde_json::from_value(result["list"][0]["coin"].clone()).unwrap_or_default();
+
+        let free = coins
+            .iter()
+            .find(|coin| coin.coin == asset)
+            .map(|coin| {
+                coin.wallet_balance.parse::<f64>().unwrap_or(0.0)
+                    - coin.locked.parse::<f64>().unwrap_or(0.0)
+            })
+            .unwrap_or(0.0);
+
+        Ok(result::Balance { asset, free })
+    }
+
+    fn adjust_quantity(&self, pair: String, qty: f64) -> Result<f64, ExchangeError> {
+        let (base_precision, _, _) = self.get_precisions(&pair)?;
+
+        Ok(round_to_step(qty, base_precision, f64::floor))
+    }
+
+    fn limit_buy(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let (base_precision, _, tick_size) = self.get_precisions(&pair)?;
+        let price = round_to_step(price, tick_size, f64::floor);
+        let qty = round_to_step(qty, base_precision, f64::floor);
+        let order_id = self.place_order(&pair, "Buy", "Limit", qty, Some(price))?;
+
+        self.get_order(pair, order_id.parse().unwrap_or(0))
+    }
+
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let (base_precision, _, tick_size) = self.get_precisions(&pair)?;
+        let price = round_to_step(price, tick_size, f64::floor);
+        let qty = round_to_step(qty, base_precision, f64::floor);
+        let order_id = self.place_order(&pair, "Sell", "Limit", qty, Some(price))?;
+
+        self.get_order(pair, order_id.parse().unwrap_or(0))
+    }
+
+    fn get_order(&self, pair: String, order_id: u64) -> Result<result::Order, ExchangeError> {
+        let order_id = order_id.to_string();
+        let mut order = Self::order_from_status(self.order_status(&pair, &order_id)?);
+
+        if order.executed_qty > 0.0 {
+            let (commission, commission_asset) = self.order_commission(&pair, &order_id)?;
+            order.commission = commission;
+            order.commission_asset = commission_asset;
+        }
+
+        Ok(order)
+    }
+
+    fn cancel_order(&self, pair: String, order_id: u64) -> Result<(), ExchangeError> {
+        self.post(
+            "/v5/order/cancel",
+            json!({
+                "category": "spot",
+                "symbol": pair,
+                "orderId": order_id.to_string(),
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    fn get_open_orders(&self, pair: String) -> Result<Vec<result::Order>, ExchangeError> {
+        let orders: Vec<OrderStatus> = Self::list(self.get(
+            "/v5/order/realtime",
+            &[("category", "spot"), ("symbol", &pair), ("openOnly", "0")],
+        )?)?;
+
+        Ok(orders.into_iter().map(Self::order_from_status).collect())
+    }
+
+    fn get_fills(&self, pair: String) -> Result<Vec<result::Fill>, ExchangeError> {
+        let executions = self.executions(&pair, None)?;
+
+        Ok(executions
+            .into_iter()
+            .map(|execution| result::Fill {
+                order_id: execution.order_id.parse().unwrap_or(0),
+                pair: execution.symbol,
+                price: execution.exec_price.parse().unwrap_or(0.0),
+                qty: execution.exec_qty.parse().unwrap_or(0.0),
+                commission: execution.exec_fee.parse().unwrap_or(0.0),
+                commission_asset: execution.fee_currency,
+                is_buyer: execution.side == "Buy",
+                timestamp: execution.exec_time.parse().unwrap_or(0),
+            })
+            .collect())
+    }
+}
+
+/// Kline interval as Bybit names it: minutes, or `D`, `W` and `M`.
+fn interval_from(kline: &str) -> String {
+    let (amount, unit) = kline.split_at(kline.len().saturating_sub(1));
+    let amount: u64 = amount.parse().unwrap_or(1);
+
+    match unit {
+        "h" => (amount * 60).to_string(),
+        "d" => String::from("D"),
+        "w" => String::from("W"),
+        "M" => String::from("M"),
+        _ => amount.to_string(),
+    }
+}
+
+/// Closed klines of the pair from the public market endpoint, oldest first,
+/// at most `limit` of them.
+pub fn get_klines(
+    pair: &str,
+    kline: &str,
+    limit: u64,
+) -> Result<Vec<result::Ticker>, ExchangeError> {
+    let base_url = env::var("SPEARMINT_BYBIT_URL").unwrap_or_else(|_| String::from(REST_URL));
+    // Newest first, starting with the kline that is still open.
+    let response = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap()
+        .get(format!(
+            "{}/v5/market/kline?category=spot&symbol={}&interval={}&limit={}",
+            base_url,
+            pair,
+            interval_from(kline),
+            limit + 1
+        ))
+        .send();
+    let rows: Vec<Vec<String>> = Connector::list(Connector::result_from(response)?)?;
+
+    Ok(rows
+        .into_iter()
+        .skip(1)
+        .rev()
+        .map(|row| {
+            let value = |index: usize| {
+                row.get(index)
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .unwrap_or(0.0)
+            };
+
+            result::Ticker {
+                pair: String::from(pair),
+                timestamp: value(0) as u64 / 1000,
+                open: value(1),
+                high: value(2),
+                low: value(3),
+                close: value(4),
+                volume: value(5),
+                ..Default::default()
+            }
+        })
+        .collect())
+}
+
+/// Streams klines of the pairs from the public spot stream, calling
+/// `handler` on every update until the connection drops.
+pub fn stream_klines<F>(pairs: &[String], kline: &str, mut handler: F) -> Result<(), ExchangeError>
+where
+    F: FnMut(Kline),
+{
+    let url = env::var("SPEARMINT_BYBIT_STREAM_URL").unwrap_or_else(|_| String::from(STREAM_URL));
+    let interval = interval_from(kline);
+    let topics: Vec<String> = pairs
+        .iter()
+        .map(|pair| format!("kline.{}.{}", interval, pair))
+        .collect();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let (ws_stream, _) = connect_async(url.as_str())
+            .await
+            .map_err(|err| ExchangeError::Network(err.to_string()))?;
+        let (mut write, mut read) = ws_stream.split();
+
+        // Spot streams take at most 10 topics per subscription.
+        for chunk in topics.chunks(10) {
+            let subscribe = json!({ "op": "subscribe", "args": chunk });
+            write
+                .send(Message::Text(subscribe.to_string().into()))
+                .await
+                .map_err(|err| ExchangeError::Network(err.to_string()))?;
+        }
+
+        let mut ping = tokio::time::interval(Duration::from_secs(20));
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    write
+                        .send(Message::Text(json!({ "op": "ping" }).to_string().into()))
+                        .await
+                        .map_err(|err| ExchangeError::Network(err.to_string()))?;
+                }
+                message = read.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        let event: Value = serde_json::from_str(text.as_str()).unwrap_or_default();
+                        let topic = event["topic"].as_str().unwrap_or_default();
+                        let symbol = match topic.rsplit_once('.') {
+                            Some((_, symbol)) if topic.starts_with("kline.") => symbol,
+                            _ => continue,
+                        };
+
+                        let klines: Vec<Kline> =
+                            serde_json::from_value(event["data"].clone()).unwrap_or_default();
+                        for mut kline in klines {
+                            kline.symbol = String::from(symbol);
+                            handler(kline);
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(ExchangeError::Network(err.to_string())),
+                    None => return Err(ExchangeError::Network(String::from("stream closed"))),
+                },
+            }
+        }
+    })
+}
+
+fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
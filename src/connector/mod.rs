@@ -1 +1,90 @@
+use crate::model::context::Context;
+use crate::model::{bind, Exchange, ExchangeError};
+
 pub mod binance;
+pub mod bybit;
+pub mod okx;
+pub mod paper;
+pub mod simulator;
+
+/// Platforms a bot can trade on, by the name used in `platform`.
+pub const PLATFORMS: [&str; 4] = [
+    paper::PLATFORM,
+    binance::PLATFORM,
+    bybit::PLATFORM,
+    okx::PLATFORM,
+];
+
+pub fn check(platform: &str) -> Result<(), String> {
+    if PLATFORMS.contains(&platform) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown platform `{}`, expected one of {}",
+            platform,
+            PLATFORMS.join(", ")
+        ))
+    }
+}
+
+/// Connector of `account`, e.g. `binance:sub1`, signed with the keys of its
+/// binding. Paper accounts trade `base` against `quote`.
+pub fn build(
+    ctx: &Context,
+    account: &str,
+    base: &str,
+    quote: &str,
+) -> Result<Box<dyn Exchange>, String> {
+    let (platform, _) = bind::parse_account(account);
+    check(platform)?;
+
+    if platform == paper::PLATFORM {
+        return Ok(Box::new(paper::PaperExchange::new(ctx, base, quote)));
+    }
+
+    let credential = bind::get(ctx, account)?;
+    let connector: Box<dyn Exchange> = match platform {
+        bybit::PLATFORM => Box::new(bybit::Connector::from_credential(
+            credential.api,
+            credential.secret,
+        )),
+        okx::PLATFORM => Box::new(okx::Connector::from_credential(
+            credential.api,
+            credential.secret,
+            credential.passphrase,
+        )),
+        _ => Box::new(binance::Connector::with_config(
+            credential.api,
+            credential.secret,
+            &binance::config(&credential.environment),
+        )),
+    };
+
+    Ok(connector)
+}
+
+/// Smallest order value accepted on the pair, read without keys. Zero for
+/// platforms whose filters are not looked up.
+pub fn min_notional(ctx: &Context, account: &str, pair: &str) -> Result<f64, ExchangeError> {
+    let (platform, _) = bind::parse_account(account);
+    if platform != binance::PLATFORM {
+        return Ok(0.0);
+    }
+
+    let connector = binance::Connector::public(&binance::config(&bind::environment(ctx, account)));
+
+    Ok(connector.symbol_filters(pair)?.min_notional)
+}
+
+/// Rounds `value` to a multiple of `step`, such as a tick size of `0.0001`,
+/// keeping the step's decimals.
+pub fn round_to_step(value: f64, step: f64, round: fn(f64) -> f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+
+    let precision = 10f64.powi((-step.log10()).ceil().max(0.0) as i32);
+    let steps = round(value / step + 1e-9);
+
+    (steps * step * precision).round() / precision
+}
@@ -0,0 +1,513 @@
+use crate::connector::round_to_step;
+use crate::model;
+use crate::model::{result, Exchange, ExchangeError};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const PLATFORM: &str = "okx";
+
+const REST_URL: &str = "https://www.okx.com";
+/// Quote currencies used to split pairs such as `PENGUUSDT` into OKX
+/// instrument ids such as `PENGU-USDT`.
+const QUOTES: [&str; 6] = ["USDT", "USDC", "BTC", "ETH", "EUR", "OKB"];
+/// How often a market order is polled until it's filled, and for how long.
+const FILL_POLL: Duration = Duration::from_millis(250);
+const FILL_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an instrument's lot size is trusted before it's fetched again.
+const INSTRUMENT_REFRESH: Duration = Duration::from_secs(60 * 60);
+
+/// Spot trading on OKX through the v5 REST API, in cash mode. The endpoint
+/// can be pointed elsewhere, e.g. at a mock server, with `SPEARMINT_OKX_URL`.
+pub struct Connector {
+    api_key: String,
+    api_secret: String,
+    passphrase: String,
+    base_url: String,
+    client: Client,
+    lot_sizes: Mutex<HashMap<String, (Instant, LotSize)>>,
+}
+
+/// `lotSz` and `tickSz` of an instrument.
+type LotSize = (f64, f64);
+
+#[derive(Deserialize)]
+struct Response {
+    code: String,
+    msg: String,
+    #[serde(default)]
+    data: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OrderDetail {
+    inst_id: String,
+    ord_id: String,
+    side: String,
+    #[serde(default)]
+    px: String,
+    #[serde(default)]
+    sz: String,
+    #[serde(default)]
+    tgt_ccy: String,
+    #[serde(default)]
+    acc_fill_sz: String,
+    #[serde(default)]
+    avg_px: String,
+    state: String,
+    #[serde(default)]
+    fee: String,
+    #[serde(default)]
+    fee_ccy: String,
+    #[serde(default)]
+    u_time: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fill {
+    inst_id: String,
+    ord_id: String,
+    side: String,
+    fill_px: String,
+    fill_sz: String,
+    #[serde(default)]
+    fee: String,
+    #[serde(default)]
+    fee_ccy: String,
+    ts: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Detail {
+    ccy: String,
+    #[serde(default)]
+    avail_bal: String,
+}
+
+impl Connector {
+    pub fn from_credential(api_key: String, api_secret: String, passphrase: String) -> Self {
+        Self {
+            api_key,
+            api_secret,
+            passphrase,
+            base_url: env::var("SPEARMINT_OKX_URL").unwrap_or_else(|_| String::from(REST_URL)),
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            lot_sizes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Base64 HMAC-SHA256 of timestamp, method, request path and body.
+    fn sign(&self, timestamp: &str, method: &str, path: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.api_secret.as_bytes()).unwrap();
+        mac.update(timestamp.as_bytes());
+        mac.update(method.as_bytes());
+        mac.update(path.as_bytes());
+        mac.update(body.as_bytes());
+
+        STANDARD.encode(mac.finalize().into_bytes())
+    }
+
+    fn send(&self, method: &str, path: &str, body: String) -> Result<Value, ExchangeError> {
+        let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+        let url = format!("{}{}", self.base_url, path);
+
+        let request = if method == "GET" {
+            self.client.get(url)
+        } else {
+            self.client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(body.clone())
+        };
+
+        let response = request
+            .header("OK-ACCESS-KEY", &self.api_key)
+            .header("OK-ACCESS-SIGN", self.sign(&timestamp, method, path, &body))
+            .header("OK-ACCESS-TIMESTAMP", &timestamp)
+            .header("OK-ACCESS-PASSPHRASE", &self.passphrase)
+            .send()
+            .map_err(|err| ExchangeError::Network(err.to_string()))?;
+
+        let status = response.status();
+        let response: Response = response
+            .json()
+            .map_err(|err| ExchangeError::Network(format!("{}: {}", status, err)))?;
+
+        if response.code == "0" {
+            return Ok(response.data);
+        }
+
+        // Order endpoints report the reason per order rather than at the top.
+        let (code, msg) = match response.data[0]["sCode"].as_str() {
+            Some(code) if code != "0" => (
+                String::from(code),
+                String::from(response.data[0]["sMsg"].as_str().unwrap_or_default()),
+            ),
+            _ => (response.code, response.msg),
+        };
+
+        Err(match code.as_str() {
+            "50011" | "50061" => ExchangeError::RateLimited(msg),
+            "50102" | "50112" => ExchangeError::Timestamp(msg),
+            "50001" | "50013" | "50026" => ExchangeError::Network(msg),
+            "51008" => ExchangeError::InsufficientBalance(msg),
+            "51020" => ExchangeError::MinNotional(msg),
+            "51001" => ExchangeError::InvalidSymbol(msg),
+            "51000" | "51121" | "51400" | "51401" | "51402" => ExchangeError::Rejected(msg),
+            _ => ExchangeError::Other(format!("{}: {}", code, msg)),
+        })
+    }
+
+    fn get(&self, path: &str, query: &[(&str, &str)]) -> Result<Value, ExchangeError> {
+        let query = query
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>()
+            .join("&");
+
+        if query.is_empty() {
+            self.send("GET", path, String::new())
+        } else {
+            self.send("GET", &format!("{}?{}", path, query), String::new())
+        }
+    }
+
+    fn post(&self, path: &str, body: Value) -> Result<Value, ExchangeError> {
+        self.send("POST", path, body.to_string())
+    }
+
+    fn data<T: for<'de> Deserialize<'de>>(data: Value) -> Result<Vec<T>, ExchangeError> {
+        serde_json::from_value(data)
+            .map_err(|err| ExchangeError::Other(format!("unexpected response: {}", err)))
+    }
+
+    /// Lot size of the pair, fetched once every `INSTRUMENT_REFRESH`.
+    fn get_lot_size(&self, pair: &str) -> Result<LotSize, ExchangeError> {
+        let mut cache = self.lot_sizes.lock().unwrap();
+
+        match cache.get(pair) {
+            Some((fetched_at, lot_size)) if fetched_at.elapsed() < INSTRUMENT_REFRESH => {
+                Ok(*lot_size)
+            }
+            _ => {
+                let lot_size = self.fetch_lot_size(pair)?;
+                cache.insert(String::from(pair), (Instant::now(), lot_size));
+
+                Ok(lot_size)
+            }
+        }
+    }
+
+    fn fetch_lot_size(&self, pair: &str) -> Result<LotSize, ExchangeError> {
+        let data = self.get(
+            "/api/v5/public/instruments",
+            &[("instType", "SPOT"), ("instId", &inst_id(pair))],
+        )?;
+        let instrument = &data[0];
+        if instrument.is_null() {
+            return Err(ExchangeError::InvalidSymbol(String::from(pair)));
+        }
+
+        let size = |value: &Value| value.as_str().unwrap_or("0").parse().unwrap_or(0.0);
+
+        Ok((size(&instrument["lotSz"]), size(&instrument["tickSz"])))
+    }
+
+    fn place_order(
+        &self,
+        pair: &str,
+        side: &str,
+        qty: f64,
+        price: Option<f64>,
+    ) -> Result<String, ExchangeError> {
+        let mut body = json!({
+            "instId": inst_id(pair),
+            "tdMode": "cash",
+            "side": side,
+            "sz": qty.to_string(),
+        });
+        if let Some(price) = price {
+            body["ordType"] = json!("limit");
+            body["px"] = json!(price.to_string());
+        } else {
+            body["ordType"] = json!("market");
+            body["tgtCcy"] = json!(if side == "buy" {
+                "quote_ccy"
+            } else {
+                "base_ccy"
+            });
+        }
+
+        let data = self.post("/api/v5/trade/order", body)?;
+
+        data[0]["ordId"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| ExchangeError::Other(String::from("order id missing from response")))
+    }
+
+    fn order_from_detail(order: OrderDetail) -> result::Order {
+        let executed_qty: f64 = order.acc_fill_sz.parse().unwrap_or(0.0);
+        let avg_price: f64 = order.avg_px.parse().unwrap_or(0.0);
+        // Market buys are sized in quote currency.
+        let orig_qty = if order.tgt_ccy == "quote_ccy" && avg_price > 0.0 {
+            order.sz.parse::<f64>().unwrap_or(0.0) / avg_price
+        } else {
+            order.sz.parse().unwrap_or(0.0)
+        };
+
+        result::Order {
+            pair: order.inst_id.replace('-', ""),
+            order_id: order.ord_id.parse().unwrap_or(0),
+            side: order.side.to_uppercase(),
+            price: order.px.parse().unwrap_or(avg_price),
+            orig_qty,
+            executed_qty,
+            quote_qty: executed_qty * avg_price,
+            // Fees are reported as negative amounts.
+            commission: -order.fee.parse::<f64>().unwrap_or(0.0),
+            commission_asset: order.fee_ccy,
+            status: Self::status_from(&order.state),
+            timestamp: order.u_time.parse().unwrap_or(0),
+        }
+    }
+
+    /// Order states as Binance names them, which is what the bot checks for.
+    fn status_from(state: &str) -> String {
+        String::from(match state {
+            "live" => "NEW",
+            "partially_filled" => "PARTIALLY_FILLED",
+            "filled" => "FILLED",
+            "canceled" | "mmp_canceled" => "CANCELED",
+            other => other,
+        })
+    }
+
+    /// Polls a placed order until it's filled or canceled. An order that is
+    /// still live after `FILL_TIMEOUT` is an error, not an empty fill.
+    fn wait_for_fill(&self, pair: &str, order_id: u64) -> Result<result::Order, ExchangeError> {
+        let started = Instant::now();
+
+        loop {
+            let order = self.get_order(String::from(pair), order_id)?;
+            match order.status.as_str() {
+                "FILLED" => return Ok(order),
+                "CANCELED" if order.executed_qty > 0.0 => return Ok(order),
+                "CANCELED" => {
+                    return Err(ExchangeError::Rejected(format!(
+                        "order {} canceled without fills",
+                        order_id
+                    )))
+                }
+                _ if started.elapsed() >= FILL_TIMEOUT => {
+                    return Err(ExchangeError::Other(format!(
+                        "order {} not filled after {}s",
+                        order_id,
+                        FILL_TIMEOUT.as_secs()
+                    )))
+                }
+                _ => thread::sleep(FILL_POLL),
+            }
+        }
+    }
+
+    /// Waits for a market order's fill and returns it as a transaction.
+    fn market_order(
+        &self,
+        pair: String,
+        side: &str,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        let order_id = self.place_order(&pair, side, qty, None)?;
+        let order = self.wait_for_fill(&pair, order_id.parse().unwrap_or(0))?;
+
+        Ok(result::Transaction {
+            order_id: order.order_id,
+            pair: order.pair,
+            price: if order.executed_qty > 0.0 {
+                order.quote_qty / order.executed_qty
+            } else {
+                0.0
+            },
+            qty: order.executed_qty,
+            quote_qty: order.quote_qty,
+            commission: order.commission,
+            commission_asset: order.commission_asset,
+            platform: String::from(PLATFORM),
+            timestamp: order.timestamp,
+        })
+    }
+}
+
+/// OKX instrument id of a pair, e.g. `PENGU-USDT` for `PENGUUSDT`.
+fn inst_id(pair: &str) -> String {
+    if pair.contains('-') {
+        return String::from(pair);
+    }
+
+    QUOTES
+        .iter()
+        .find(|quote| pair.len() > quote.len() && pair.ends_with(*quote))
+        .map(|quote| format!("{}-{}", &pair[..pair.len() - quote.len()], quote))
+        .unwrap_or_else(|| String::from(pair))
+}
+
+impl model::Exchange for Connector {
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError> {
+        let data = self.get("/api/v5/account/balance", &[])?;
+        let details: Vec<Detail> =
+            serde_json::from_value(data[0]["details"].clone()).unwrap_or_default();
+
+        Ok(details
+            .into_iter()
+            .map(|detail| result::Balance {
+                asset: detail.ccy,
+                free: detail.avail_bal.parse().unwrap_or(0.0),
+            })
+            .filter(|balance| balance.free != 0.0)
+            .collect())
+    }
+
+    fn market_buy_using_quote_quantity(
+        &self,
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        self.market_order(pair, "buy", qty)
+    }
+
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError> {
+        self.market_order(pair, "sell", qty)
+    }
+
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError> {
+        let data = self.get("/api/v5/account/balance", &[("ccy", &asset)])?;
+        let details: Vec<Detail> =
+            serde_json::from_value(data[0]["details"].clone()).unwrap_or_default();
+
+        let free = details
+            .iter()
+            .find(|detail| detail.ccy == asset)
+            .map(|detail| detail.avail_bal.parse().unwrap_or(0.0))
+            .unwrap_or(0.0);
+
+        Ok(result::Balance { asset, free })
+    }
+
+    fn adjust_quantity(&self, pair: String, qty: f64) -> Result<f64, ExchangeError> {
+        let (lot_size, _) = self.get_lot_size(&pair)?;
+
+        Ok(round_to_step(qty, lot_size, f64::floor))
+    }
+
+    fn limit_buy(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let (lot_size, tick_size) = self.get_lot_size(&pair)?;
+        let order_id = self.place_order(
+            &pair,
+            "buy",
+            round_to_step(qty, lot_size, f64::floor),
+            Some(round_to_step(price, tick_size, f64::floor)),
+        )?;
+
+        self.get_order(pair, order_id.parse().unwrap_or(0))
+    }
+
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let (lot_size, tick_size) = self.get_lot_size(&pair)?;
+        let order_id = self.place_order(
+            &pair,
+            "sell",
+            round_to_step(qty, lot_size, f64::floor),
+            Some(round_to_step(price, tick_size, f64::floor)),
+        )?;
+
+        self.get_order(pair, order_id.parse().unwrap_or(0))
+    }
+
+    fn get_order(&self, pair: String, order_id: u64) -> Result<result::Order, ExchangeError> {
+        let data = self.get(
+            "/api/v5/trade/order",
+            &[
+                ("instId", &inst_id(&pair)),
+                ("ordId", &order_id.to_string()),
+            ],
+        )?;
+
+        Self::data::<OrderDetail>(data)?
+            .into_iter()
+            .next()
+            .map(Self::order_from_detail)
+            .ok_or_else(|| ExchangeError::Other(format!("order {} not found", order_id)))
+    }
+
+    fn cancel_order(&self, pair: String, order_id: u64) -> Result<(), ExchangeError> {
+        self.post(
+            "/api/v5/trade/cancel-order",
+            json!({
+                "instId": inst_id(&pair),
+                "ordId": order_id.to_string(),
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    fn get_open_orders(&self, pair: String) -> Result<Vec<result::Order>, ExchangeError> {
+        let data = self.get(
+            "/api/v5/trade/orders-pending",
+            &[("instType", "SPOT"), ("instId", &inst_id(&pair))],
+        )?;
+
+        Ok(Self::data::<OrderDetail>(data)?
+            .into_iter()
+            .map(Self::order_from_detail)
+            .collect())
+    }
+
+    fn get_fills(&self, pair: String) -> Result<Vec<result::Fill>, ExchangeError> {
+        let data = self.get(
+            "/api/v5/trade/fills",
+            &[("instType", "SPOT"), ("instId", &inst_id(&pair))],
+        )?;
+
+        Ok(Self::data::<Fill>(data)?
+            .into_iter()
+            .map(|fill| result::Fill {
+                order_id: fill.ord_id.parse().unwrap_or(0),
+                pair: fill.inst_id.replace('-', ""),
+                price: fill.fill_px.parse().unwrap_or(0.0),
+                qty: fill.fill_sz.parse().unwrap_or(0.0),
+                // Fees are reported as negative amounts.
+                commission: -fill.fee.parse::<f64>().unwrap_or(0.0),
+                commission_asset: fill.fee_ccy,
+                is_buyer: fill.side == "buy",
+                timestamp: fill.ts.parse().unwrap_or(0),
+            })
+            .collect())
+    }
+}
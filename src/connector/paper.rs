@@ -0,0 +1,280 @@
+use crate::model;
+use crate::model::context::Context;
+use crate::model::{paper, result, storage, ExchangeError};
+
+pub const PLATFORM: &str = "paper";
+
+/// Simulated exchange backed by the `paper_balances` table. Market orders fill
+/// at the latest collected ticker close, moved against us by the configured
+/// slippage and charged the taker fee. Limit orders rest in `paper_orders` and
+/// fill at their price once a later close crosses it. Without a collected
+/// close, market orders are refused and limit orders keep resting.
+pub struct PaperExchange {
+    context: Context,
+    base: String,
+    quote: String,
+    taker_fee: f64,
+    slippage: f64,
+}
+
+impl PaperExchange {
+    pub fn new(context: &Context, base: &str, quote: &str) -> Self {
+        let account = paper::get(context);
+
+        Self {
+            context: context.clone(),
+            base: String::from(base),
+            quote: String::from(quote),
+            taker_fee: account.taker_fee / 100.0,
+            slippage: account.slippage / 100.0,
+        }
+    }
+
+    fn place_order(
+        &self,
+        pair: String,
+        side: &str,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let (asset, amount) = if side == "BUY" {
+            (&self.quote, qty * price)
+        } else {
+            (&self.base, qty)
+        };
+
+        let free = paper::get_balance(&self.context, asset);
+        if free < amount {
+            return Err(ExchangeError::InsufficientBalance(format!(
+                "{} {} free, {} required",
+                free, asset, amount
+            )));
+        }
+        paper::update_balance(&self.context, asset, free - amount);
+
+        Ok(paper::create_order(
+            &self.context,
+            result::Order {
+                pair,
+                order_id: 0,
+                side: String::from(side),
+                price,
+                orig_qty: qty,
+                executed_qty: 0.0,
+                quote_qty: 0.0,
+                commission: 0.0,
+                commission_asset: String::new(),
+                status: String::from("NEW"),
+                timestamp: chrono::offset::Utc::now().timestamp_millis() as u64,
+            },
+        ))
+    }
+
+    /// Latest collected close of the pair, an error rather than a made-up
+    /// price when there is none.
+    fn price(&self, pair: &str) -> Result<f64, ExchangeError> {
+        match storage::find_latest_price(&self.context.ticker_path("1m"), pair) {
+            Ok(Some(ticker)) if ticker.close.is_finite() && ticker.close > 0.0 => Ok(ticker.close),
+            Ok(_) => Err(ExchangeError::Rejected(format!("no price for {}", pair))),
+            Err(e) => Err(ExchangeError::Other(format!(
+                "no price for {}: {}",
+                pair, e
+            ))),
+        }
+    }
+
+    /// Fills resting orders the latest ticker close has crossed.
+    fn fill_orders(&self, pair: &str) {
+        let close = match self.price(pair) {
+            Ok(close) => close,
+            Err(_) => return,
+        };
+
+        for mut order in paper::get_orders(&self.context, pair, "NEW") {
+            let crossed = match order.side.as_str() {
+                "BUY" => close <= order.price,
+                _ => close >= order.price,
+            };
+            if !crossed {
+                continue;
+            }
+
+            if order.side == "BUY" {
+                let base_free = paper::get_balance(&self.context, &self.base);
+                paper::update_balance(
+                    &self.context,
+                    &self.base,
+                    base_free + order.orig_qty * (1.0 - self.taker_fee),
+                );
+            } else {
+                let quote_free = paper::get_balance(&self.context, &self.quote);
+                paper::update_balance(
+                    &self.context,
+                    &self.quote,
+                    quote_free + order.orig_qty * order.price * (1.0 - self.taker_fee),
+                );
+            }
+
+            order.executed_qty = order.orig_qty;
+            order.status = String::from("FILLED");
+            order.timestamp = chrono::offset::Utc::now().timestamp_millis() as u64;
+            paper::update_order(&self.context, &order);
+        }
+    }
+
+    fn transaction(
+        &self,
+        pair: String,
+        price: f64,
+        qty: f64,
+        commission: f64,
+        commission_asset: &str,
+    ) -> result::Transaction {
+        let timestamp = chrono::offset::Utc::now().timestamp_millis() as u64;
+
+        result::Transaction {
+            order_id: timestamp,
+            pair,
+            price,
+            qty,
+            quote_qty: qty * price,
+            commission,
+            commission_asset: String::from(commission_asset),
+            platform: String::from(PLATFORM),
+            timestamp,
+        }
+    }
+}
+
+impl model::Exchange for PaperExchange {
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError> {
+        Ok(paper::get_balances(&self.context)
+            .into_iter()
+            .filter(|b| b.free != 0.0)
+            .collect())
+    }
+
+    fn market_buy_using_quote_quantity(
+        &self,
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        let price = self.price(&pair)? * (1.0 + self.slippage);
+        let base_qty = qty / price;
+
+        let quote_free = paper::get_balance(&self.context, &self.quote);
+        if quote_free < qty {
+            return Err(ExchangeError::InsufficientBalance(format!(
+                "{} {} free, {} required",
+                quote_free, self.quote, qty
+            )));
+        }
+
+        let base_free = paper::get_balance(&self.context, &self.base);
+        paper::update_balance(&self.context, &self.quote, quote_free - qty);
+        paper::update_balance(
+            &self.context,
+            &self.base,
+            base_free + base_qty * (1.0 - self.taker_fee),
+        );
+
+        Ok(self.transaction(pair, price, base_qty, base_qty * self.taker_fee, &self.base))
+    }
+
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError> {
+        let price = self.price(&pair)? * (1.0 - self.slippage);
+
+        let base_free = paper::get_balance(&self.context, &self.base);
+        let qty = qty.min(base_free);
+        let quote_free = paper::get_balance(&self.context, &self.quote);
+        paper::update_balance(&self.context, &self.base, base_free - qty);
+        paper::update_balance(
+            &self.context,
+            &self.quote,
+            quote_free + qty * price * (1.0 - self.taker_fee),
+        );
+
+        Ok(self.transaction(pair, price, qty, qty * price * self.taker_fee, &self.quote))
+    }
+
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError> {
+        Ok(result::Balance {
+            free: paper::get_balance(&self.context, &asset),
+            asset,
+        })
+    }
+
+    fn adjust_quantity(&self, _pair: String, qty: f64) -> Result<f64, ExchangeError> {
+        Ok(qty)
+    }
+
+    fn limit_buy(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        self.place_order(pair, "BUY", qty, price)
+    }
+
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        self.place_order(pair, "SELL", qty, price)
+    }
+
+    fn get_order(&self, pair: String, order_id: u64) -> Result<result::Order, ExchangeError> {
+        self.fill_orders(&pair);
+
+        match paper::get_order(&self.context, order_id) {
+            order if order.order_id == 0 => Err(ExchangeError::Rejected(format!(
+                "unknown order {}",
+                order_id
+            ))),
+            mut order => {
+                if order.status == "FILLED" && order.side == "BUY" {
+                    order.commission = order.executed_qty * self.taker_fee;
+                    order.commission_asset = self.base.clone();
+                } else if order.status == "FILLED" {
+                    order.commission = order.quote_qty * self.taker_fee;
+                    order.commission_asset = self.quote.clone();
+                }
+
+                Ok(order)
+            }
+        }
+    }
+
+    fn cancel_order(&self, _pair: String, order_id: u64) -> Result<(), ExchangeError> {
+        let mut order = paper::get_order(&self.context, order_id);
+        if order.status != "NEW" {
+            return Ok(());
+        }
+
+        if order.side == "BUY" {
+            let quote_free = paper::get_balance(&self.context, &self.quote);
+            paper::update_balance(
+                &self.context,
+                &self.quote,
+                quote_free + order.orig_qty * order.price,
+            );
+        } else {
+            let base_free = paper::get_balance(&self.context, &self.base);
+            paper::update_balance(&self.context, &self.base, base_free + order.orig_qty);
+        }
+
+        order.status = String::from("CANCELED");
+        paper::update_order(&self.context, &order);
+
+        Ok(())
+    }
+
+    fn get_open_orders(&self, pair: String) -> Result<Vec<result::Order>, ExchangeError> {
+        self.fill_orders(&pair);
+
+        Ok(paper::get_orders(&self.context, &pair, "NEW"))
+    }
+}
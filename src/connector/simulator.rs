@@ -0,0 +1,313 @@
+use crate::model;
+use crate::model::{result, ExchangeError};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub const PLATFORM: &str = "backtest";
+
+/// Shortfall put down to rounding, as the bot sums its position from trades
+/// while balances are updated fill by fill.
+const TOLERANCE: f64 = 1e-9;
+
+struct Account {
+    base: String,
+    quote: String,
+    fee: f64,
+    price: f64,
+    timestamp: u64,
+    cost: f64,
+    next_order_id: u64,
+    balances: HashMap<String, f64>,
+    orders: Vec<result::Order>,
+}
+
+impl Account {
+    fn withdraw(&mut self, asset: &str, amount: f64) -> Result<(), ExchangeError> {
+        let free = self.balances.get_mut(asset).unwrap();
+        if amount - *free > TOLERANCE * amount.max(1.0) {
+            return Err(ExchangeError::InsufficientBalance(format!(
+                "{} {} free, {} required",
+                free, asset, amount
+            )));
+        }
+
+        *free -= amount;
+        Ok(())
+    }
+
+    fn place_order(
+        &mut self,
+        pair: String,
+        side: &str,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        let (base, quote) = (self.base.clone(), self.quote.clone());
+
+        if side == "BUY" {
+            self.withdraw(&quote, qty * price)?;
+            self.cost += qty * price;
+        } else {
+            self.withdraw(&base, qty)?;
+        }
+
+        let order = result::Order {
+            pair,
+            order_id: self.next_order_id,
+            side: String::from(side),
+            price,
+            orig_qty: qty,
+            executed_qty: 0.0,
+            quote_qty: 0.0,
+            commission: 0.0,
+            commission_asset: String::new(),
+            status: String::from("NEW"),
+            timestamp: self.timestamp,
+        };
+        self.next_order_id += 1;
+        self.orders.push(order.clone());
+
+        Ok(order)
+    }
+
+    fn fill_orders(&mut self) {
+        let (base, quote) = (self.base.clone(), self.quote.clone());
+        let (price, fee, timestamp) = (self.price, self.fee, self.timestamp);
+
+        for order in self.orders.iter_mut() {
+            let crossed = match order.side.as_str() {
+                "BUY" => price <= order.price,
+                _ => price >= order.price,
+            };
+            if order.status != "NEW" || !crossed {
+                continue;
+            }
+
+            if order.side == "BUY" {
+                *self.balances.get_mut(&base).unwrap() += order.orig_qty * (1.0 - fee);
+                order.commission = order.orig_qty * fee;
+                order.commission_asset = base.clone();
+            } else {
+                *self.balances.get_mut(&quote).unwrap() +=
+                    order.orig_qty * order.price * (1.0 - fee);
+                order.commission = order.orig_qty * order.price * fee;
+                order.commission_asset = quote.clone();
+                self.cost = 0.0;
+            }
+
+            order.executed_qty = order.orig_qty;
+            order.quote_qty = order.orig_qty * order.price;
+            order.status = String::from("FILLED");
+            order.timestamp = timestamp;
+        }
+    }
+}
+
+/// In-memory exchange used to replay stored tickers. Market orders fill
+/// immediately at the price set by `set_market`, limit orders once that price
+/// crosses them, both minus the fee.
+#[derive(Clone)]
+pub struct Connector {
+    account: Rc<RefCell<Account>>,
+}
+
+impl Connector {
+    pub fn new(base: &str, quote: &str, capital: f64, fee: f64) -> Self {
+        let mut balances = HashMap::new();
+        balances.insert(String::from(base), 0.0);
+        balances.insert(String::from(quote), capital);
+
+        Self {
+            account: Rc::new(RefCell::new(Account {
+                base: String::from(base),
+                quote: String::from(quote),
+                fee: fee / 100.0,
+                price: 0.0,
+                timestamp: 0,
+                cost: 0.0,
+                next_order_id: 1,
+                balances,
+                orders: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn set_market(&self, price: f64, timestamp: u64) {
+        let mut account = self.account.borrow_mut();
+        account.price = price;
+        account.timestamp = timestamp;
+        account.fill_orders();
+    }
+
+    /// Base asset currently held, including the part locked in open sell orders.
+    pub fn position(&self) -> f64 {
+        let account = self.account.borrow();
+        let locked: f64 = account
+            .orders
+            .iter()
+            .filter(|order| order.side == "SELL" && order.status == "NEW")
+            .map(|order| order.orig_qty)
+            .sum();
+
+        account.balances[&account.base] + locked
+    }
+
+    /// Quote asset spent on the position currently held.
+    pub fn cost(&self) -> f64 {
+        self.account.borrow().cost
+    }
+
+    pub fn fee(&self) -> f64 {
+        self.account.borrow().fee
+    }
+}
+
+impl model::Exchange for Connector {
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError> {
+        let account = self.account.borrow();
+
+        Ok(account
+            .balances
+            .iter()
+            .map(|(asset, free)| result::Balance {
+                asset: asset.clone(),
+                free: *free,
+            })
+            .collect())
+    }
+
+    fn market_buy_using_quote_quantity(
+        &self,
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        let mut account = self.account.borrow_mut();
+        let price = account.price;
+        let base_qty = qty / price;
+        let (base, quote, fee) = (account.base.clone(), account.quote.clone(), account.fee);
+
+        account.withdraw(&quote, qty)?;
+        *account.balances.get_mut(&base).unwrap() += base_qty * (1.0 - fee);
+        account.cost += qty;
+        account.next_order_id += 1;
+
+        Ok(result::Transaction {
+            order_id: account.next_order_id - 1,
+            pair,
+            price,
+            qty: base_qty,
+            quote_qty: qty,
+            commission: base_qty * fee,
+            commission_asset: base,
+            platform: String::from(PLATFORM),
+            timestamp: account.timestamp,
+        })
+    }
+
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError> {
+        let mut account = self.account.borrow_mut();
+        let price = account.price;
+        let (base, quote, fee) = (account.base.clone(), account.quote.clone(), account.fee);
+
+        account.withdraw(&base, qty)?;
+        *account.balances.get_mut(&quote).unwrap() += qty * price * (1.0 - fee);
+        account.cost = 0.0;
+        account.next_order_id += 1;
+
+        Ok(result::Transaction {
+            order_id: account.next_order_id - 1,
+            pair,
+            price,
+            qty,
+            quote_qty: qty * price,
+            commission: qty * price * fee,
+            commission_asset: quote,
+            platform: String::from(PLATFORM),
+            timestamp: account.timestamp,
+        })
+    }
+
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError> {
+        let account = self.account.borrow();
+
+        Ok(result::Balance {
+            free: account.balances.get(&asset).copied().unwrap_or(0.0),
+            asset,
+        })
+    }
+
+    fn adjust_quantity(&self, _pair: String, qty: f64) -> Result<f64, ExchangeError> {
+        Ok(qty)
+    }
+
+    fn limit_buy(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        self.account
+            .borrow_mut()
+            .place_order(pair, "BUY", qty, price)
+    }
+
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        self.account
+            .borrow_mut()
+            .place_order(pair, "SELL", qty, price)
+    }
+
+    fn get_order(&self, _pair: String, order_id: u64) -> Result<result::Order, ExchangeError> {
+        let account = self.account.borrow();
+
+        account
+            .orders
+            .iter()
+            .find(|order| order.order_id == order_id)
+            .cloned()
+            .ok_or_else(|| ExchangeError::Rejected(format!("unknown order {}", order_id)))
+    }
+
+    fn cancel_order(&self, _pair: String, order_id: u64) -> Result<(), ExchangeError> {
+        let mut account = self.account.borrow_mut();
+        let (base, quote) = (account.base.clone(), account.quote.clone());
+
+        let order = match account
+            .orders
+            .iter_mut()
+            .find(|order| order.order_id == order_id && order.status == "NEW")
+        {
+            Some(order) => {
+                order.status = String::from("CANCELED");
+                order.clone()
+            }
+            None => return Ok(()),
+        };
+
+        if order.side == "BUY" {
+            *account.balances.get_mut(&quote).unwrap() += order.orig_qty * order.price;
+            account.cost -= order.orig_qty * order.price;
+        } else {
+            *account.balances.get_mut(&base).unwrap() += order.orig_qty;
+        }
+
+        Ok(())
+    }
+
+    fn get_open_orders(&self, _pair: String) -> Result<Vec<result::Order>, ExchangeError> {
+        let account = self.account.borrow();
+
+        Ok(account
+            .orders
+            .iter()
+            .filter(|order| order.status == "NEW")
+            .cloned()
+            .collect())
+    }
+}
@@ -1 +1,7 @@
+pub mod backtest;
+pub mod bot;
+pub mod connector;
 pub mod model;
+pub mod optimize;
+pub mod reconcile;
+pub mod strategy;
@@ -1,12 +1,15 @@
+mod backtest;
 mod bot;
 mod cli;
 mod connector;
 mod model;
 mod notification;
+mod optimize;
+mod reconcile;
 mod run;
+mod status;
 mod strategy;
 
-use bot::BotBuilder;
 use clap::{Parser, Subcommand};
 use connector::binance;
 use model::Exchange;
@@ -18,8 +21,17 @@ struct Args {
     #[clap(subcommand)]
     command: Option<Commands>,
 
-    #[clap(short, long, value_name = "FILE", default_value = "spearmint.db")]
-    database: String,
+    #[clap(short, long, value_name = "FILE")]
+    database: Option<String>,
+
+    #[clap(long, value_name = "FILE")]
+    config: Option<String>,
+
+    #[clap(long, value_name = "FILE")]
+    data_database: Option<String>,
+
+    #[clap(long, value_name = "FILE")]
+    ticker: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -31,8 +43,18 @@ enum Commands {
 
     Test {},
 
+    Db {
+        #[clap(subcommand)]
+        command: DbCommands,
+    },
+
+    Bind {
+        #[clap(subcommand)]
+        command: BindCommands,
+    },
+
     Account {
-        #[clap(short, long)]
+        #[clap(short, long, alias = "account")]
         platform: String,
     },
 
@@ -41,6 +63,16 @@ enum Commands {
         name: String,
     },
 
+    Orders {
+        #[clap(short, long)]
+        name: String,
+    },
+
+    Reconcile {
+        #[clap(short, long)]
+        name: String,
+    },
+
     Start {
         #[clap(short, long)]
         name: String,
@@ -57,8 +89,11 @@ enum Commands {
     },
 
     Tick {
-        #[clap(short, long, default_value = "spearmint_data.db")]
-        path: String,
+        #[clap(short, long)]
+        path: Option<String>,
+
+        #[clap(long, default_value = binance::PLATFORM)]
+        platform: String,
     },
 
     List {},
@@ -83,8 +118,8 @@ enum Commands {
         #[clap(short, long, default_value = "main")]
         name: String,
 
-        #[clap(short, long, value_name = "FILE", default_value = "spearmint.db")]
-        path: String,
+        #[clap(short, long, value_name = "FILE")]
+        path: Option<String>,
     },
 
     Run {
@@ -99,10 +134,85 @@ enum Commands {
         #[clap(short, long, default_value = "30")]
         duration: u64,
     },
+
+    Backtest {
+        #[clap(short, long)]
+        name: String,
+
+        #[clap(long)]
+        from: String,
+
+        #[clap(long)]
+        to: String,
+
+        #[clap(short, long)]
+        path: Option<String>,
+
+        #[clap(long)]
+        capital: Option<f64>,
+
+        #[clap(long, default_value = "0.1")]
+        fee: f64,
+    },
+
+    Optimize {
+        #[clap(short, long)]
+        name: String,
+
+        #[clap(long)]
+        from: String,
+
+        #[clap(long)]
+        to: String,
+
+        #[clap(short, long, parse(from_os_str), value_name = "FILE")]
+        file: PathBuf,
+
+        #[clap(short, long)]
+        path: Option<String>,
+
+        #[clap(long, default_value = "0.1")]
+        fee: f64,
+
+        #[clap(short, long, parse(from_os_str), value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommands {
+    Migrate {},
+
+    Status {},
+}
+
+#[derive(Subcommand)]
+enum BindCommands {
+    List {},
+
+    Remove {
+        #[clap(short, long)]
+        account: String,
+    },
+
+    Rotate {},
 }
 
 fn main() {
     let args = Args::parse();
+    let ctx = model::context::Context::load(
+        args.config.clone(),
+        args.database.clone(),
+        args.data_database.clone(),
+        args.ticker.clone(),
+    );
+
+    if !matches!(args.command, Some(Commands::Db { .. })) {
+        if let Err(e) = model::migration::migrate_all(&ctx) {
+            println!("error: {}", e);
+            return;
+        }
+    }
 
     match &args.command {
         Some(Commands::Apply { file }) => {
@@ -112,88 +222,166 @@ fn main() {
 
                 if kind.kind == "bot" {
                     let bot: model::result::Bot = toml::from_str(&content).unwrap();
-                    let config = model::bot::save(bot);
-                    cli::display_bot(config);
+                    let checked = connector::check(&bot.platform)
+                        .and(strategy::build(&bot).map(|_| ()))
+                        .and(
+                            model::indicator::Pipeline::new(&model::indicator::with_mfi(&bot))
+                                .map(|_| ()),
+                        );
+                    match checked {
+                        Ok(()) => {
+                            if let Ok(min_notional) =
+                                connector::min_notional(&ctx, &bot.account(), &bot.pair)
+                            {
+                                if bot.parameters.first_buy_in < min_notional {
+                                    println!(
+                                        "warning: first_buy_in {} is below the {} minimum notional of {}",
+                                        bot.parameters.first_buy_in, bot.pair, min_notional
+                                    );
+                                }
+                            }
+
+                            cli::display_bot(model::bot::save(&ctx, bot))
+                        }
+                        Err(e) => println!("error: {}", e),
+                    }
                 } else if kind.kind == "bind" {
                     let binding: model::args::ApiCredential = toml::from_str(&content).unwrap();
-                    let api = model::bind::save(binding);
-                    cli::display_bind(api);
+                    match model::bind::save(&ctx, binding) {
+                        Ok(api) => cli::display_bind(api),
+                        Err(e) => println!("error: {}", e),
+                    }
+                } else if kind.kind == "paper" {
+                    let account: model::args::PaperAccount = toml::from_str(&content).unwrap();
+                    let account = model::paper::save(&ctx, account);
+                    cli::display_paper_account(account);
                 }
             }
         }
 
-        Some(Commands::Status { name }) => {
-            println!("args {}", args.database);
-            println!("Status ID: {}", name);
-        }
+        Some(Commands::Db { command }) => match command {
+            DbCommands::Migrate {} => match model::migration::migrate_all(&ctx) {
+                Ok(databases) => {
+                    for (path, applied) in databases {
+                        match applied.last() {
+                            Some(version) => println!("{} migrated to version {}", path, version),
+                            None => println!("{} is up to date", path),
+                        }
+                    }
+                }
+                Err(e) => println!("error: {}", e),
+            },
+            DbCommands::Status {} => {
+                for (path, migrations) in model::migration::databases(&ctx) {
+                    match model::migration::status(&path, migrations) {
+                        Ok(status) => cli::display_migrations(&path, status),
+                        Err(e) => println!("error: {}", e),
+                    }
+                }
+            }
+        },
+
+        Some(Commands::Bind { command }) => match command {
+            BindCommands::List {} => cli::display_bindings(model::bind::stored(&ctx)),
+            BindCommands::Remove { account } => model::bind::remove(&ctx, account),
+            BindCommands::Rotate {} => match model::bind::rotate(&ctx) {
+                Ok(total) => println!("{} bindings encrypted with the new passphrase!", total),
+                Err(e) => println!("error: {}", e),
+            },
+        },
+
+        Some(Commands::Status { name }) => match model::bot::get(&ctx, name) {
+            Ok(bot) => cli::display_status(&bot, status::get(&ctx, &bot)),
+            Err(e) => println!("error: {}", e),
+        },
 
         Some(Commands::Start { name }) => {
-            model::start(name);
+            model::start(&ctx, name);
         }
 
         Some(Commands::Stop { name }) => {
-            model::stop(name);
+            model::stop(&ctx, name);
         }
 
         Some(Commands::Delete { name }) => {
-            model::delete(name);
+            model::delete(&ctx, name);
         }
 
-        Some(Commands::List {}) => match model::bot::all() {
+        Some(Commands::List {}) => match model::bot::all(&ctx) {
             Ok(bots) => cli::display_bots(bots),
             Err(e) => println!("error: {}", e),
         },
 
-        Some(Commands::Bot { name }) => match model::bot::get(name) {
+        Some(Commands::Bot { name }) => match model::bot::get(&ctx, name) {
             Ok(bot) => cli::display_bot(bot),
             Err(e) => println!("error: {}", e),
         },
 
         Some(Commands::Setup { path, name }) => {
             if name == "main" {
-                model::setup(path).expect("Failed to create database");
+                let path = path.clone().unwrap_or_else(|| ctx.db_path.clone());
+                model::setup(&path).expect("Failed to create database");
             } else if name == "data" {
-                model::setup_price(path).expect("Failed to create database");
+                let path = path.clone().unwrap_or_else(|| ctx.ticker_path("1m"));
+                model::setup_price(&path).expect("Failed to create database");
             }
         }
 
-        Some(Commands::Run { name, duration }) => match model::bot::get(name) {
+        Some(Commands::Run { name, duration }) => match model::bot::get(&ctx, name) {
+            Ok(bot) => run::run(or_exit(run::build(&ctx, &bot)), *duration),
+            Err(e) => println!("error: {}", e),
+        },
+
+        Some(Commands::Orders { name }) => match model::bot::get(&ctx, name) {
             Ok(bot) => {
-                let strategy = strategy::helldiver::HellDiverStrategy {
-                    first_buy_in: bot.parameters.first_buy_in,
-                    entry: bot.parameters.entry,
-                    take_profit: bot.parameters.take_profit,
-                    margin_configuration: bot.margin.margin_configuration,
-                };
-                let credential = model::bind::get(&bot.platform);
-                let account =
-                    binance::Connector::from_credential(credential.api, credential.secret);
-                let bot =
-                    BotBuilder::<binance::Connector, strategy::helldiver::HellDiverStrategy>::new()
-                        .with_info(bot::BotInfo {
-                            platform: bot.platform,
-                            pair: bot.pair,
-                            base: bot.base,
-                            quote: bot.quote,
-                        })
-                        .with_strategy(strategy)
-                        .with_connector(account)
-                        .build();
-
-                run::run(bot, *duration);
+                let account = or_exit(connector::build(
+                    &ctx,
+                    &bot.account(),
+                    &bot.base,
+                    &bot.quote,
+                ));
+                match account.get_open_orders(bot.pair) {
+                    Ok(orders) => cli::display_orders(orders),
+                    Err(e) => println!("error: {}", e),
+                }
             }
             Err(e) => println!("error: {}", e),
         },
 
-        Some(Commands::Account { platform }) => {
-            let credential = model::bind::get(platform);
-            let account = binance::Connector::from_credential(credential.api, credential.secret);
+        Some(Commands::Reconcile { name }) => match model::bot::get(&ctx, name) {
+            Ok(bot) => {
+                let account = or_exit(connector::build(
+                    &ctx,
+                    &bot.account(),
+                    &bot.base,
+                    &bot.quote,
+                ));
+                match account.get_fills(bot.pair.clone()) {
+                    Ok(fills) => {
+                        let discrepancies = reconcile::run(&ctx, &bot, &fills);
+
+                        if discrepancies.is_empty() {
+                            println!("{} is in sync with {}", name, bot.platform);
+                        } else {
+                            cli::display_reconciliation(&discrepancies);
+                            reconcile::import(&ctx, discrepancies);
+                        }
+                    }
+                    Err(e) => println!("error: {}", e),
+                }
+            }
+            Err(e) => println!("error: {}", e),
+        },
 
-            cli::display_balances(account.get_balances());
+        Some(Commands::Account { platform }) => {
+            match or_exit(connector::build(&ctx, platform, "", "")).get_balances() {
+                Ok(balances) => cli::display_balances(balances),
+                Err(e) => println!("error: {}", e),
+            }
         }
 
         Some(Commands::Test {}) => {
-            let credential = model::bind::get("binance");
+            let credential = or_exit(model::bind::get(&ctx, "binance"));
             let account = binance::Connector::from_credential(credential.api, credential.secret);
             account.fetch_server_time();
             account.get_today_pnl();
@@ -204,19 +392,95 @@ fn main() {
             chat_id,
             duration,
         }) => {
-            notification::telegram::run(token.clone(), *chat_id, *duration);
+            notification::telegram::run(&ctx, token.clone(), *chat_id, *duration);
         }
 
-        Some(Commands::Tick { path }) => {
-            model::ticker::run(path);
+        Some(Commands::Tick { path, platform }) => {
+            let mut ctx = ctx.clone();
+            if let Some(path) = path {
+                ctx.data_path = path.clone();
+            }
+            model::ticker::collect(&ctx, "1m", platform);
         }
 
         Some(Commands::RunAll { duration }) => {
-            let bots = model::bot::active().unwrap();
+            let bots = model::bot::active(&ctx).unwrap();
 
-            run::run_all(bots, *duration);
+            run::run_all(&ctx, bots, *duration);
         }
 
+        Some(Commands::Backtest {
+            name,
+            from,
+            to,
+            path,
+            capital,
+            fee,
+        }) => match model::bot::get(&ctx, name) {
+            Ok(bot) => {
+                let path = path.clone().unwrap_or_else(|| ctx.ticker_path("1m"));
+                let from = backtest::parse_time(from).expect("Invalid --from time");
+                let to = backtest::parse_time(to).expect("Invalid --to time");
+                let tickers = backtest::tickers(&path, &bot, from, to);
+                let capital = capital.unwrap_or_else(|| backtest::required_capital(&bot));
+
+                match backtest::run(&bot, &tickers, capital, *fee) {
+                    Ok(reports) => cli::display_backtest(reports),
+                    Err(e) => println!("error: {}", e),
+                }
+            }
+            Err(e) => println!("error: {}", e),
+        },
+
+        Some(Commands::Optimize {
+            name,
+            from,
+            to,
+            file,
+            path,
+            fee,
+            output,
+        }) => match model::bot::get(&ctx, name) {
+            Ok(bot) => {
+                let content = std::fs::read_to_string(file).unwrap();
+                let sweep: model::args::Sweep = toml::from_str(&content).unwrap();
+                let path = path.clone().unwrap_or_else(|| ctx.ticker_path("1m"));
+                let from = backtest::parse_time(from).expect("Invalid --from time");
+                let to = backtest::parse_time(to).expect("Invalid --to time");
+                let tickers = backtest::tickers(&path, &bot, from, to);
+
+                let outcomes = match optimize::run(&bot, &tickers, &sweep, *fee) {
+                    Ok(outcomes) => outcomes,
+                    Err(e) => {
+                        println!("error: {}", e);
+                        return;
+                    }
+                };
+                cli::display_optimization(&outcomes, 10);
+
+                if let Some(best) = outcomes.first() {
+                    let content = optimize::to_toml(&best.config);
+                    let output = output.clone().unwrap_or_else(|| {
+                        PathBuf::from(format!("bot.{}.optimized.toml", bot.pair.to_lowercase()))
+                    });
+
+                    std::fs::write(&output, &content).unwrap();
+                    println!("{}", content);
+                    println!("Best configuration saved to {}", output.display());
+                }
+            }
+            Err(e) => println!("error: {}", e),
+        },
+
         None => {}
     }
 }
+
+/// Value a command can't go on without, or the process exits with its error,
+/// e.g. on a wrong passphrase.
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        println!("error: {}", e);
+        std::process::exit(1)
+    })
+}
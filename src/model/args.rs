@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Kind {
@@ -28,6 +29,8 @@ pub struct Parameters {
     pub first_buy_in: f64,
     pub entry: OpenCriteria,
     pub take_profit: CloseCriteria,
+    #[serde(default)]
+    pub stop_loss: Option<StopLoss>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -48,6 +51,15 @@ pub struct OpenCriteria {
 pub struct CloseCriteria {
     pub price_change_above: f64,
     pub price_callback: f64,
+    #[serde(default)]
+    pub limit_order: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StopLoss {
+    pub price_change_below: Option<f64>,
+    pub top_price_change_below: Option<f64>,
+    pub max_cycle_hours: Option<f64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -55,4 +67,52 @@ pub struct ApiCredential {
     pub api_key: String,
     pub secret_key: String,
     pub platform: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PaperAccount {
+    pub taker_fee: f64,
+    pub slippage: f64,
+    pub balances: HashMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Range {
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Sweep {
+    #[serde(default = "default_sweep_mode")]
+    pub mode: String,
+    #[serde(default = "default_sweep_samples")]
+    pub samples: usize,
+    #[serde(default = "default_sweep_rank")]
+    pub rank_by: String,
+    pub first_buy_in: Option<Range>,
+    pub entry_price_change_below: Option<Range>,
+    pub take_profit_price_change_above: Option<Range>,
+    pub mfi_below: Option<Range>,
+    #[serde(default)]
+    pub amount_ratio: Vec<Range>,
+}
+
+fn default_sweep_mode() -> String {
+    String::from("grid")
+}
+
+fn default_sweep_samples() -> usize {
+    100
+}
+
+fn default_sweep_rank() -> String {
+    String::from("pnl")
 }
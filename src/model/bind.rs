@@ -1,31 +1,74 @@
-use super::{args, result};
-use rusqlite::{params, Connection, Result};
+use super::{args, context::Context, result, secret};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use rusqlite::{params, Result};
+use std::env;
 
-pub fn save(api: args::ApiCredential) -> result::ApiCredential {
-    let conn = Connection::open(super::DB_PATH).unwrap();
+pub const DEFAULT_ACCOUNT: &str = "main";
+pub const MAINNET: &str = "mainnet";
+pub const TESTNET: &str = "testnet";
 
-    let mut stmt = conn
-        .prepare("SELECT * FROM bindings WHERE platform=:platform LIMIT 1")
-        .unwrap();
+/// Splits an account such as `binance:sub1` into platform and name. A bare
+/// platform is its `main` account.
+pub fn parse_account(account: &str) -> (&str, &str) {
+    account
+        .split_once(':')
+        .unwrap_or((account, DEFAULT_ACCOUNT))
+}
 
-    let apis: Vec<Result<result::ApiCredential>> = stmt
-        .query_map([&api.platform], |row| {
-            Ok(result::ApiCredential {
-                api: row.get(1)?,
-                secret: row.get(2)?,
-                platform: row.get(3)?,
-            })
-        })
-        .unwrap()
-        .collect();
+/// Secret keys and API passphrases are encrypted with the passphrase before
+/// they are stored. The passphrase has to match the one of the bindings
+/// already stored.
+pub fn save(ctx: &Context, api: args::ApiCredential) -> Result<result::ApiCredential, String> {
+    let name = api.name.unwrap_or_else(|| String::from(DEFAULT_ACCOUNT));
+    let environment = api.environment.unwrap_or_else(|| String::from(MAINNET));
+    if environment != MAINNET && environment != TESTNET {
+        return Err(format!(
+            "unknown environment `{}`, expected {} or {}",
+            environment, MAINNET, TESTNET
+        ));
+    }
+    if environment == TESTNET && api.platform != crate::connector::binance::PLATFORM {
+        return Err(format!("{} has no testnet", api.platform));
+    }
+
+    let stored = stored(ctx);
+    let encrypted = stored
+        .iter()
+        .find(|binding| secret::is_encrypted(&binding.secret));
+    let passphrase = secret::passphrase(encrypted.is_none());
+
+    if let Some(binding) = encrypted {
+        if secret::decrypt(passphrase, &binding.secret).is_none() {
+            return Err(String::from(
+                "passphrase does not match the stored bindings",
+            ));
+        }
+    }
 
-    if apis.iter().count() != 0 {
+    let secret_key = secret::encrypt(passphrase, &api.secret_key);
+    let api_passphrase = api.passphrase.unwrap_or_default();
+    let encrypted_api_passphrase = encrypt_optional(passphrase, &api_passphrase);
+    let conn = ctx.conn();
+
+    if stored
+        .iter()
+        .any(|binding| binding.platform == api.platform && binding.name == name)
+    {
         conn.execute(
             "UPDATE bindings SET
                 api_key=?1,
-                secret_key=?2
-            WHERE platform=?3",
-            params![api.api_key, api.secret_key, api.platform],
+                secret_key=?2,
+                passphrase=?3,
+                environment=?4
+            WHERE platform=?5 AND name=?6",
+            params![
+                api.api_key,
+                secret_key,
+                encrypted_api_passphrase,
+                environment,
+                api.platform,
+                name
+            ],
         )
         .unwrap();
     } else {
@@ -33,41 +76,207 @@ pub fn save(api: args::ApiCredential) -> result::ApiCredential {
             "INSERT INTO bindings (
                 api_key,
                 secret_key,
-                platform
-            ) VALUES (?1, ?2, ?3)",
-            params![api.api_key, api.secret_key, api.platform],
+                platform,
+                name,
+                passphrase,
+                environment
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                api.api_key,
+                secret_key,
+                api.platform,
+                name,
+                encrypted_api_passphrase,
+                environment
+            ],
         )
         .unwrap();
     }
 
-    result::ApiCredential {
+    Ok(result::ApiCredential {
         api: api.api_key,
         secret: api.secret_key,
         platform: api.platform,
+        name,
+        passphrase: api_passphrase,
+        environment,
+    })
+}
+
+/// Empty values, such as the passphrase of a platform that has none, are
+/// stored as they are.
+fn encrypt_optional(passphrase: &str, value: &str) -> String {
+    if value.is_empty() {
+        String::new()
+    } else {
+        secret::encrypt(passphrase, value)
     }
 }
 
-pub fn get(platform: &str) -> result::ApiCredential {
-    let conn = Connection::open(super::DB_PATH).unwrap();
+/// Bindings as stored, with secret keys and API passphrases still encrypted.
+pub fn stored(ctx: &Context) -> Vec<result::ApiCredential> {
+    let conn = ctx.conn();
     let mut stmt = conn
         .prepare(
-            "SELECT api_key, secret_key, platform FROM bindings WHERE platform=:platform LIMIT 1",
+            "SELECT api_key, secret_key, platform, name, COALESCE(passphrase, ''),
+                COALESCE(environment, 'mainnet')
+            FROM bindings ORDER BY platform, name",
         )
         .unwrap();
-    let mut bindings: Vec<Result<result::ApiCredential>> = stmt
-        .query_map([platform], |row| {
+    let bindings: Vec<Result<result::ApiCredential>> = stmt
+        .query_map([], |row| {
             Ok(result::ApiCredential {
                 api: row.get(0)?,
                 secret: row.get(1)?,
                 platform: row.get(2)?,
+                name: row.get(3)?,
+                passphrase: row.get(4)?,
+                environment: row.get(5)?,
             })
         })
         .unwrap()
         .collect();
 
-    if bindings.len() > 0 {
-        bindings.remove(0).unwrap()
+    bindings
+        .into_iter()
+        .map(|binding| binding.unwrap())
+        .collect()
+}
+
+/// Keys from `SPEARMINT_<PLATFORM>_API_KEY` and `SPEARMINT_<PLATFORM>_SECRET_KEY`
+/// for the main account, `SPEARMINT_<PLATFORM>_<NAME>_API_KEY` and so on for the others.
+/// `SPEARMINT_<PLATFORM>_API_PASSPHRASE` and `SPEARMINT_<PLATFORM>_ENVIRONMENT`
+/// are optional.
+fn from_env(platform: &str, name: &str) -> Option<result::ApiCredential> {
+    let prefix = if name == DEFAULT_ACCOUNT {
+        format!("SPEARMINT_{}", platform)
     } else {
-        Default::default()
+        format!("SPEARMINT_{}_{}", platform, name)
+    }
+    .to_uppercase();
+
+    Some(result::ApiCredential {
+        api: env::var(format!("{}_API_KEY", prefix)).ok()?,
+        secret: env::var(format!("{}_SECRET_KEY", prefix)).ok()?,
+        platform: String::from(platform),
+        name: String::from(name),
+        passphrase: env::var(format!("{}_API_PASSPHRASE", prefix)).unwrap_or_default(),
+        environment: env::var(format!("{}_ENVIRONMENT", prefix))
+            .unwrap_or_else(|_| String::from(MAINNET)),
+    })
+}
+
+/// Environment of the account, `mainnet` unless its binding says otherwise.
+/// Unlike `get`, no secret is decrypted.
+pub fn environment(ctx: &Context, account: &str) -> String {
+    let (platform, name) = parse_account(account);
+    if let Some(credential) = from_env(platform, name) {
+        return credential.environment;
     }
+
+    stored(ctx)
+        .into_iter()
+        .find(|binding| binding.platform == platform && binding.name == name)
+        .map(|binding| binding.environment)
+        .unwrap_or_else(|| String::from(MAINNET))
+}
+
+/// Decrypted keys of the account, e.g. `binance:sub1` or `binance`. Keys set
+/// in the environment take precedence over the stored binding. Fails when the
+/// passphrase doesn't decrypt the binding.
+pub fn get(ctx: &Context, account: &str) -> Result<result::ApiCredential, String> {
+    let (platform, name) = parse_account(account);
+    if let Some(credential) = from_env(platform, name) {
+        return Ok(credential);
+    }
+
+    let mut binding = match stored(ctx)
+        .into_iter()
+        .find(|binding| binding.platform == platform && binding.name == name)
+    {
+        Some(binding) => binding,
+        None => return Ok(Default::default()),
+    };
+
+    for value in [&mut binding.secret, &mut binding.passphrase] {
+        if secret::is_encrypted(value) {
+            *value = secret::decrypt(secret::passphrase(false), value)
+                .ok_or_else(|| String::from("invalid passphrase"))?;
+        }
+    }
+
+    Ok(binding)
+}
+
+pub fn remove(ctx: &Context, account: &str) {
+    let (platform, name) = parse_account(account);
+
+    if Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Do you really want to continue?")
+        .default(true)
+        .interact()
+        .unwrap()
+    {
+        let conn = ctx.conn();
+        conn.execute(
+            "DELETE FROM bindings WHERE platform=?1 AND name=?2",
+            params![platform, name],
+        )
+        .unwrap();
+        println!("{} binding removed!", account);
+    } else {
+        println!("Ok, nevermind then");
+    }
+}
+
+/// Encrypts every stored secret key and API passphrase again under a new
+/// passphrase, including keys stored in plaintext before encryption was
+/// introduced.
+pub fn rotate(ctx: &Context) -> Result<usize, String> {
+    let stored = stored(ctx);
+    let passphrase = if stored
+        .iter()
+        .any(|binding| secret::is_encrypted(&binding.secret))
+    {
+        secret::passphrase(false)
+    } else {
+        ""
+    };
+
+    let mut secrets: Vec<(String, String, String, String)> = Vec::new();
+    for binding in stored {
+        match (
+            secret::decrypt(passphrase, &binding.secret),
+            secret::decrypt(passphrase, &binding.passphrase),
+        ) {
+            (Some(plaintext), Some(api_passphrase)) => {
+                secrets.push((binding.platform, binding.name, plaintext, api_passphrase))
+            }
+            _ => {
+                return Err(format!(
+                    "cannot decrypt {}:{} binding: wrong passphrase",
+                    binding.platform, binding.name
+                ))
+            }
+        }
+    }
+
+    let new_passphrase = secret::new_passphrase();
+    ctx.transaction(|| {
+        for (platform, name, plaintext, api_passphrase) in secrets.iter() {
+            ctx.conn()
+                .execute(
+                    "UPDATE bindings SET secret_key=?1, passphrase=?2 WHERE platform=?3 AND name=?4",
+                    params![
+                        secret::encrypt(&new_passphrase, plaintext),
+                        encrypt_optional(&new_passphrase, api_passphrase),
+                        platform,
+                        name
+                    ],
+                )
+                .unwrap();
+        }
+
+        Ok(secrets.len())
+    })
 }
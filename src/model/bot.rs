@@ -1,21 +1,21 @@
-use super::result;
-use rusqlite::{params, Connection, Result};
+use super::{context::Context, indicator, result};
+use rusqlite::{params, Result, Row};
 
-pub fn save(config: result::Bot) -> result::Bot {
-    let conn = Connection::open(super::DB_PATH).unwrap();
+/// Saves the bot under its title, updating the bot already named so. Bots on
+/// the same pair are kept apart.
+pub fn save(ctx: &Context, mut config: result::Bot) -> result::Bot {
+    let conn = ctx.conn();
 
     let mut stmt = conn
-        .prepare("SELECT id FROM bots WHERE pair=:pair AND platform=:platform LIMIT 1")
+        .prepare("SELECT id FROM bots WHERE title=:title LIMIT 1")
         .unwrap();
-    let pair = &config.pair;
-    let platform = &config.platform;
     let bots: Vec<Result<u64>> = stmt
-        .query_map([pair, platform], |row| Ok(row.get(0)?))
+        .query_map([&config.title], |row| Ok(row.get(0)?))
         .unwrap()
         .collect();
 
     if bots.iter().count() != 0 {
-        let bot = bots.into_iter().next().unwrap();
+        config.id = bots.into_iter().next().unwrap().unwrap();
         conn.execute(
             "UPDATE bots SET
                 title=?1,
@@ -28,8 +28,13 @@ pub fn save(config: result::Bot) -> result::Bot {
                 first_buy_in=?8,
                 entry=?9,
                 take_profit=?10,
-                margin=?11
-            WHERE id=?12",
+                margin=?11,
+                stop_loss=?12,
+                account=?13,
+                grid=?14,
+                indicators=?15,
+                mfi_period=?16
+            WHERE id=?17",
             params![
                 config.title,
                 config.pair,
@@ -42,7 +47,12 @@ pub fn save(config: result::Bot) -> result::Bot {
                 serde_json::to_string(&config.parameters.entry).unwrap(),
                 serde_json::to_string(&config.parameters.take_profit).unwrap(),
                 serde_json::to_string(&config.margin.margin_configuration).unwrap(),
-                bot.unwrap()
+                serde_json::to_string(&config.parameters.stop_loss).unwrap(),
+                config.account,
+                serde_json::to_string(&config.parameters.grid).unwrap(),
+                serde_json::to_string(&config.indicators).unwrap(),
+                config.parameters.mfi_period,
+                config.id
             ],
         )
         .unwrap();
@@ -60,8 +70,13 @@ pub fn save(config: result::Bot) -> result::Bot {
                 entry,
                 take_profit,
                 margin,
-                status
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 'PAUSED')",
+                status,
+                stop_loss,
+                account,
+                grid,
+                indicators,
+                mfi_period
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 'PAUSED', ?12, ?13, ?14, ?15, ?16)",
             params![
                 config.title,
                 config.pair,
@@ -73,78 +88,73 @@ pub fn save(config: result::Bot) -> result::Bot {
                 config.parameters.first_buy_in,
                 serde_json::to_string(&config.parameters.entry).unwrap(),
                 serde_json::to_string(&config.parameters.take_profit).unwrap(),
-                serde_json::to_string(&config.margin.margin_configuration).unwrap()
+                serde_json::to_string(&config.margin.margin_configuration).unwrap(),
+                serde_json::to_string(&config.parameters.stop_loss).unwrap(),
+                config.account,
+                serde_json::to_string(&config.parameters.grid).unwrap(),
+                serde_json::to_string(&config.indicators).unwrap(),
+                config.parameters.mfi_period
             ],
         )
         .unwrap();
+        config.id = conn.last_insert_rowid() as u64;
     }
 
     config
 }
 
-pub fn get(name: &str) -> Result<result::Bot> {
-    let conn = Connection::open(super::DB_PATH).unwrap();
+fn read_bot(row: &Row) -> Result<result::Bot> {
+    let entry: String = row.get(9)?;
+    let take_profit: String = row.get(10)?;
+    let margin_configuration: String = row.get(11)?;
+    let stop_loss: Option<String> = row.get(13)?;
+    let grid: Option<String> = row.get(15)?;
+    let indicators: Option<String> = row.get(16)?;
+
+    Ok(result::Bot {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        pair: row.get(2)?,
+        base: row.get(3)?,
+        quote: row.get(4)?,
+        platform: row.get(5)?,
+        strategy: row.get(6)?,
+        parameters: result::Parameters {
+            cycle: row.get(7)?,
+            first_buy_in: row.get(8)?,
+            mfi_period: row
+                .get::<_, Option<usize>>(17)?
+                .unwrap_or(indicator::MFI_PERIOD),
+            entry: serde_json::from_str(&entry).unwrap(),
+            take_profit: serde_json::from_str(&take_profit).unwrap(),
+            stop_loss: stop_loss.and_then(|value| serde_json::from_str(&value).unwrap()),
+            grid: grid.and_then(|value| serde_json::from_str(&value).unwrap()),
+        },
+        margin: result::Margin {
+            margin_configuration: serde_json::from_str(&margin_configuration).unwrap(),
+        },
+        status: row.get(12)?,
+        account: row.get::<_, Option<String>>(14)?.unwrap_or_default(),
+        indicators: indicators
+            .map(|value| serde_json::from_str(&value).unwrap())
+            .unwrap_or_default(),
+    })
+}
+
+pub fn get(ctx: &Context, name: &str) -> Result<result::Bot> {
+    let conn = ctx.conn();
     let mut stmt = conn
         .prepare("SELECT * FROM bots WHERE title=:name LIMIT 1")
         .unwrap();
-    let mut bots: Vec<Result<result::Bot>> = stmt
-        .query_map([name], |row| {
-            let entry: String = row.get(9)?;
-            let take_profit: String = row.get(10)?;
-            let margin_configuration: String = row.get(11)?;
-
-            Ok(result::Bot {
-                title: row.get(1)?,
-                pair: row.get(2)?,
-                base: row.get(3)?,
-                quote: row.get(4)?,
-                platform: row.get(5)?,
-                strategy: row.get(6)?,
-                parameters: result::Parameters {
-                    cycle: row.get(7)?,
-                    first_buy_in: row.get(8)?,
-                    entry: serde_json::from_str(&entry).unwrap(),
-                    take_profit: serde_json::from_str(&take_profit).unwrap(),
-                },
-                margin: result::Margin {
-                    margin_configuration: serde_json::from_str(&margin_configuration).unwrap(),
-                },
-                status: row.get(12)?,
-            })
-        })
-        .unwrap()
-        .collect();
+    let mut bots: Vec<Result<result::Bot>> = stmt.query_map([name], read_bot).unwrap().collect();
 
     bots.remove(0)
 }
 
-pub fn all() -> Result<Vec<result::Bot>> {
-    let conn = Connection::open(super::DB_PATH).unwrap();
+pub fn all(ctx: &Context) -> Result<Vec<result::Bot>> {
+    let conn = ctx.conn();
     let mut stmt = conn.prepare("SELECT * FROM bots")?;
-    let bots = stmt.query_map([], |row| {
-        let entry: String = row.get(9)?;
-        let take_profit: String = row.get(10)?;
-        let margin_configuration: String = row.get(11)?;
-
-        Ok(result::Bot {
-            title: row.get(1)?,
-            pair: row.get(2)?,
-            base: row.get(3)?,
-            quote: row.get(4)?,
-            platform: row.get(5)?,
-            strategy: row.get(6)?,
-            parameters: result::Parameters {
-                cycle: row.get(7)?,
-                first_buy_in: row.get(8)?,
-                entry: serde_json::from_str(&entry).unwrap(),
-                take_profit: serde_json::from_str(&take_profit).unwrap(),
-            },
-            margin: result::Margin {
-                margin_configuration: serde_json::from_str(&margin_configuration).unwrap(),
-            },
-            status: row.get(12)?,
-        })
-    })?;
+    let bots = stmt.query_map([], read_bot)?;
 
     let mut result: Vec<result::Bot> = Vec::new();
     for bot in bots {
@@ -155,33 +165,10 @@ pub fn all() -> Result<Vec<result::Bot>> {
 }
 
 #[allow(dead_code)]
-pub fn active() -> Result<Vec<result::Bot>> {
-    let conn = Connection::open(super::DB_PATH).unwrap();
+pub fn active(ctx: &Context) -> Result<Vec<result::Bot>> {
+    let conn = ctx.conn();
     let mut stmt = conn.prepare("SELECT * FROM bots WHERE status='ACTIVE'")?;
-    let bots = stmt.query_map([], |row| {
-        let entry: String = row.get(9)?;
-        let take_profit: String = row.get(10)?;
-        let margin_configuration: String = row.get(11)?;
-
-        Ok(result::Bot {
-            title: row.get(1)?,
-            pair: row.get(2)?,
-            base: row.get(3)?,
-            quote: row.get(4)?,
-            platform: row.get(5)?,
-            strategy: row.get(6)?,
-            parameters: result::Parameters {
-                cycle: row.get(7)?,
-                first_buy_in: row.get(8)?,
-                entry: serde_json::from_str(&entry).unwrap(),
-                take_profit: serde_json::from_str(&take_profit).unwrap(),
-            },
-            margin: result::Margin {
-                margin_configuration: serde_json::from_str(&margin_configuration).unwrap(),
-            },
-            status: row.get(12)?,
-        })
-    })?;
+    let bots = stmt.query_map([], read_bot)?;
 
     let mut result: Vec<result::Bot> = Vec::new();
     for bot in bots {
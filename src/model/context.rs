@@ -0,0 +1,176 @@
+use super::migration;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::{env, fs};
+
+pub const CONFIG_PATH: &str = "spearmint.toml";
+pub const DB_PATH: &str = "spearmint.db";
+pub const DB_DATA_PATH: &str = "spearmint_data.db";
+/// Milliseconds a write waits for another process holding the database lock.
+const BUSY_TIMEOUT: u64 = 5000;
+
+/// Database locations shared by every storage call. Each path is taken from
+/// its flag, then its `SPEARMINT_*` environment variable, then the `[storage]`
+/// table of the config file, then the default file name.
+///
+/// Clones share one connection to the main database, opened on first use in
+/// WAL mode.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub db_path: String,
+    pub data_path: String,
+    pub ticker_paths: HashMap<String, String>,
+    conn: Arc<OnceLock<Mutex<Connection>>>,
+}
+
+/// Open transaction on the shared connection, rolled back when dropped
+/// without `commit`, including on panic.
+struct Transaction<'a> {
+    ctx: &'a Context,
+    done: bool,
+}
+
+impl Transaction<'_> {
+    fn commit(mut self) {
+        self.done = true;
+        self.ctx.conn().execute_batch("COMMIT").unwrap();
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.ctx.conn().execute_batch("ROLLBACK");
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct File {
+    #[serde(default)]
+    storage: Storage,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Storage {
+    database: Option<String>,
+    data_database: Option<String>,
+    #[serde(default)]
+    tickers: HashMap<String, String>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            db_path: String::from(DB_PATH),
+            data_path: String::from(DB_DATA_PATH),
+            ticker_paths: HashMap::new(),
+            conn: Default::default(),
+        }
+    }
+}
+
+impl Context {
+    /// `config` is the config file flag, `database` the main database flag,
+    /// `data_database` the data database flag and `ticker` the 1m ticker
+    /// database flag.
+    pub fn load(
+        config: Option<String>,
+        database: Option<String>,
+        data_database: Option<String>,
+        ticker: Option<String>,
+    ) -> Self {
+        let config = config
+            .or_else(|| env::var("SPEARMINT_CONFIG").ok())
+            .unwrap_or_else(|| String::from(CONFIG_PATH));
+        let file: File = match fs::read_to_string(&config) {
+            Ok(content) => toml::from_str(&content).expect("Invalid config file"),
+            Err(_) => Default::default(),
+        };
+
+        let mut ticker_paths = file.storage.tickers;
+        for (key, value) in env::vars() {
+            if let Some(interval) = key
+                .strip_prefix("SPEARMINT_TICKER_")
+                .and_then(|key| key.strip_suffix("_DATABASE"))
+            {
+                ticker_paths.insert(interval.to_lowercase(), value);
+            }
+        }
+        if let Some(path) = ticker {
+            ticker_paths.insert(String::from("1m"), path);
+        }
+
+        Self {
+            db_path: database
+                .or_else(|| env::var("SPEARMINT_DATABASE").ok())
+                .or(file.storage.database)
+                .unwrap_or_else(|| String::from(DB_PATH)),
+            data_path: data_database
+                .or_else(|| env::var("SPEARMINT_DATA_DATABASE").ok())
+                .or(file.storage.data_database)
+                .unwrap_or_else(|| String::from(DB_DATA_PATH)),
+            ticker_paths,
+            conn: Default::default(),
+        }
+    }
+
+    /// Private in-memory main database, migrated and dropped with the context.
+    pub fn in_memory() -> Self {
+        let ctx = Self {
+            db_path: String::from(":memory:"),
+            ..Default::default()
+        };
+        migration::apply(&mut ctx.conn(), migration::MAIN).unwrap();
+
+        ctx
+    }
+
+    /// Shared connection to the main database.
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn
+            .get_or_init(|| {
+                let conn = Connection::open(&self.db_path).unwrap();
+                conn.execute_batch(&format!(
+                    "PRAGMA journal_mode=WAL; PRAGMA busy_timeout={};",
+                    BUSY_TIMEOUT
+                ))
+                .unwrap();
+                Mutex::new(conn)
+            })
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `f` in one transaction on the shared connection, committed when it
+    /// returns `Ok` and rolled back otherwise. Nested calls join the outer
+    /// transaction.
+    pub fn transaction<R, E>(&self, f: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+        if !self.conn().is_autocommit() {
+            return f();
+        }
+
+        self.conn().execute_batch("BEGIN IMMEDIATE").unwrap();
+        let tx = Transaction {
+            ctx: self,
+            done: false,
+        };
+
+        let result = f();
+        if result.is_ok() {
+            tx.commit();
+        }
+
+        result
+    }
+
+    /// Ticker database collected for a kline interval, `ticker<interval>.db` by default.
+    pub fn ticker_path(&self, interval: &str) -> String {
+        self.ticker_paths
+            .get(interval)
+            .cloned()
+            .unwrap_or_else(|| format!("ticker{}.db", interval))
+    }
+}
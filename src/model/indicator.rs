@@ -0,0 +1,309 @@
+use super::result::{self, IndicatorKind};
+use ta::indicators::{
+    AverageTrueRange, BollingerBands, ExponentialMovingAverage, FastStochastic, MoneyFlowIndex,
+    MovingAverageConvergenceDivergence, RelativeStrengthIndex, SimpleMovingAverage,
+};
+use ta::{DataItem, Next};
+
+/// MFI period of bots that don't set `mfi_period`, and the one stored with
+/// every ticker.
+pub const MFI_PERIOD: usize = 14;
+/// Interval the MFI of the bots is collected on.
+pub const MFI_INTERVAL: &str = "1m";
+const MACD_FAST: usize = 12;
+const MACD_SIGNAL: usize = 9;
+const BANDS_MULTIPLIER: f64 = 2.0;
+
+enum Series {
+    Rsi(RelativeStrengthIndex),
+    Ema(ExponentialMovingAverage),
+    Sma(SimpleMovingAverage),
+    Atr(AverageTrueRange),
+    Mfi(MoneyFlowIndex),
+    Macd(MovingAverageConvergenceDivergence),
+    Bollinger(BollingerBands),
+    Stochastic(FastStochastic),
+}
+
+/// One configured indicator and the klines it has seen so far.
+struct Computed {
+    name: String,
+    series: Series,
+    warm_up: usize,
+    klines: usize,
+}
+
+impl Computed {
+    fn new(config: &result::Indicator) -> Result<Self, String> {
+        let period = config.period;
+        let invalid = |e: ta::errors::TaError| format!("indicator `{}`: {:?}", config.name, e);
+
+        let (series, warm_up) = match config.kind {
+            IndicatorKind::Rsi => (
+                Series::Rsi(RelativeStrengthIndex::new(period).map_err(invalid)?),
+                period + 1,
+            ),
+            IndicatorKind::Ema => (
+                Series::Ema(ExponentialMovingAverage::new(period).map_err(invalid)?),
+                period,
+            ),
+            IndicatorKind::Sma => (
+                Series::Sma(SimpleMovingAverage::new(period).map_err(invalid)?),
+                period,
+            ),
+            IndicatorKind::Atr => (
+                Series::Atr(AverageTrueRange::new(period).map_err(invalid)?),
+                period,
+            ),
+            IndicatorKind::Mfi => (
+                Series::Mfi(MoneyFlowIndex::new(period).map_err(invalid)?),
+                period + 1,
+            ),
+            IndicatorKind::Macd => {
+                let fast = config.fast.unwrap_or(MACD_FAST);
+                let signal = config.signal.unwrap_or(MACD_SIGNAL);
+                if fast >= period {
+                    return Err(format!(
+                        "indicator `{}`: fast period {} has to be below the slow period {}",
+                        config.name, fast, period
+                    ));
+                }
+
+                (
+                    Series::Macd(
+                        MovingAverageConvergenceDivergence::new(fast, period, signal)
+                            .map_err(invalid)?,
+                    ),
+                    period + signal,
+                )
+            }
+            IndicatorKind::Bollinger => (
+                Series::Bollinger(
+                    BollingerBands::new(period, config.multiplier.unwrap_or(BANDS_MULTIPLIER))
+                        .map_err(invalid)?,
+                ),
+                period,
+            ),
+            IndicatorKind::Stochastic => (
+                Series::Stochastic(FastStochastic::new(period).map_err(invalid)?),
+                period,
+            ),
+        };
+
+        Ok(Self {
+            name: config.name.clone(),
+            series,
+            warm_up,
+            klines: 0,
+        })
+    }
+
+    fn next(&mut self, di: &DataItem, values: &mut Vec<(String, f64)>) {
+        self.klines += 1;
+        let computed: Vec<(String, f64)> = match &mut self.series {
+            Series::Rsi(series) => vec![(self.name.clone(), series.next(di))],
+            Series::Ema(series) => vec![(self.name.clone(), series.next(di))],
+            Series::Sma(series) => vec![(self.name.clone(), series.next(di))],
+            Series::Atr(series) => vec![(self.name.clone(), series.next(di))],
+            Series::Mfi(series) => vec![(self.name.clone(), series.next(di))],
+            Series::Stochastic(series) => vec![(self.name.clone(), series.next(di))],
+            Series::Macd(series) => {
+                let output = series.next(di);
+                vec![
+                    (self.name.clone(), output.macd),
+                    (format!("{}.signal", self.name), output.signal),
+                    (format!("{}.histogram", self.name), output.histogram),
+                ]
+            }
+            Series::Bollinger(series) => {
+                let output = series.next(di);
+                vec![
+                    (self.name.clone(), output.average),
+                    (format!("{}.upper", self.name), output.upper),
+                    (format!("{}.lower", self.name), output.lower),
+                ]
+            }
+        };
+
+        if self.klines >= self.warm_up {
+            values.extend(computed);
+        }
+    }
+}
+
+/// Name the MFI over `period` is collected under, e.g. `mfi_14`.
+pub fn mfi_name(period: usize) -> String {
+    format!("mfi_{}", period)
+}
+
+/// The bot's indicators, plus the MFI over its `mfi_period` that it trades on.
+pub fn with_mfi(config: &result::Bot) -> Vec<result::Indicator> {
+    let mut indicators = config.indicators.clone();
+    let period = config.parameters.mfi_period;
+
+    indicators.push(result::Indicator {
+        name: mfi_name(period),
+        kind: IndicatorKind::Mfi,
+        period,
+        interval: String::from(MFI_INTERVAL),
+        fast: None,
+        signal: None,
+        multiplier: None,
+    });
+
+    indicators
+}
+
+/// Indicators configured by the bots of a pair, computed on every closed
+/// kline. Values are only produced once an indicator has seen enough klines
+/// to warm up.
+pub struct Pipeline {
+    indicators: Vec<Computed>,
+}
+
+impl Pipeline {
+    pub fn new(configs: &[result::Indicator]) -> Result<Self, String> {
+        let mut indicators: Vec<Computed> = Vec::new();
+        for config in configs {
+            if indicators.iter().any(|known| known.name == config.name) {
+                return Err(format!("indicator `{}` is defined twice", config.name));
+            }
+
+            indicators.push(Computed::new(config)?);
+        }
+
+        Ok(Self { indicators })
+    }
+
+    /// Klines the slowest indicator needs before its first value.
+    pub fn warm_up(&self) -> usize {
+        self.indicators
+            .iter()
+            .map(|indicator| indicator.warm_up)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Feeds the closed kline, returning the values of the indicators that
+    /// are warmed up by name.
+    pub fn next(&mut self, ticker: &result::Ticker) -> Vec<(String, f64)> {
+        let di = DataItem::builder()
+            .high(ticker.high)
+            .low(ticker.low)
+            .close(ticker.close)
+            .open(ticker.open)
+            .volume(ticker.volume)
+            .build()
+            .unwrap();
+
+        let mut values: Vec<(String, f64)> = Vec::new();
+        for indicator in self.indicators.iter_mut() {
+            indicator.next(&di, &mut values);
+        }
+
+        values
+    }
+}
+
+/// Length in seconds of a kline interval such as `5m`, `4h` or `1d`, and the
+/// offset its klines open at: weekly klines open on Mondays.
+fn interval_seconds(interval: &str) -> Result<(u64, u64), String> {
+    let invalid = || format!("can't merge 1m tickers into `{}` klines", interval);
+    let (amount, unit) = interval.split_at(interval.len().saturating_sub(1));
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+
+    let (seconds, offset) = match unit {
+        "m" => (60, 0),
+        "h" => (3_600, 0),
+        "d" => (86_400, 0),
+        "w" => (604_800, 3 * 86_400),
+        _ => return Err(invalid()),
+    };
+    if amount == 0 {
+        return Err(invalid());
+    }
+
+    Ok((amount * seconds, offset))
+}
+
+/// Pipeline of the indicators of one interval, fed 1m tickers that are merged
+/// into klines of that interval. A kline is fed once its last minute is, or
+/// once a later minute shows it has closed.
+pub struct Resampled {
+    seconds: u64,
+    offset: u64,
+    pipeline: Pipeline,
+    kline: Option<result::Ticker>,
+}
+
+impl Resampled {
+    pub fn new(interval: &str, configs: &[result::Indicator]) -> Result<Self, String> {
+        let (seconds, offset) = interval_seconds(interval)?;
+
+        Ok(Self {
+            seconds,
+            offset,
+            pipeline: Pipeline::new(configs)?,
+            kline: None,
+        })
+    }
+
+    /// Pipelines of the indicators, one per interval.
+    pub fn by_interval(configs: &[result::Indicator]) -> Result<Vec<Self>, String> {
+        let mut intervals: Vec<&str> = Vec::new();
+        for config in configs {
+            if !intervals.contains(&config.interval.as_str()) {
+                intervals.push(&config.interval);
+            }
+        }
+
+        intervals
+            .into_iter()
+            .map(|interval| {
+                let configs: Vec<result::Indicator> = configs
+                    .iter()
+                    .filter(|config| config.interval == interval)
+                    .cloned()
+                    .collect();
+
+                Self::new(interval, &configs)
+            })
+            .collect()
+    }
+
+    /// Adds the 1m ticker to its kline, returning the values of the klines
+    /// that closed.
+    pub fn next(&mut self, ticker: &result::Ticker) -> Vec<(String, f64)> {
+        let open_time =
+            (ticker.timestamp + self.offset) / self.seconds * self.seconds - self.offset;
+        let mut values: Vec<(String, f64)> = Vec::new();
+
+        if let Some(kline) = self.kline.take() {
+            if kline.timestamp == open_time {
+                self.kline = Some(kline);
+            } else {
+                values.extend(self.pipeline.next(&kline));
+            }
+        }
+
+        let kline = self.kline.get_or_insert_with(|| result::Ticker {
+            pair: ticker.pair.clone(),
+            timestamp: open_time,
+            open: ticker.open,
+            high: ticker.high,
+            low: ticker.low,
+            ..Default::default()
+        });
+        kline.high = kline.high.max(ticker.high);
+        kline.low = kline.low.min(ticker.low);
+        kline.close = ticker.close;
+        kline.volume += ticker.volume;
+
+        if ticker.timestamp + 60 >= open_time + self.seconds {
+            let kline = self.kline.take().unwrap();
+            values.extend(self.pipeline.next(&kline));
+        }
+
+        values
+    }
+}
@@ -0,0 +1,503 @@
+use super::context::Context;
+use crate::connector::simulator;
+use rusqlite::{params, Connection, Result, TransactionBehavior};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One schema change. Versions are applied in order and recorded in the
+/// `schema_version` table of the database they ran against.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Connection) -> Result<()>,
+}
+
+/// Migrations of `spearmint.db`: bots, bindings, trades, wallets and paper accounts.
+pub const MAIN: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create bots, bindings, trades, tokens and bot_states",
+        apply: create_main,
+    },
+    Migration {
+        version: 2,
+        description: "create paper_accounts and paper_balances",
+        apply: create_paper,
+    },
+    Migration {
+        version: 3,
+        description: "add bots.stop_loss",
+        apply: add_stop_loss,
+    },
+    Migration {
+        version: 4,
+        description: "add bot_states.take_profit_order_id and paper_orders",
+        apply: add_take_profit_order,
+    },
+    Migration {
+        version: 5,
+        description: "add order id, quote quantity and commission to trades",
+        apply: add_trade_fill,
+    },
+    Migration {
+        version: 6,
+        description: "remove duplicated tokens and index trades and bot_states",
+        apply: index_main,
+    },
+    Migration {
+        version: 7,
+        description: "key trades and bot_states by bot id",
+        apply: add_bot_id,
+    },
+    Migration {
+        version: 8,
+        description: "name accounts in bindings, tokens and bots",
+        apply: add_account,
+    },
+    Migration {
+        version: 9,
+        description: "add bindings.passphrase",
+        apply: add_api_passphrase,
+    },
+    Migration {
+        version: 10,
+        description: "add bindings.environment",
+        apply: add_environment,
+    },
+    Migration {
+        version: 11,
+        description: "add bots.grid and grid_levels",
+        apply: add_grid,
+    },
+    Migration {
+        version: 12,
+        description: "add bots.indicators",
+        apply: add_indicators,
+    },
+    Migration {
+        version: 13,
+        description: "add bots.mfi_period",
+        apply: add_mfi_period,
+    },
+    Migration {
+        version: 14,
+        description: "add bot_states.peak_price",
+        apply: add_peak_price,
+    },
+    Migration {
+        version: 15,
+        description: "remove backtest trades and bot_states",
+        apply: remove_backtests,
+    },
+];
+
+/// Migrations of the ticker databases written by `ticker` and `tick`.
+pub const TICKER: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create tickers",
+        apply: create_tickers,
+    },
+    Migration {
+        version: 2,
+        description: "index tickers",
+        apply: index_tickers,
+    },
+    Migration {
+        version: 3,
+        description: "add rsi, bollinger band, ema and volume average to tickers",
+        apply: add_ticker_indicators,
+    },
+    Migration {
+        version: 4,
+        description: "create indicator_values",
+        apply: create_indicator_values,
+    },
+];
+
+#[derive(Debug)]
+pub struct Applied {
+    pub version: u32,
+    pub description: String,
+    pub applied_at: Option<u64>,
+}
+
+fn create_version_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE if not exists schema_version (
+            version                         INTEGER PRIMARY KEY,
+            description                     TEXT,
+            applied_at                      INTEGER NOT NULL
+        )
+    ",
+        [],
+    )?;
+
+    Ok(())
+}
+
+pub fn version(conn: &Connection) -> Result<u32> {
+    create_version_table(conn)?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Applies every pending migration, each in its own transaction, and returns
+/// the versions applied.
+pub fn migrate(path: &str, migrations: &[Migration]) -> Result<Vec<u32>> {
+    apply(&mut Connection::open(path)?, migrations)
+}
+
+/// Same as `migrate`, on an already opened connection.
+pub fn apply(conn: &mut Connection, migrations: &[Migration]) -> Result<Vec<u32>> {
+    create_version_table(conn)?;
+
+    let mut applied: Vec<u32> = Vec::new();
+    for migration in migrations {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if migration.version > version(&tx)? {
+            (migration.apply)(&tx)?;
+            tx.execute(
+                "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.description, now()],
+            )?;
+            applied.push(migration.version);
+        }
+        tx.commit()?;
+    }
+
+    Ok(applied)
+}
+
+/// Every known migration with the time it was applied, if it was.
+pub fn status(path: &str, migrations: &[Migration]) -> Result<Vec<Applied>> {
+    let conn = Connection::open(path)?;
+    create_version_table(&conn)?;
+
+    let mut stmt = conn.prepare("SELECT applied_at FROM schema_version WHERE version=?1")?;
+    let mut status: Vec<Applied> = Vec::new();
+    for migration in migrations {
+        let mut rows = stmt.query_map([migration.version], |row| row.get(0))?;
+        status.push(Applied {
+            version: migration.version,
+            description: String::from(migration.description),
+            applied_at: rows.next().transpose()?,
+        });
+    }
+
+    Ok(status)
+}
+
+/// The main database, plus the data and ticker databases that already exist.
+pub fn databases(ctx: &Context) -> Vec<(String, &'static [Migration])> {
+    let mut databases: Vec<(String, &'static [Migration])> = vec![(ctx.db_path.clone(), MAIN)];
+
+    let mut tickers = vec![ctx.data_path.clone(), ctx.ticker_path("1m")];
+    tickers.extend(ctx.ticker_paths.values().cloned());
+    for path in tickers {
+        if Path::new(&path).exists() && !databases.iter().any(|(known, _)| *known == path) {
+            databases.push((path, TICKER));
+        }
+    }
+
+    databases
+}
+
+/// Brings every database of the context up to date.
+pub fn migrate_all(ctx: &Context) -> Result<Vec<(String, Vec<u32>)>> {
+    databases(ctx)
+        .into_iter()
+        .map(|(path, migrations)| {
+            let applied = migrate(&path, migrations)?;
+            Ok((path, applied))
+        })
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns: Vec<String> = stmt
+        .query_map([], |row| row.get(1))?
+        .collect::<Result<Vec<String>>>()?;
+
+    if !columns.iter().any(|name| name == column) {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn create_main(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE if not exists bots (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            title                           TEXT NOT NULL,
+            pair                            TEXT,
+            base                            TEXT,
+            quote                           TEXT,
+            platform                        TEXT,
+            strategy                        TEXT,
+            cycle                           TEXT,
+            first_buy_in                    REAL,
+            entry                           TEXT,
+            take_profit                     TEXT,
+            margin                          TEXT,
+            status                          TEXT
+        );
+
+        CREATE TABLE if not exists bindings (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            api_key                         TEXT NOT NULL,
+            secret_key                      TEXT,
+            platform                        TEXT
+        );
+
+        CREATE TABLE if not exists trades (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            pair                            TEXT NOT NULL,
+            cycle                           INTEGER,
+            price                           REAL,
+            qty                             REAL,
+            platform                        TEXT,
+            status                          TEXT,
+            timestamp                       INTEGER NOT NULL
+        );
+
+        CREATE TABLE if not exists tokens (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            token                           TEXT NOT NULL,
+            amount                          REAL,
+            platform                        TEXT
+        );
+
+        CREATE TABLE if not exists bot_states (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            pair                            TEXT NOT NULL,
+            cycle                           INTEGER,
+            margin_position                 INTEGER,
+            top_price                       REAL,
+            bottom_price                    REAL,
+            bottom_mfi                      REAL,
+            platform                        TEXT,
+            timestamp                       INTEGER NOT NULL
+        );
+    ",
+    )?;
+
+    for token in ["USDC", "USDT", "BTC"] {
+        conn.execute(
+            "INSERT INTO tokens (token, amount, platform)
+            SELECT ?1, 0, 'binance'
+            WHERE NOT EXISTS (SELECT 1 FROM tokens WHERE token=?1 AND platform='binance')",
+            params![token],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn create_paper(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE if not exists paper_accounts (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            taker_fee                       REAL,
+            slippage                        REAL
+        );
+
+        CREATE TABLE if not exists paper_balances (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            asset                           TEXT NOT NULL,
+            free                            REAL
+        );
+    ",
+    )
+}
+
+fn add_stop_loss(conn: &Connection) -> Result<()> {
+    add_column(conn, "bots", "stop_loss", "TEXT")
+}
+
+fn add_take_profit_order(conn: &Connection) -> Result<()> {
+    add_column(conn, "bot_states", "take_profit_order_id", "INTEGER")?;
+
+    conn.execute_batch(
+        "CREATE TABLE if not exists paper_orders (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            pair                            TEXT NOT NULL,
+            side                            TEXT NOT NULL,
+            price                           REAL,
+            orig_qty                        REAL,
+            executed_qty                    REAL,
+            status                          TEXT NOT NULL,
+            timestamp                       INTEGER NOT NULL
+        );
+    ",
+    )
+}
+
+fn add_trade_fill(conn: &Connection) -> Result<()> {
+    add_column(conn, "trades", "order_id", "INTEGER")?;
+    add_column(conn, "trades", "quote_qty", "REAL")?;
+    add_column(conn, "trades", "commission", "REAL")?;
+    add_column(conn, "trades", "commission_asset", "TEXT")
+}
+
+fn index_main(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DELETE FROM tokens WHERE id NOT IN (SELECT MIN(id) FROM tokens GROUP BY token, platform);
+
+        CREATE INDEX if not exists trades_pair_idx ON trades (platform, pair);
+        CREATE INDEX if not exists trades_timestamp_idx ON trades (timestamp DESC);
+        CREATE INDEX if not exists bot_states_pair_idx ON bot_states (platform, pair);
+        CREATE INDEX if not exists bot_states_timestamp_idx ON bot_states (timestamp DESC);
+    ",
+    )
+}
+
+fn add_bot_id(conn: &Connection) -> Result<()> {
+    add_column(conn, "trades", "bot_id", "INTEGER")?;
+    add_column(conn, "bot_states", "bot_id", "INTEGER")?;
+
+    remove_backtests(conn)?;
+
+    conn.execute_batch(
+        "UPDATE trades SET bot_id=(
+            SELECT MIN(id) FROM bots WHERE bots.platform=trades.platform AND bots.pair=trades.pair
+        ) WHERE bot_id IS NULL;
+        UPDATE bot_states SET bot_id=(
+            SELECT MIN(id) FROM bots WHERE bots.platform=bot_states.platform AND bots.pair=bot_states.pair
+        ) WHERE bot_id IS NULL;
+
+        CREATE INDEX if not exists trades_bot_idx ON trades (bot_id, cycle);
+        CREATE INDEX if not exists bot_states_bot_idx ON bot_states (bot_id);
+    ",
+    )
+}
+
+/// Backtests used to write to the main database. Their rows belong to no bot
+/// and would otherwise be taken for the trades of a live one.
+fn remove_backtests(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM trades WHERE platform=?1",
+        params![simulator::PLATFORM],
+    )?;
+    conn.execute(
+        "DELETE FROM bot_states WHERE platform=?1",
+        params![simulator::PLATFORM],
+    )?;
+
+    Ok(())
+}
+
+fn add_account(conn: &Connection) -> Result<()> {
+    add_column(conn, "bots", "account", "TEXT")?;
+    add_column(conn, "bindings", "name", "TEXT")?;
+    add_column(conn, "tokens", "account", "TEXT")?;
+
+    conn.execute_batch(
+        "UPDATE bindings SET name='main' WHERE name IS NULL;
+        UPDATE tokens SET account=platform || ':main' WHERE account IS NULL;
+    ",
+    )
+}
+
+fn add_api_passphrase(conn: &Connection) -> Result<()> {
+    add_column(conn, "bindings", "passphrase", "TEXT")
+}
+
+fn add_environment(conn: &Connection) -> Result<()> {
+    add_column(conn, "bindings", "environment", "TEXT")?;
+
+    conn.execute_batch("UPDATE bindings SET environment='mainnet' WHERE environment IS NULL;")
+}
+
+fn add_grid(conn: &Connection) -> Result<()> {
+    add_column(conn, "bots", "grid", "TEXT")?;
+
+    conn.execute_batch(
+        "CREATE TABLE if not exists grid_levels (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            bot_id                          INTEGER NOT NULL,
+            level                           INTEGER NOT NULL,
+            price                           REAL,
+            qty                             REAL,
+            timestamp                       INTEGER NOT NULL
+        );
+
+        CREATE UNIQUE INDEX if not exists grid_levels_bot_idx ON grid_levels (bot_id, level);
+    ",
+    )
+}
+
+fn add_indicators(conn: &Connection) -> Result<()> {
+    add_column(conn, "bots", "indicators", "TEXT")
+}
+
+fn add_mfi_period(conn: &Connection) -> Result<()> {
+    add_column(conn, "bots", "mfi_period", "INTEGER")
+}
+
+fn add_peak_price(conn: &Connection) -> Result<()> {
+    add_column(conn, "bot_states", "peak_price", "REAL")
+}
+
+fn create_tickers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE if not exists tickers (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            pair                            TEXT NOT NULL,
+            timestamp                       INTEGER NOT NULL,
+            open                            REAL,
+            high                            REAL,
+            low                             REAL,
+            close                           REAL,
+            volume                          REAL,
+            mfi                             REAL
+        );
+    ",
+    )
+}
+
+fn index_tickers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX if not exists tickers_pair_idx ON tickers (pair);
+        CREATE INDEX if not exists tickers_timestamp_idx ON tickers (timestamp DESC);
+    ",
+    )
+}
+
+fn add_ticker_indicators(conn: &Connection) -> Result<()> {
+    add_column(conn, "tickers", "rsi", "REAL")?;
+    add_column(conn, "tickers", "bb_lower", "REAL")?;
+    add_column(conn, "tickers", "ema", "REAL")?;
+    add_column(conn, "tickers", "volume_avg", "REAL")
+}
+
+fn create_indicator_values(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE if not exists indicator_values (
+            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
+            pair                            TEXT NOT NULL,
+            interval                        TEXT NOT NULL,
+            timestamp                       INTEGER NOT NULL,
+            name                            TEXT NOT NULL,
+            value                           REAL
+        );
+
+        CREATE INDEX if not exists indicator_values_pair_idx ON indicator_values (pair, interval, name);
+    ",
+    )
+}
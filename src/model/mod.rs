@@ -1,153 +1,41 @@
+use context::Context;
 use dialoguer::{theme::ColorfulTheme, Confirm};
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Result};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 pub mod args;
 pub mod bind;
 pub mod bot;
+pub mod context;
+pub mod indicator;
+pub mod migration;
+pub mod paper;
 pub mod result;
+pub mod secret;
 pub mod storage;
 pub mod ticker;
 
-pub const DB_PATH: &str = "spearmint.db";
-
 pub fn setup(path: &str) -> Result<()> {
-    let conn = Connection::open(path)?;
-
-    conn.execute(
-        "CREATE TABLE if not exists bots (
-            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
-            title                           TEXT NOT NULL,
-            pair                            TEXT,
-            base                            TEXT,
-            quote                           TEXT,
-            platform                        TEXT,
-            strategy                        TEXT,
-            cycle                           TEXT,
-            first_buy_in                    REAL,
-            entry                           TEXT,
-            take_profit                     TEXT,
-            margin                          TEXT,
-            status                          TEXT
-        )
-    ",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE if not exists bindings (
-            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
-            api_key                         TEXT NOT NULL,
-            secret_key                      TEXT,
-            platform                        TEXT
-        )
-    ",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE if not exists trades (
-            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
-            pair                            TEXT NOT NULL,
-            cycle                           INTEGER,
-            price                           REAL,
-            qty                             REAL,
-            platform                        TEXT,
-            status                          TEXT,
-            timestamp                       INTEGER NOT NULL
-        );
-
-        CREATE INDEX pair_idx ON trades (platform, pair);
-        CREATE INDEX timestamp_idx ON trades (timestamp) DESC;
-    ",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE if not exists tokens (
-            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
-            token                           TEXT NOT NULL,
-            amount                          REAL,
-            platform                        TEXT
-        )
-    ",
-        [],
-    )?;
-
-    conn.execute(
-        "INSERT INTO tokens (token, amount, platform) VALUES (?1, ?2, ?3)
-    ",
-        ["USDC", "0", "binance"],
-    )?;
-
-    conn.execute(
-        "INSERT INTO tokens (token, amount, platform) VALUES (?1, ?2, ?3)
-    ",
-        ["USDT", "0", "binance"],
-    )?;
-
-    conn.execute(
-        "INSERT INTO tokens (token, amount, platform) VALUES (?1, ?2, ?3)
-    ",
-        ["BTC", "0", "binance"],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE if not exists bot_states (
-            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
-            pair                            TEXT NOT NULL,
-            cycle                           INTEGER,
-            margin_position                 INTEGER,
-            top_price                       REAL,
-            bottom_price                    REAL,
-            bottom_mfi                      REAL,
-            platform                        TEXT,
-            timestamp                       INTEGER NOT NULL
-        );
-
-        CREATE INDEX pair_idx ON bot_states (platform, pair);
-        CREATE INDEX timestamp_idx ON bot_states (timestamp) DESC;
-    ",
-        [],
-    )?;
+    migration::migrate(path, migration::MAIN)?;
 
     Ok(())
 }
 
 pub fn setup_price(path: &str) -> Result<()> {
-    let conn = Connection::open(path)?;
-
-    conn.execute(
-        "CREATE TABLE if not exists tickers (
-            id                              INTEGER PRIMARY KEY AUTOINCREMENT,
-            pair                            TEXT NOT NULL,
-            timestamp                       INTEGER NOT NULL,
-            open                            REAL,
-            high                            REAL,
-            low                             REAL,
-            close                           REAL,
-            volume                          REAL,
-            mfi                             REAL
-            
-        );
-
-        CREATE INDEX pair_idx ON tickers (pair);
-        CREATE INDEX timestamp_idx ON tickers (timestamp) DESC;
-    ",
-        [],
-    )?;
+    migration::migrate(path, migration::TICKER)?;
 
     Ok(())
 }
 
-pub fn delete(name: &str) {
+pub fn delete(ctx: &Context, name: &str) {
     if Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt("Do you really want to continue?")
         .default(true)
         .interact()
         .unwrap()
     {
-        let conn = Connection::open(DB_PATH).unwrap();
+        let conn = ctx.conn();
         conn.execute("DELETE FROM bots WHERE title=?1", params![name])
             .unwrap();
         println!("{} deleted!", name);
@@ -156,14 +44,14 @@ pub fn delete(name: &str) {
     }
 }
 
-pub fn start(name: &str) {
+pub fn start(ctx: &Context, name: &str) {
     if Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt("Do you really want to continue?")
         .default(true)
         .interact()
         .unwrap()
     {
-        let conn = Connection::open(DB_PATH).unwrap();
+        let conn = ctx.conn();
         conn.execute(
             "UPDATE bots SET status='ACTIVE' WHERE title=?1",
             params![name],
@@ -175,14 +63,14 @@ pub fn start(name: &str) {
     }
 }
 
-pub fn stop(name: &str) {
+pub fn stop(ctx: &Context, name: &str) {
     if Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt("Do you really want to continue?")
         .default(true)
         .interact()
         .unwrap()
     {
-        let conn = Connection::open(DB_PATH).unwrap();
+        let conn = ctx.conn();
         conn.execute(
             "UPDATE bots SET status='PAUSED' WHERE title=?1",
             params![name],
@@ -194,42 +82,178 @@ pub fn stop(name: &str) {
     }
 }
 
+#[derive(Debug)]
+pub enum ExchangeError {
+    InsufficientBalance(String),
+    MinNotional(String),
+    LotSize(String),
+    RateLimited(String),
+    Timestamp(String),
+    Network(String),
+    InvalidSymbol(String),
+    Rejected(String),
+    Other(String),
+}
+
+impl ExchangeError {
+    /// Errors that are expected to clear up by the next tick.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ExchangeError::RateLimited(_) | ExchangeError::Timestamp(_) | ExchangeError::Network(_)
+        )
+    }
+}
+
+impl std::fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExchangeError::InsufficientBalance(msg) => write!(f, "insufficient balance: {}", msg),
+            ExchangeError::MinNotional(msg) => write!(f, "below min notional: {}", msg),
+            ExchangeError::LotSize(msg) => write!(f, "outside lot size: {}", msg),
+            ExchangeError::RateLimited(msg) => write!(f, "rate limited: {}", msg),
+            ExchangeError::Timestamp(msg) => write!(f, "timestamp out of window: {}", msg),
+            ExchangeError::Network(msg) => write!(f, "network error: {}", msg),
+            ExchangeError::InvalidSymbol(msg) => write!(f, "invalid symbol: {}", msg),
+            ExchangeError::Rejected(msg) => write!(f, "order rejected: {}", msg),
+            ExchangeError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ExchangeError {}
+
 pub trait Exchange {
-    fn get_balances(&self) -> Vec<result::Balance>;
-    fn market_buy_using_quote_quantity(&self, pair: String, qty: f64) -> result::Transaction;
-    fn market_sell(&self, pair: String, qty: f64) -> result::Transaction;
-    fn get_balance(&self, asset: String) -> result::Balance;
-    fn adjust_quantity(&self, pair: String, qty: f64) -> f64;
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError>;
+    fn market_buy_using_quote_quantity(
+        &self,
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError>;
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError>;
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError>;
+    fn adjust_quantity(&self, pair: String, qty: f64) -> Result<f64, ExchangeError>;
+    #[allow(dead_code)]
+    fn limit_buy(&self, pair: String, qty: f64, price: f64)
+        -> Result<result::Order, ExchangeError>;
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError>;
+    fn get_order(&self, pair: String, order_id: u64) -> Result<result::Order, ExchangeError>;
+    fn cancel_order(&self, pair: String, order_id: u64) -> Result<(), ExchangeError>;
+    fn get_open_orders(&self, pair: String) -> Result<Vec<result::Order>, ExchangeError>;
+    /// The account's own fills on the pair, for platforms that keep a trade history.
+    fn get_fills(&self, _pair: String) -> Result<Vec<result::Fill>, ExchangeError> {
+        Err(ExchangeError::Other(String::from(
+            "no trade history to reconcile",
+        )))
+    }
 }
 
-pub trait Strategy {
-    fn run(&self, price: f64, session: Session) -> BotCommand;
-    fn is_entry_signal(&self, top_percent_change: f64, bottom_percent_change: f64) -> bool;
-    fn is_sell_signal(&self, top_percent_change: f64, avg_percent_change: f64) -> bool;
-    fn is_avg_buy_signal(
+/// Connectors picked at runtime by `connector::build`.
+impl<E: Exchange + ?Sized> Exchange for Box<E> {
+    fn get_balances(&self) -> Result<Vec<result::Balance>, ExchangeError> {
+        (**self).get_balances()
+    }
+
+    fn market_buy_using_quote_quantity(
         &self,
-        avg_percent_change: f64,
-        bottom_percent_change: f64,
-        margin_position: usize,
-    ) -> bool;
-    fn is_mfi_approved(
+        pair: String,
+        qty: f64,
+    ) -> Result<result::Transaction, ExchangeError> {
+        (**self).market_buy_using_quote_quantity(pair, qty)
+    }
+
+    fn market_sell(&self, pair: String, qty: f64) -> Result<result::Transaction, ExchangeError> {
+        (**self).market_sell(pair, qty)
+    }
+
+    fn get_balance(&self, asset: String) -> Result<result::Balance, ExchangeError> {
+        (**self).get_balance(asset)
+    }
+
+    fn adjust_quantity(&self, pair: String, qty: f64) -> Result<f64, ExchangeError> {
+        (**self).adjust_quantity(pair, qty)
+    }
+
+    fn limit_buy(
         &self,
-        mfi: f64,
-        mfi_bottom_change: f64,
-        mfi_dir: &str,
-        margin_position: usize,
-    ) -> bool;
-    fn is_entry_mfi_approved(&self, mfi: f64, mfi_bottom_change: f64, mfi_dir: &str) -> bool;
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        (**self).limit_buy(pair, qty, price)
+    }
+
+    fn limit_sell(
+        &self,
+        pair: String,
+        qty: f64,
+        price: f64,
+    ) -> Result<result::Order, ExchangeError> {
+        (**self).limit_sell(pair, qty, price)
+    }
+
+    fn get_order(&self, pair: String, order_id: u64) -> Result<result::Order, ExchangeError> {
+        (**self).get_order(pair, order_id)
+    }
+
+    fn cancel_order(&self, pair: String, order_id: u64) -> Result<(), ExchangeError> {
+        (**self).cancel_order(pair, order_id)
+    }
+
+    fn get_open_orders(&self, pair: String) -> Result<Vec<result::Order>, ExchangeError> {
+        (**self).get_open_orders(pair)
+    }
+
+    fn get_fills(&self, pair: String) -> Result<Vec<result::Fill>, ExchangeError> {
+        (**self).get_fills(pair)
+    }
+}
+
+/// A strategy decides each tick from the price and the bot's session.
+pub trait Strategy {
+    fn run(&self, price: f64, session: Session) -> BotCommand;
+}
+
+/// Strategies picked at runtime by `strategy::build`.
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn run(&self, price: f64, session: Session) -> BotCommand {
+        (**self).run(price, session)
+    }
 }
 
 #[derive(Debug)]
 pub enum BotCommand {
     Entry(f64),
     Buy(f64),
-    Sell(),
+    Sell(Exit),
+    LimitSell(f64),
+    /// Buys a grid level, by its index, for the quote amount.
+    GridBuy(u64, f64),
+    /// Sells the base held by a grid level.
+    GridSell(u64),
     Pause(),
 }
 
+#[derive(Debug)]
+pub enum Exit {
+    TakeProfit,
+    StopLoss,
+}
+
+impl Exit {
+    pub fn status(&self) -> &str {
+        match self {
+            Exit::TakeProfit => "CLOSE",
+            Exit::StopLoss => "STOPPED",
+        }
+    }
+}
+
 pub trait SessionState {}
 
 pub struct Initial;
@@ -241,9 +265,20 @@ pub struct Session<State: SessionState = Initial> {
     pub avg_price: f64,
     pub top_price: f64,
     pub bottom_price: f64,
+    /// Highest price since the cycle's entry, trailed by the stop loss.
+    pub peak_price: f64,
     pub margin_position: u64,
-    pub mfi: f64,
+    /// Latest MFI, `None` while it is warming up.
+    pub mfi: Option<f64>,
     pub mfi_dir: String,
     pub bottom_mfi: f64,
+    pub opened_at: u64,
+    pub timestamp: u64,
+    pub take_profit_order_id: u64,
+    pub grid_levels: Vec<result::GridLevel>,
+    /// Last closed 1m kline with its indicators.
+    pub ticker: result::Ticker,
+    /// Latest values of the bot's configured indicators, by name.
+    pub indicators: HashMap<String, f64>,
     pub phantom: PhantomData<State>,
 }
@@ -0,0 +1,200 @@
+use super::{args, context::Context, result};
+use rusqlite::{params, Result};
+
+pub fn save(ctx: &Context, account: args::PaperAccount) -> result::PaperAccount {
+    {
+        let conn = ctx.conn();
+
+        conn.execute("DELETE FROM paper_accounts", []).unwrap();
+        conn.execute(
+            "INSERT INTO paper_accounts (
+                taker_fee,
+                slippage
+            ) VALUES (?1, ?2)",
+            params![account.taker_fee, account.slippage],
+        )
+        .unwrap();
+
+        conn.execute("DELETE FROM paper_balances", []).unwrap();
+        for (asset, free) in account.balances.iter() {
+            conn.execute(
+                "INSERT INTO paper_balances (asset, free) VALUES (?1, ?2)",
+                params![asset, free],
+            )
+            .unwrap();
+        }
+    }
+
+    // The shared connection isn't re-entrant, so it is released before
+    // `get` locks it again.
+    get(ctx)
+}
+
+pub fn get(ctx: &Context) -> result::PaperAccount {
+    let account = ctx.conn().query_row(
+        "SELECT taker_fee, slippage FROM paper_accounts LIMIT 1",
+        [],
+        |row| {
+            Ok(result::PaperAccount {
+                taker_fee: row.get(0)?,
+                slippage: row.get(1)?,
+                balances: Vec::new(),
+            })
+        },
+    );
+
+    let mut account = account.unwrap_or_default();
+    account.balances = get_balances(ctx);
+
+    account
+}
+
+pub fn get_balances(ctx: &Context) -> Vec<result::Balance> {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT asset, free FROM paper_balances ORDER BY asset")
+        .unwrap();
+    let balances: Vec<Result<result::Balance>> = stmt
+        .query_map([], |row| {
+            Ok(result::Balance {
+                asset: row.get(0)?,
+                free: row.get(1)?,
+            })
+        })
+        .unwrap()
+        .collect();
+
+    balances.into_iter().map(|b| b.unwrap()).collect()
+}
+
+pub fn get_balance(ctx: &Context, asset: &str) -> f64 {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT free FROM paper_balances WHERE asset=:asset LIMIT 1")
+        .unwrap();
+    let mut balances: Vec<Result<f64>> = stmt
+        .query_map([asset], |row| Ok(row.get(0)?))
+        .unwrap()
+        .collect();
+
+    if balances.len() > 0 {
+        balances.remove(0).unwrap()
+    } else {
+        0.0
+    }
+}
+
+pub fn update_balance(ctx: &Context, asset: &str, free: f64) {
+    let conn = ctx.conn();
+    let updated = conn
+        .execute(
+            "UPDATE paper_balances SET
+                free=?1
+            WHERE asset=?2",
+            params![free, asset],
+        )
+        .unwrap();
+
+    if updated == 0 {
+        conn.execute(
+            "INSERT INTO paper_balances (asset, free) VALUES (?1, ?2)",
+            params![asset, free],
+        )
+        .unwrap();
+    }
+}
+
+pub fn create_order(ctx: &Context, order: result::Order) -> result::Order {
+    let conn = ctx.conn();
+    conn.execute(
+        "INSERT INTO paper_orders (
+            pair,
+            side,
+            price,
+            orig_qty,
+            executed_qty,
+            status,
+            timestamp
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            order.pair,
+            order.side,
+            order.price,
+            order.orig_qty,
+            order.executed_qty,
+            order.status,
+            order.timestamp
+        ],
+    )
+    .unwrap();
+
+    result::Order {
+        order_id: conn.last_insert_rowid() as u64,
+        ..order
+    }
+}
+
+pub fn update_order(ctx: &Context, order: &result::Order) {
+    let conn = ctx.conn();
+    conn.execute(
+        "UPDATE paper_orders SET
+            executed_qty=?1,
+            status=?2,
+            timestamp=?3
+        WHERE id=?4",
+        params![
+            order.executed_qty,
+            order.status,
+            order.timestamp,
+            order.order_id
+        ],
+    )
+    .unwrap();
+}
+
+pub fn get_orders(ctx: &Context, pair: &str, status: &str) -> Vec<result::Order> {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT * FROM paper_orders WHERE pair=:pair AND status=:status ORDER BY id")
+        .unwrap();
+    let orders: Vec<Result<result::Order>> = stmt
+        .query_map([pair, status], read_order)
+        .unwrap()
+        .collect();
+
+    orders.into_iter().map(|o| o.unwrap()).collect()
+}
+
+pub fn get_order(ctx: &Context, order_id: u64) -> result::Order {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT * FROM paper_orders WHERE id=:id LIMIT 1")
+        .unwrap();
+    let mut orders: Vec<Result<result::Order>> =
+        stmt.query_map([order_id], read_order).unwrap().collect();
+
+    if orders.len() > 0 {
+        orders.remove(0).unwrap()
+    } else {
+        Default::default()
+    }
+}
+
+fn read_order(row: &rusqlite::Row) -> Result<result::Order> {
+    let price: f64 = row.get(3)?;
+    let executed_qty: f64 = row.get(5)?;
+
+    Ok(result::Order {
+        order_id: row.get(0)?,
+        pair: row.get(1)?,
+        side: row.get(2)?,
+        price,
+        orig_qty: row.get(4)?,
+        executed_qty,
+        quote_qty: executed_qty * price,
+        commission: 0.0,
+        commission_asset: String::new(),
+        status: row.get(6)?,
+        timestamp: row.get(7)?,
+    })
+}
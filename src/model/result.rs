@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Bot {
+    #[serde(default, skip_serializing)]
+    pub id: u64,
     pub title: String,
     pub pair: String,
     pub base: String,
@@ -9,40 +11,183 @@ pub struct Bot {
     pub platform: String,
     pub strategy: String,
     pub parameters: Parameters,
+    #[serde(default)]
     pub margin: Margin,
     pub status: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub account: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indicators: Vec<Indicator>,
+}
+
+impl Bot {
+    /// Account the bot trades on, e.g. `binance:sub1`; the platform's `main`
+    /// account when the config doesn't name one.
+    pub fn account(&self) -> String {
+        if self.account.is_empty() {
+            format!("{}:{}", self.platform, super::bind::DEFAULT_ACCOUNT)
+        } else {
+            self.account.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Parameters {
     pub cycle: String,
     pub first_buy_in: f64,
+    /// Klines the MFI of entries and margin buys is computed over.
+    #[serde(default = "default_mfi_period")]
+    pub mfi_period: usize,
+    #[serde(default)]
     pub entry: OpenCriteria,
+    #[serde(default)]
     pub take_profit: CloseCriteria,
+    #[serde(default)]
+    pub stop_loss: Option<StopLoss>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<Grid>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+fn default_mfi_period() -> usize {
+    super::indicator::MFI_PERIOD
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Margin {
     pub margin_configuration: Vec<OpenCriteria>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct OpenCriteria {
     pub mfi_below: f64,
     pub mfi_callback: f64,
     pub price_change_below: f64,
     pub price_callback: f64,
     pub amount_ratio: f64,
+    /// Indicator conditions the entry or margin buy also needs, on top of
+    /// the MFI and price criteria.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub combine: Combine,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Indicator condition on the last closed 1m kline, e.g.
+/// `{ indicator = "rsi_below", value = 30.0 }`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "indicator", rename_all = "snake_case")]
+pub enum Condition {
+    /// RSI(14) below `value`.
+    RsiBelow { value: f64 },
+    /// Close below the lower Bollinger band (20, 2).
+    BelowLowerBand,
+    /// Price more than `percent` below the EMA(20).
+    BelowEma { percent: f64 },
+    /// Volume at least `ratio` times the average of the previous 20 klines.
+    VolumeSpike { ratio: f64 },
+    /// Latest value of the bot's indicator `name` below `value`.
+    ValueBelow { name: String, value: f64 },
+    /// Latest value of the bot's indicator `name` above `value`.
+    ValueAbove { name: String, value: f64 },
+}
+
+/// Indicator the `ticker` collector computes on every closed kline of
+/// `interval` for the bot's pair, stored in `indicator_values` under `name`.
+/// Bollinger bands also store `<name>.upper` and `<name>.lower`, and MACD
+/// `<name>.signal` and `<name>.histogram`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Indicator {
+    pub name: String,
+    pub kind: IndicatorKind,
+    pub period: usize,
+    #[serde(default = "default_interval")]
+    pub interval: String,
+    /// Fast period of MACD, `period` being the slow one. Defaults to 12.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fast: Option<usize>,
+    /// Signal period of MACD. Defaults to 9.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<usize>,
+    /// Standard deviations of the Bollinger bands. Defaults to 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiplier: Option<f64>,
+}
+
+fn default_interval() -> String {
+    String::from("1m")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndicatorKind {
+    Rsi,
+    Ema,
+    Sma,
+    Atr,
+    Mfi,
+    Macd,
+    Bollinger,
+    Stochastic,
+}
+
+/// Whether every condition has to hold (AND) or any one of them (OR).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Combine {
+    #[default]
+    All,
+    Any,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct CloseCriteria {
     pub price_change_above: f64,
     pub price_callback: f64,
+    /// Rests a limit sell at `price_change_above` instead, leaving
+    /// `price_callback` unused.
+    #[serde(default)]
+    pub limit_order: bool,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StopLoss {
+    pub price_change_below: Option<f64>,
+    pub top_price_change_below: Option<f64>,
+    pub max_cycle_hours: Option<f64>,
+}
+
+/// Price ladder of the grid strategy: `levels` prices from `lower_price` to
+/// `upper_price`, evenly apart or at a constant ratio.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Grid {
+    pub lower_price: f64,
+    pub upper_price: f64,
+    pub levels: u64,
+    #[serde(default)]
+    pub spacing: Spacing,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spacing {
+    #[default]
+    Arithmetic,
+    Geometric,
+}
+
+/// Base held by a grid level, bought at or below the level's price and sold
+/// once the price reaches the next level up.
+#[derive(Debug, Clone, Default)]
+pub struct GridLevel {
+    pub level: u64,
+    pub price: f64,
+    pub qty: f64,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Trade {
+    pub id: u64,
     pub pair: String,
     pub cycle: u64,
     pub price: f64,
@@ -50,6 +195,34 @@ pub struct Trade {
     pub platform: String,
     pub status: String,
     pub timestamp: u64,
+    pub order_id: u64,
+    pub quote_qty: f64,
+    pub commission: f64,
+    pub commission_asset: String,
+    pub bot_id: u64,
+}
+
+impl Trade {
+    /// Commission charged in the pair's base asset, e.g. DOGE on a DOGEUSDT buy.
+    pub fn base_fee(&self) -> f64 {
+        if !self.commission_asset.is_empty() && self.pair.starts_with(&self.commission_asset) {
+            self.commission
+        } else {
+            0.0
+        }
+    }
+
+    /// Commission charged in the pair's quote asset, e.g. USDT on a DOGEUSDT sell.
+    pub fn quote_fee(&self) -> f64 {
+        if !self.commission_asset.is_empty()
+            && self.pair.ends_with(&self.commission_asset)
+            && !self.pair.starts_with(&self.commission_asset)
+        {
+            self.commission
+        } else {
+            0.0
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -63,6 +236,11 @@ pub struct BotState {
     pub bottom_mfi: f64,
     pub platform: String,
     pub timestamp: u64,
+    pub take_profit_order_id: u64,
+    pub bot_id: u64,
+    /// Highest price since the cycle's entry, unlike `top_price` never reset
+    /// to the average price.
+    pub peak_price: f64,
 }
 
 #[derive(Debug, Default)]
@@ -70,6 +248,18 @@ pub struct ApiCredential {
     pub api: String,
     pub secret: String,
     pub platform: String,
+    pub name: String,
+    /// API passphrase, required by OKX and empty elsewhere.
+    pub passphrase: String,
+    /// `mainnet`, or `testnet` for Binance Spot Testnet.
+    pub environment: String,
+}
+
+#[derive(Debug, Default)]
+pub struct PaperAccount {
+    pub taker_fee: f64,
+    pub slippage: f64,
+    pub balances: Vec<Balance>,
 }
 
 #[derive(Debug)]
@@ -85,34 +275,61 @@ pub struct Transaction {
     pub pair: String,
     pub price: f64,
     pub qty: f64,
+    pub quote_qty: f64,
+    pub commission: f64,
+    pub commission_asset: String,
     pub platform: String,
     pub timestamp: u64,
 }
 
-#[allow(dead_code)]
-#[derive(Debug)]
+/// One execution of an order as reported by the exchange's trade history.
+#[derive(Debug, Clone, Default)]
+pub struct Fill {
+    pub order_id: u64,
+    pub pair: String,
+    pub price: f64,
+    pub qty: f64,
+    pub commission: f64,
+    pub commission_asset: String,
+    pub is_buyer: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Order {
     pub pair: String,
     pub order_id: u64,
+    pub side: String,
     pub price: f64,
-    pub orig_qty: String,
-    pub executed_qty: String,
+    pub orig_qty: f64,
+    pub executed_qty: f64,
+    pub quote_qty: f64,
+    pub commission: f64,
+    pub commission_asset: String,
+    pub status: String,
+    pub timestamp: u64,
 }
 
 #[derive(Debug, Default)]
 pub struct PnL {
-    pub pair: String,
     pub cycle: u64,
     pub pnl: f64,
 }
 
-#[derive(Debug, Default)]
+/// Closed kline with the indicators computed on it. Indicators still warming
+/// up, or stored before they were collected, are `None`.
+#[derive(Debug, Clone, Default)]
 pub struct Ticker {
     pub pair: String,
+    pub timestamp: u64,
     pub open: f64,
     pub high: f64,
     pub low: f64,
     pub close: f64,
     pub volume: f64,
-    pub mfi: f64,
+    pub mfi: Option<f64>,
+    pub rsi: Option<f64>,
+    pub bb_lower: Option<f64>,
+    pub ema: Option<f64>,
+    pub volume_avg: Option<f64>,
 }
@@ -0,0 +1,114 @@
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dialoguer::{theme::ColorfulTheme, Password};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::sync::OnceLock;
+use std::{env, fs};
+
+/// Marks values encrypted by `encrypt`, followed by base64 of salt, nonce and ciphertext.
+const PREFIX: &str = "enc:v1:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Passphrase protecting stored secrets, read once per process from
+/// `SPEARMINT_PASSPHRASE`, the file named by `SPEARMINT_PASSPHRASE_FILE`, or
+/// a prompt. `confirm` asks twice when prompting for a first passphrase.
+pub fn passphrase(confirm: bool) -> &'static str {
+    PASSPHRASE.get_or_init(|| read("SPEARMINT_PASSPHRASE", "Passphrase", confirm))
+}
+
+/// Replacement passphrase for `bind rotate`, from `SPEARMINT_NEW_PASSPHRASE`,
+/// `SPEARMINT_NEW_PASSPHRASE_FILE` or a prompt.
+pub fn new_passphrase() -> String {
+    read("SPEARMINT_NEW_PASSPHRASE", "New passphrase", true)
+}
+
+fn read(var: &str, prompt: &str, confirm: bool) -> String {
+    if let Ok(passphrase) = env::var(var) {
+        return passphrase;
+    }
+
+    if let Ok(path) = env::var(format!("{}_FILE", var)) {
+        let content = fs::read_to_string(path).expect("Cannot read passphrase file");
+        return content.trim_end().to_string();
+    }
+
+    let theme = ColorfulTheme::default();
+    let mut password = Password::with_theme(&theme);
+    password.with_prompt(prompt);
+    if confirm {
+        password.with_confirmation("Repeat passphrase", "Passphrases don't match");
+    }
+
+    password.interact().unwrap()
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .unwrap();
+
+    key
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(PREFIX)
+}
+
+pub fn encrypt(passphrase: &str, plaintext: &str) -> String {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive(passphrase, &salt));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .unwrap();
+
+    let mut data = salt.to_vec();
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+
+    format!("{}{}", PREFIX, STANDARD.encode(data))
+}
+
+/// Plaintext of an `encrypt`ed value, `None` when the passphrase is wrong.
+/// Values stored before encryption are returned as they are.
+pub fn decrypt(passphrase: &str, value: &str) -> Option<String> {
+    let encoded = match value.strip_prefix(PREFIX) {
+        Some(encoded) => encoded,
+        None => return Some(value.to_string()),
+    };
+
+    let data = STANDARD.decode(encoded).ok()?;
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return None;
+    }
+
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(&derive(passphrase, salt));
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+
+    String::from_utf8(plaintext).ok()
+}
+
+/// First and last four characters, the rest replaced by `*`.
+pub fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+
+    format!("{}{}{}", head, "*".repeat(chars.len() - 8), tail)
+}
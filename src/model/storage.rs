@@ -1,30 +1,42 @@
-use super::result;
+use super::bind;
+use super::context::Context;
+use super::{indicator, result};
 use rusqlite::{params, Connection, Result};
+use std::collections::HashMap;
+use std::path::Path;
 
-pub const DB_PATH: &str = "spearmint.db";
-pub const DB_DATA_PATH: &str = "spearmint_data.db";
+fn read_trade(row: &rusqlite::Row) -> Result<result::Trade> {
+    let price: f64 = row.get(3)?;
+    let qty: f64 = row.get(4)?;
 
-pub fn get_latest_trade(platform: &str, pair: &str) -> Result<result::Trade> {
-    let conn = Connection::open(DB_PATH).unwrap();
+    Ok(result::Trade {
+        id: row.get(0)?,
+        pair: row.get(1)?,
+        cycle: row.get(2)?,
+        price,
+        qty,
+        platform: row.get(5)?,
+        status: row.get(6)?,
+        timestamp: row.get(7)?,
+        order_id: row.get::<_, Option<u64>>(8)?.unwrap_or(0),
+        quote_qty: row.get::<_, Option<f64>>(9)?.unwrap_or(price * qty),
+        commission: row.get::<_, Option<f64>>(10)?.unwrap_or(0.0),
+        commission_asset: row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+        bot_id: row.get::<_, Option<u64>>(12)?.unwrap_or(0),
+    })
+}
+
+/// Latest trade setting the status of the bot's cycle. Sells that leave the
+/// cycle open, such as a partly filled take profit, don't.
+pub fn get_latest_trade(ctx: &Context, bot_id: u64) -> Result<result::Trade> {
+    let conn = ctx.conn();
     let mut stmt = conn
         .prepare(
-            "SELECT * FROM trades WHERE platform=:platform AND pair=:pair ORDER BY id DESC LIMIT 1",
+            "SELECT * FROM trades WHERE bot_id=:bot_id AND status!='SELL' ORDER BY id DESC LIMIT 1",
         )
         .unwrap();
-    let mut trades: Vec<Result<result::Trade>> = stmt
-        .query_map([platform, pair], |row| {
-            Ok(result::Trade {
-                pair: row.get(1)?,
-                cycle: row.get(2)?,
-                price: row.get(3)?,
-                qty: row.get(4)?,
-                platform: row.get(5)?,
-                status: row.get(6)?,
-                timestamp: row.get(7)?,
-            })
-        })
-        .unwrap()
-        .collect();
+    let mut trades: Vec<Result<result::Trade>> =
+        stmt.query_map([bot_id], read_trade).unwrap().collect();
 
     if trades.len() > 0 {
         trades.remove(0)
@@ -33,48 +45,37 @@ pub fn get_latest_trade(platform: &str, pair: &str) -> Result<result::Trade> {
     }
 }
 
-pub fn get_latest_pnl_trade(platform: &str, pair: &str) -> Result<result::PnL> {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn get_latest_pnl_trade(ctx: &Context, bot_id: u64) -> Result<result::PnL> {
+    let conn = ctx.conn();
     let mut stmt = conn
-    .prepare("SELECT cycle FROM trades WHERE platform=:platform AND pair=:pair AND status=:status ORDER BY timestamp DESC LIMIT 1")
+    .prepare("SELECT cycle FROM trades WHERE bot_id=:bot_id AND status IN ('CLOSE', 'STOPPED') ORDER BY timestamp DESC LIMIT 1")
     .unwrap();
     let mut trades: Vec<Result<u64>> = stmt
-        .query_map([platform, pair, "CLOSE"], |row| Ok(row.get(0)?))
+        .query_map([bot_id], |row| Ok(row.get(0)?))
         .unwrap()
         .collect();
 
     if trades.len() > 0 {
         let cycle = trades.remove(0).unwrap();
         let mut stmt = conn
-    .prepare("SELECT * FROM trades WHERE platform=:platform AND pair=:pair AND cycle=:cycle ORDER BY timestamp DESC")
+    .prepare("SELECT * FROM trades WHERE bot_id=:bot_id AND cycle=:cycle ORDER BY timestamp DESC")
     .unwrap();
         let trades: Vec<Result<result::Trade>> = stmt
-            .query_map([platform, pair, &cycle.to_string()], |row| {
-                Ok(result::Trade {
-                    pair: row.get(1)?,
-                    cycle: row.get(2)?,
-                    price: row.get(3)?,
-                    qty: row.get(4)?,
-                    platform: row.get(5)?,
-                    status: row.get(6)?,
-                    timestamp: row.get(7)?,
-                })
-            })
+            .query_map([bot_id, cycle], read_trade)
             .unwrap()
             .collect();
 
         let mut pnl: f64 = 0.0;
         for trade in trades {
             let tr = trade.unwrap();
-            if tr.status == "CLOSE" {
-                pnl = pnl + (tr.price * tr.qty);
+            if tr.status == "CLOSE" || tr.status == "STOPPED" || tr.status == "SELL" {
+                pnl = pnl + tr.quote_qty - tr.quote_fee();
             } else if tr.status == "OPEN" {
-                pnl = pnl - (tr.price * tr.qty);
+                pnl = pnl - tr.quote_qty - tr.quote_fee();
             }
         }
 
         Ok(result::PnL {
-            pair: String::from(pair),
             cycle: cycle,
             pnl: pnl,
         })
@@ -83,8 +84,8 @@ pub fn get_latest_pnl_trade(platform: &str, pair: &str) -> Result<result::PnL> {
     }
 }
 
-pub fn create_trade(trade: result::Trade) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn create_trade(ctx: &Context, trade: result::Trade) {
+    let conn = ctx.conn();
     conn.execute(
         "INSERT INTO trades (
         pair,
@@ -93,8 +94,13 @@ pub fn create_trade(trade: result::Trade) {
         qty,
         platform,
         status,
-        timestamp
-    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        timestamp,
+        order_id,
+        quote_qty,
+        commission,
+        commission_asset,
+        bot_id
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
         params![
             trade.pair,
             trade.cycle,
@@ -102,54 +108,138 @@ pub fn create_trade(trade: result::Trade) {
             trade.qty,
             trade.platform,
             trade.status,
-            trade.timestamp
+            trade.timestamp,
+            trade.order_id,
+            trade.quote_qty,
+            trade.commission,
+            trade.commission_asset,
+            trade.bot_id
+        ],
+    )
+    .unwrap();
+}
+
+pub fn update_trade_fill(ctx: &Context, id: u64, fill: &result::Trade) {
+    let conn = ctx.conn();
+    conn.execute(
+        "UPDATE trades SET
+            price=?1,
+            qty=?2,
+            order_id=?3,
+            quote_qty=?4,
+            commission=?5,
+            commission_asset=?6
+        WHERE id=?7",
+        params![
+            fill.price,
+            fill.qty,
+            fill.order_id,
+            fill.quote_qty,
+            fill.commission,
+            fill.commission_asset,
+            id
         ],
     )
     .unwrap();
 }
 
-pub fn get_avg_price(platform: &str, pair: &str, cycle: u64) -> f64 {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn delete_trade(ctx: &Context, id: u64) {
+    let conn = ctx.conn();
+    conn.execute("DELETE FROM trades WHERE id=?1", params![id])
+        .unwrap();
+}
+
+pub fn get_trades(ctx: &Context, bot_id: u64) -> Vec<Result<result::Trade>> {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT * FROM trades WHERE bot_id=:bot_id ORDER BY id")
+        .unwrap();
+    let trades: Vec<Result<result::Trade>> =
+        stmt.query_map([bot_id], read_trade).unwrap().collect();
+
+    trades
+}
+
+/// Trades of every bot on the pair, for matching against the exchange's history.
+pub fn get_pair_trades(ctx: &Context, platform: &str, pair: &str) -> Vec<Result<result::Trade>> {
+    let conn = ctx.conn();
     let mut stmt = conn
-        .prepare("SELECT * FROM trades WHERE platform=:platform AND pair=:pair AND cycle=:cycle")
+        .prepare("SELECT * FROM trades WHERE platform=:platform AND pair=:pair ORDER BY id")
         .unwrap();
     let trades: Vec<Result<result::Trade>> = stmt
-        .query_map([platform, pair, &cycle.to_string()], |row| {
-            Ok(result::Trade {
-                pair: row.get(1)?,
-                cycle: row.get(2)?,
-                price: row.get(3)?,
-                qty: row.get(4)?,
-                platform: row.get(5)?,
-                status: row.get(6)?,
-                timestamp: row.get(7)?,
-            })
-        })
+        .query_map([platform, pair], read_trade)
+        .unwrap()
+        .collect();
+
+    trades
+}
+
+/// Break-even price of the cycle's buys: quote spent plus quote fees over the
+/// base actually received.
+pub fn get_avg_price(ctx: &Context, bot_id: u64, cycle: u64) -> f64 {
+    let (cost, position) = get_position(ctx, bot_id, cycle);
+
+    if position == 0.0 {
+        return 0.0;
+    }
+
+    cost / position
+}
+
+/// Quote spent, fees included, and base held by the cycle's buys, less the
+/// share partial sells took out at the average price. Fees paid in a third
+/// asset such as BNB are ignored.
+pub fn get_position(ctx: &Context, bot_id: u64, cycle: u64) -> (f64, f64) {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT * FROM trades WHERE bot_id=:bot_id AND cycle=:cycle AND status IN ('OPEN', 'SELL') ORDER BY id")
+        .unwrap();
+    let trades: Vec<Result<result::Trade>> = stmt
+        .query_map([bot_id, cycle], read_trade)
         .unwrap()
         .collect();
 
-    let mut total_amount: f64 = 0.0;
-    let mut total_qty: f64 = 0.0;
+    let mut cost: f64 = 0.0;
+    let mut position: f64 = 0.0;
     for trade in trades {
         let item = trade.unwrap();
-        total_amount += item.price * item.qty;
-        total_qty += item.qty;
+        if item.status == "OPEN" {
+            cost += item.quote_qty + item.quote_fee();
+            position += item.qty - item.base_fee();
+        } else if position > 0.0 {
+            let sold = (item.qty / position).min(1.0);
+            cost -= cost * sold;
+            position -= position * sold;
+        }
     }
 
-    if total_qty == 0.0 {
-        return 0.0;
-    }
+    (cost, position)
+}
 
-    total_amount / total_qty
+pub fn get_opened_at(ctx: &Context, bot_id: u64, cycle: u64) -> u64 {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT timestamp FROM trades WHERE bot_id=:bot_id AND cycle=:cycle AND status='OPEN' ORDER BY id LIMIT 1")
+        .unwrap();
+    let mut trades: Vec<Result<u64>> = stmt
+        .query_map([bot_id, cycle], |row| Ok(row.get(0)?))
+        .unwrap()
+        .collect();
+
+    if trades.len() > 0 {
+        trades.remove(0).unwrap()
+    } else {
+        0
+    }
 }
 
-pub fn get_latest_state(platform: &str, pair: &str) -> Result<result::BotState> {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn get_latest_state(ctx: &Context, bot_id: u64) -> Result<result::BotState> {
+    let conn = ctx.conn();
     let mut stmt = conn
-    .prepare("SELECT * FROM bot_states WHERE platform=:platform AND pair=:pair ORDER BY timestamp DESC LIMIT 1")
+    .prepare("SELECT * FROM bot_states WHERE bot_id=:bot_id ORDER BY timestamp DESC, id DESC LIMIT 1")
     .unwrap();
     let mut states: Vec<Result<result::BotState>> = stmt
-        .query_map([platform, pair], |row| {
+        .query_map([bot_id], |row| {
             Ok(result::BotState {
                 id: row.get(0)?,
                 pair: row.get(1)?,
@@ -160,6 +250,9 @@ pub fn get_latest_state(platform: &str, pair: &str) -> Result<result::BotState>
                 bottom_mfi: row.get(6)?,
                 platform: row.get(7)?,
                 timestamp: row.get(8)?,
+                take_profit_order_id: row.get::<_, Option<u64>>(9)?.unwrap_or(0),
+                bot_id: row.get::<_, Option<u64>>(10)?.unwrap_or(0),
+                peak_price: row.get::<_, Option<f64>>(11)?.unwrap_or(0.0),
             })
         })
         .unwrap()
@@ -172,8 +265,8 @@ pub fn get_latest_state(platform: &str, pair: &str) -> Result<result::BotState>
     }
 }
 
-pub fn create_bot_state(state: result::BotState) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn create_bot_state(ctx: &Context, state: result::BotState) {
+    let conn = ctx.conn();
     conn.execute(
         "INSERT INTO bot_states (
         pair,
@@ -183,8 +276,11 @@ pub fn create_bot_state(state: result::BotState) {
         bottom_price,
         bottom_mfi,
         platform,
-        timestamp
-    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        timestamp,
+        take_profit_order_id,
+        bot_id,
+        peak_price
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
         params![
             state.pair,
             state.cycle,
@@ -193,14 +289,17 @@ pub fn create_bot_state(state: result::BotState) {
             state.bottom_price,
             state.bottom_mfi,
             state.platform,
-            state.timestamp
+            state.timestamp,
+            state.take_profit_order_id,
+            state.bot_id,
+            state.peak_price
         ],
     )
     .unwrap();
 }
 
-pub fn update_top_price(id: u64, top_price: f64) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn update_top_price(ctx: &Context, id: u64, top_price: f64) {
+    let conn = ctx.conn();
     conn.execute(
         "UPDATE bot_states SET
             top_price=?1
@@ -210,8 +309,19 @@ pub fn update_top_price(id: u64, top_price: f64) {
     .unwrap();
 }
 
-pub fn update_bottom_price(id: u64, bottom_price: f64) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn update_peak_price(ctx: &Context, id: u64, peak_price: f64) {
+    let conn = ctx.conn();
+    conn.execute(
+        "UPDATE bot_states SET
+            peak_price=?1
+        WHERE id=?2",
+        params![peak_price, id],
+    )
+    .unwrap();
+}
+
+pub fn update_bottom_price(ctx: &Context, id: u64, bottom_price: f64) {
+    let conn = ctx.conn();
     conn.execute(
         "UPDATE bot_states SET
             bottom_price=?1
@@ -221,8 +331,8 @@ pub fn update_bottom_price(id: u64, bottom_price: f64) {
     .unwrap();
 }
 
-pub fn update_bottom_mfi(id: u64, bottom_mfi: f64) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn update_bottom_mfi(ctx: &Context, id: u64, bottom_mfi: f64) {
+    let conn = ctx.conn();
     conn.execute(
         "UPDATE bot_states SET
             bottom_mfi=?1
@@ -232,41 +342,129 @@ pub fn update_bottom_mfi(id: u64, bottom_mfi: f64) {
     .unwrap();
 }
 
-pub fn update_margin_position(id: u64, position: u64) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn update_take_profit_order(ctx: &Context, id: u64, order_id: u64) {
+    let conn = ctx.conn();
     conn.execute(
         "UPDATE bot_states SET
-            margin_position=?1
+            take_profit_order_id=?1
         WHERE id=?2",
-        params![position, id],
+        params![order_id, id],
     )
     .unwrap();
 }
 
-pub fn get_wallet(quote: &str) -> f64 {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn pause_bot(ctx: &Context, bot_id: u64) {
+    let conn = ctx.conn();
+    conn.execute(
+        "UPDATE bots SET status='PAUSED' WHERE id=?1",
+        params![bot_id],
+    )
+    .unwrap();
+}
+
+/// Grid levels of the bot currently holding base.
+pub fn get_grid_levels(ctx: &Context, bot_id: u64) -> Vec<result::GridLevel> {
+    let conn = ctx.conn();
     let mut stmt = conn
-        .prepare("SELECT amount FROM tokens WHERE token=:token LIMIT 1")
+        .prepare("SELECT level, price, qty FROM grid_levels WHERE bot_id=:bot_id ORDER BY level")
         .unwrap();
-    let mut tokens: Vec<Result<f64>> = stmt
-        .query_map([quote], |row| Ok(row.get(0)?))
+    let levels: Vec<Result<result::GridLevel>> = stmt
+        .query_map([bot_id], |row| {
+            Ok(result::GridLevel {
+                level: row.get(0)?,
+                price: row.get(1)?,
+                qty: row.get(2)?,
+            })
+        })
         .unwrap()
         .collect();
 
-    tokens.remove(0).unwrap()
+    levels.into_iter().map(|level| level.unwrap()).collect()
 }
 
-pub fn update_wallet(quote: &str, amount: f64) {
-    let conn = Connection::open(DB_PATH).unwrap();
+pub fn fill_grid_level(ctx: &Context, bot_id: u64, level: result::GridLevel, timestamp: u64) {
+    let conn = ctx.conn();
     conn.execute(
-        "UPDATE tokens SET
-            amount=?1
-        WHERE token=?2",
-        params![amount, quote],
+        "INSERT INTO grid_levels (bot_id, level, price, qty, timestamp)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT (bot_id, level) DO UPDATE SET
+            price=excluded.price,
+            qty=excluded.qty,
+            timestamp=excluded.timestamp",
+        params![bot_id, level.level, level.price, level.qty, timestamp],
     )
     .unwrap();
 }
 
+pub fn update_grid_level_qty(ctx: &Context, bot_id: u64, level: u64, qty: f64) {
+    let conn = ctx.conn();
+    conn.execute(
+        "UPDATE grid_levels SET
+            qty=?1
+        WHERE bot_id=?2 AND level=?3",
+        params![qty, bot_id, level],
+    )
+    .unwrap();
+}
+
+pub fn clear_grid_level(ctx: &Context, bot_id: u64, level: u64) {
+    let conn = ctx.conn();
+    conn.execute(
+        "DELETE FROM grid_levels WHERE bot_id=?1 AND level=?2",
+        params![bot_id, level],
+    )
+    .unwrap();
+}
+
+pub fn update_margin_position(ctx: &Context, id: u64, position: u64) {
+    let conn = ctx.conn();
+    conn.execute(
+        "UPDATE bot_states SET
+            margin_position=?1
+        WHERE id=?2",
+        params![position, id],
+    )
+    .unwrap();
+}
+
+pub fn get_wallet(ctx: &Context, account: &str, quote: &str) -> f64 {
+    let conn = ctx.conn();
+    let mut stmt = conn
+        .prepare("SELECT amount FROM tokens WHERE token=:token AND account=:account LIMIT 1")
+        .unwrap();
+    let mut tokens: Vec<Result<f64>> = stmt
+        .query_map([quote, account], |row| Ok(row.get(0)?))
+        .unwrap()
+        .collect();
+
+    if tokens.len() > 0 {
+        tokens.remove(0).unwrap()
+    } else {
+        0.0
+    }
+}
+
+pub fn update_wallet(ctx: &Context, account: &str, quote: &str, amount: f64) {
+    let conn = ctx.conn();
+    let updated = conn
+        .execute(
+            "UPDATE tokens SET
+                amount=?1
+            WHERE token=?2 AND account=?3",
+            params![amount, quote, account],
+        )
+        .unwrap();
+
+    if updated == 0 {
+        let (platform, _) = bind::parse_account(account);
+        conn.execute(
+            "INSERT INTO tokens (token, amount, platform, account) VALUES (?1, ?2, ?3, ?4)",
+            params![quote, amount, platform, account],
+        )
+        .unwrap();
+    }
+}
+
 pub fn create_ticker(path: &str, ticker: result::Ticker) {
     let conn = Connection::open(path).unwrap();
     conn.execute(
@@ -278,43 +476,144 @@ pub fn create_ticker(path: &str, ticker: result::Ticker) {
         low,
         close,
         volume,
-        mfi
-    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        mfi,
+        rsi,
+        bb_lower,
+        ema,
+        volume_avg
+    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
         params![
             ticker.pair,
-            chrono::offset::Utc::now().timestamp() as u64,
+            ticker.timestamp,
             ticker.open,
             ticker.high,
             ticker.low,
             ticker.close,
             ticker.volume,
-            ticker.mfi
+            ticker.mfi,
+            ticker.rsi,
+            ticker.bb_lower,
+            ticker.ema,
+            ticker.volume_avg
         ],
     )
     .unwrap();
 }
 
-pub fn get_latest_mfi(pair: &str) -> [f64; 2] {
-    let conn = Connection::open(DB_DATA_PATH).unwrap();
+pub fn create_indicator_values(
+    path: &str,
+    pair: &str,
+    interval: &str,
+    timestamp: u64,
+    values: &[(String, f64)],
+) {
+    let conn = Connection::open(path).unwrap();
     let mut stmt = conn
-        .prepare("SELECT mfi FROM tickers WHERE pair=:pair ORDER BY timestamp DESC LIMIT 2")
+        .prepare(
+            "INSERT INTO indicator_values (pair, interval, timestamp, name, value)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
         .unwrap();
-    let mut tickers: Vec<Result<f64>> = stmt
-        .query_map([pair], |row| Ok(row.get(0)?))
+
+    for (name, value) in values {
+        stmt.execute(params![pair, interval, timestamp, name, value])
+            .unwrap();
+    }
+}
+
+/// Latest value of each indicator stored for the pair and interval. Empty
+/// when the database hasn't been collected into yet.
+pub fn get_latest_indicators(path: &str, pair: &str, interval: &str) -> HashMap<String, f64> {
+    if !Path::new(path).exists() {
+        return HashMap::new();
+    }
+
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = match conn.prepare(
+        "SELECT name, value FROM indicator_values WHERE id IN (
+            SELECT MAX(id) FROM indicator_values WHERE pair=?1 AND interval=?2 GROUP BY name
+        )",
+    ) {
+        Ok(stmt) => stmt,
+        Err(_) => return HashMap::new(),
+    };
+
+    stmt.query_map([pair, interval], |row| Ok((row.get(0)?, row.get(1)?)))
         .unwrap()
+        .filter_map(|value| value.ok())
+        .collect()
+}
+
+/// Two latest values of the pair's MFI over `period`, latest first. `None`
+/// until both have been computed on a warmed-up MFI.
+fn latest_mfi(path: &str, pair: &str, period: usize) -> Option<[f64; 2]> {
+    if !Path::new(path).exists() {
+        return None;
+    }
+
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = conn
+        .prepare(
+            "SELECT value FROM indicator_values WHERE pair=?1 AND interval=?2 AND name=?3
+            ORDER BY id DESC LIMIT 2",
+        )
+        .ok()?;
+    let values: Vec<f64> = stmt
+        .query_map(
+            params![pair, indicator::MFI_INTERVAL, indicator::mfi_name(period)],
+            |row| row.get(0),
+        )
+        .ok()?
+        .filter_map(|value| value.ok())
         .collect();
 
-    let mut ticks = [0.0, 0.0];
-    if tickers.len() > 1 {
-        ticks[0] = tickers.remove(0).unwrap();
-        ticks[1] = tickers.remove(0).unwrap();
+    match values[..] {
+        [latest, previous] => Some([latest, previous]),
+        _ => None,
     }
+}
+
+pub fn get_latest_mfi(ctx: &Context, pair: &str, period: usize) -> Option<[f64; 2]> {
+    latest_mfi(&ctx.data_path, pair, period)
+}
+
+/// The pair's MFI over `period` between `from` and `to`, by ticker timestamp.
+pub fn get_mfi_between(
+    path: &str,
+    pair: &str,
+    period: usize,
+    from: u64,
+    to: u64,
+) -> HashMap<u64, f64> {
+    let conn = Connection::open(path).unwrap();
+    let mut stmt = match conn.prepare(
+        "SELECT timestamp, value FROM indicator_values
+        WHERE pair=?1 AND interval=?2 AND name=?3 AND timestamp>=?4 AND timestamp<=?5",
+    ) {
+        Ok(stmt) => stmt,
+        Err(_) => return HashMap::new(),
+    };
+
+    let values: HashMap<u64, f64> = stmt
+        .query_map(
+            params![
+                pair,
+                indicator::MFI_INTERVAL,
+                indicator::mfi_name(period),
+                from,
+                to
+            ],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+        .filter_map(|value| value.ok())
+        .collect();
 
-    ticks
+    values
 }
 
-pub fn get_tickers(pair: &str, limit: u64) -> Vec<Result<result::Ticker>> {
-    let conn = Connection::open(DB_DATA_PATH).unwrap();
+pub fn get_tickers(ctx: &Context, pair: &str, limit: u64) -> Vec<Result<result::Ticker>> {
+    let conn = Connection::open(&ctx.data_path).unwrap();
     let mut stmt = conn
         .prepare("SELECT * FROM tickers WHERE pair=:pair ORDER BY timestamp DESC LIMIT :limit")
         .unwrap();
@@ -322,12 +621,17 @@ pub fn get_tickers(pair: &str, limit: u64) -> Vec<Result<result::Ticker>> {
         .query_map([pair, &limit.to_string()], |row| {
             Ok(result::Ticker {
                 pair: row.get(1)?,
+                timestamp: row.get(2)?,
                 open: row.get(3)?,
                 high: row.get(4)?,
                 low: row.get(5)?,
                 close: row.get(6)?,
                 volume: row.get(7)?,
                 mfi: row.get(8)?,
+                rsi: row.get(9)?,
+                bb_lower: row.get(10)?,
+                ema: row.get(11)?,
+                volume_avg: row.get(12)?,
             })
         })
         .unwrap()
@@ -336,44 +640,70 @@ pub fn get_tickers(pair: &str, limit: u64) -> Vec<Result<result::Ticker>> {
     tickers
 }
 
-pub fn get_latest_price(path: &str, pair: &str) -> result::Ticker {
+pub fn get_tickers_between(
+    path: &str,
+    pair: &str,
+    from: u64,
+    to: u64,
+) -> Vec<Result<result::Ticker>> {
     let conn = Connection::open(path).unwrap();
     let mut stmt = conn
-        .prepare("SELECT * FROM tickers WHERE pair=:pair ORDER BY timestamp DESC LIMIT 1")
+        .prepare(
+            "SELECT * FROM tickers WHERE pair=:pair AND timestamp>=:from AND timestamp<=:to ORDER BY timestamp, id",
+        )
         .unwrap();
-    let mut tickers: Vec<Result<result::Ticker>> = stmt
-        .query_map([pair], |row| {
+    let tickers: Vec<Result<result::Ticker>> = stmt
+        .query_map(params![pair, from, to], |row| {
             Ok(result::Ticker {
                 pair: row.get(1)?,
+                timestamp: row.get(2)?,
                 open: row.get(3)?,
                 high: row.get(4)?,
                 low: row.get(5)?,
                 close: row.get(6)?,
                 volume: row.get(7)?,
                 mfi: row.get(8)?,
+                rsi: row.get(9)?,
+                bb_lower: row.get(10)?,
+                ema: row.get(11)?,
+                volume_avg: row.get(12)?,
             })
         })
         .unwrap()
         .collect();
 
-    tickers.remove(0).unwrap()
+    tickers
 }
 
-pub fn get_latest_mfi1m(pair: &str) -> [f64; 2] {
-    let conn = Connection::open("ticker1m.db").unwrap();
-    let mut stmt = conn
-        .prepare("SELECT mfi FROM tickers WHERE pair=:pair ORDER BY timestamp DESC LIMIT 2")
-        .unwrap();
-    let mut tickers: Vec<Result<f64>> = stmt
-        .query_map([pair], |row| Ok(row.get(0)?))
-        .unwrap()
-        .collect();
+pub fn get_latest_price(path: &str, pair: &str) -> result::Ticker {
+    find_latest_price(path, pair).unwrap().unwrap_or_default()
+}
 
-    let mut ticks = [0.0, 0.0];
-    if tickers.len() > 1 {
-        ticks[0] = tickers.remove(0).unwrap();
-        ticks[1] = tickers.remove(0).unwrap();
-    }
+/// Latest stored ticker of the pair, `None` when there is none yet.
+pub fn find_latest_price(path: &str, pair: &str) -> Result<Option<result::Ticker>> {
+    let conn = Connection::open(path)?;
+    let mut stmt =
+        conn.prepare("SELECT * FROM tickers WHERE pair=:pair ORDER BY timestamp DESC LIMIT 1")?;
+    let mut tickers = stmt.query_map([pair], |row| {
+        Ok(result::Ticker {
+            pair: row.get(1)?,
+            timestamp: row.get(2)?,
+            open: row.get(3)?,
+            high: row.get(4)?,
+            low: row.get(5)?,
+            close: row.get(6)?,
+            volume: row.get(7)?,
+            mfi: row.get(8)?,
+            rsi: row.get(9)?,
+            bb_lower: row.get(10)?,
+            ema: row.get(11)?,
+            volume_avg: row.get(12)?,
+        })
+    })?;
+
+    tickers.next().transpose()
+}
 
-    ticks
+pub fn get_latest_mfi1m(ctx: &Context, pair: &str, period: usize) -> Option<[f64; 2]> {
+    latest_mfi(&ctx.ticker_path(indicator::MFI_INTERVAL), pair, period)
 }
@@ -1,97 +1,328 @@
-use super::{bot, result, storage};
+use super::{bind, bot, context::Context, indicator, migration, result, storage};
+use crate::connector::{binance as conn_binance, bybit};
 use binance::websockets::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::AtomicBool;
-use ta::indicators::MoneyFlowIndex;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use ta::indicators::{
+    BollingerBands, ExponentialMovingAverage, MoneyFlowIndex, RelativeStrengthIndex,
+    SimpleMovingAverage,
+};
 use ta::DataItem;
 use ta::Next;
 
-pub fn run(path: &str) {
-    let keep_running = AtomicBool::new(true);
-    let bots = bot::active().unwrap();
-
-    let mut mfis: HashMap<String, MoneyFlowIndex> = HashMap::new();
-    for val in bots.iter() {
-        let tickers = storage::get_tickers(&val.pair, 20);
-        let mut mf = MoneyFlowIndex::new(14).unwrap();
-        for tick in tickers.iter().rev() {
-            let ticker = tick.as_ref().unwrap();
-            let di = DataItem::builder()
-                .high(ticker.high)
-                .low(ticker.low)
-                .close(ticker.close)
-                .open(ticker.open)
-                .volume(ticker.volume)
-                .build()
-                .unwrap();
-
-            mf.next(&di);
+const RSI_PERIOD: usize = 14;
+const BANDS_PERIOD: usize = 20;
+const BANDS_MULTIPLIER: f64 = 2.0;
+const EMA_PERIOD: usize = 20;
+const VOLUME_PERIOD: usize = 20;
+
+/// Klines replayed on start, enough for every indicator above to warm up.
+const HISTORY: u64 = 50;
+/// Most klines the exchanges return in one request.
+const MAX_HISTORY: u64 = 1000;
+
+/// Wait before reconnecting a dropped stream, doubled while it keeps failing.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Indicators of one pair, fed every closed kline.
+struct Indicators {
+    mfi: MoneyFlowIndex,
+    rsi: RelativeStrengthIndex,
+    bands: BollingerBands,
+    ema: ExponentialMovingAverage,
+    volume: SimpleMovingAverage,
+    volume_avg: Option<f64>,
+    klines: usize,
+}
+
+impl Indicators {
+    fn new() -> Self {
+        Self {
+            mfi: MoneyFlowIndex::new(indicator::MFI_PERIOD).unwrap(),
+            rsi: RelativeStrengthIndex::new(RSI_PERIOD).unwrap(),
+            bands: BollingerBands::new(BANDS_PERIOD, BANDS_MULTIPLIER).unwrap(),
+            ema: ExponentialMovingAverage::new(EMA_PERIOD).unwrap(),
+            volume: SimpleMovingAverage::new(VOLUME_PERIOD).unwrap(),
+            volume_avg: None,
+            klines: 0,
         }
+    }
+
+    /// Sets the indicators of the closed kline, leaving those still warming
+    /// up empty. The volume average is the one of the previous klines, so a
+    /// spike doesn't raise its own baseline.
+    fn next(&mut self, ticker: &mut result::Ticker) {
+        let di = DataItem::builder()
+            .high(ticker.high)
+            .low(ticker.low)
+            .close(ticker.close)
+            .open(ticker.open)
+            .volume(ticker.volume)
+            .build()
+            .unwrap();
+
+        self.klines += 1;
+        let mfi = self.mfi.next(&di);
+        ticker.mfi = Some(mfi).filter(|_| self.klines > indicator::MFI_PERIOD);
 
-        mfis.insert(val.pair.clone(), mf);
+        let rsi = self.rsi.next(&di);
+        ticker.rsi = Some(rsi).filter(|_| self.klines > RSI_PERIOD);
+
+        let bands = self.bands.next(&di);
+        ticker.bb_lower = Some(bands.lower).filter(|_| self.klines >= BANDS_PERIOD);
+
+        let ema = self.ema.next(&di);
+        ticker.ema = Some(ema).filter(|_| self.klines >= EMA_PERIOD);
+
+        ticker.volume_avg = self.volume_avg;
+        let volume_avg = self.volume.next(ticker.volume);
+        self.volume_avg = Some(volume_avg).filter(|_| self.klines >= VOLUME_PERIOD);
     }
+}
 
-    let mut web_socket: WebSockets = WebSockets::new(|event: WebsocketEvent| {
-        match event {
-            WebsocketEvent::Kline(kline_event) => {
-                let symbol = kline_event.kline.symbol.clone();
-                let kline = kline_event.kline;
-                let open = kline.open.parse::<f64>().unwrap_or(0.0);
-                let high = kline.high.parse::<f64>().unwrap_or(0.0);
-                let low = kline.low.parse::<f64>().unwrap_or(0.0);
-                let close = kline.close.parse::<f64>().unwrap_or(0.0);
-                let volume = kline.volume.parse::<f64>().unwrap_or(0.0);
-                let is_kline_closed = kline.is_final_bar;
-
-                if is_kline_closed {
-                    let di = DataItem::builder()
-                        .high(high)
-                        .low(low)
-                        .close(close)
-                        .open(open)
-                        .volume(volume)
-                        .build()
-                        .unwrap();
-
-                    let mut mf_val = 0.0;
-                    if let Some(value) = mfis.get_mut(&symbol) {
-                        mf_val = value.next(&di);
-                    }
-
-                    storage::create_ticker(
-                        path,
-                        result::Ticker {
-                            pair: symbol.clone(),
-                            open,
-                            high,
-                            low,
-                            close,
-                            volume,
-                            mfi: mf_val,
-                        },
-                    );
+/// Closed klines of the bots' pairs, stored with their indicators in
+/// `data_path`, and the values of the indicators the bots configure for the
+/// interval in `indicator_values`. On 1m these include an MFI for every
+/// `mfi_period` the pair's bots use.
+struct Collector {
+    path: String,
+    interval: String,
+    indicators: HashMap<String, Indicators>,
+    pipelines: HashMap<String, indicator::Pipeline>,
+}
 
-                    println!(
-                        "Symbol: {}, High: {:.4}, Low: {:.4}, Close: {:.4}, Volume: {:.2}, MFI: {:.2}",
-                        symbol, high, low, close, volume, mf_val
-                    );
+impl Collector {
+    fn new(ctx: &Context, bots: &[result::Bot], interval: &str, platform: &str) -> Self {
+        let mut configs: HashMap<String, (&result::Bot, Vec<result::Indicator>)> = HashMap::new();
+        for bot in bots.iter() {
+            let (_, pair_configs) = configs
+                .entry(bot.pair.clone())
+                .or_insert_with(|| (bot, Vec::new()));
+            for config in indicator::with_mfi(bot) {
+                if config.interval == interval
+                    && !pair_configs.iter().any(|known| known.name == config.name)
+                {
+                    pair_configs.push(config);
                 }
             }
-            _ => (),
         }
 
-        Ok(())
-    });
+        let mut indicators: HashMap<String, Indicators> = HashMap::new();
+        let mut pipelines: HashMap<String, indicator::Pipeline> = HashMap::new();
+        for (pair, (bot, pair_configs)) in configs.iter() {
+            let mut pipeline = indicator::Pipeline::new(pair_configs).unwrap_or_else(|e| {
+                println!("Error: {}: {}", pair, e);
+                indicator::Pipeline::new(&[]).unwrap()
+            });
+            let history = HISTORY.max(pipeline.warm_up() as u64).min(MAX_HISTORY);
+
+            let mut pair_indicators = Indicators::new();
+            for mut ticker in warm_up_klines(ctx, bot, interval, platform, history) {
+                pipeline.next(&ticker);
+                pair_indicators.next(&mut ticker);
+            }
+
+            indicators.insert(pair.clone(), pair_indicators);
+            pipelines.insert(pair.clone(), pipeline);
+        }
+
+        Self {
+            path: ctx.data_path.clone(),
+            interval: String::from(interval),
+            indicators,
+            pipelines,
+        }
+    }
+
+    fn close(&mut self, symbol: &str, open: f64, high: f64, low: f64, close: f64, volume: f64) {
+        let mut ticker = result::Ticker {
+            pair: String::from(symbol),
+            timestamp: chrono::offset::Utc::now().timestamp() as u64,
+            open,
+            high,
+            low,
+            close,
+            volume,
+            ..Default::default()
+        };
+
+        if let Some(pipeline) = self.pipelines.get_mut(symbol) {
+            let values = pipeline.next(&ticker);
+            storage::create_indicator_values(
+                &self.path,
+                symbol,
+                &self.interval,
+                ticker.timestamp,
+                &values,
+            );
+        }
+
+        if let Some(indicators) = self.indicators.get_mut(symbol) {
+            indicators.next(&mut ticker);
+        }
+
+        let mfi = match ticker.mfi {
+            Some(mfi) => format!("{:.2}", mfi),
+            None => String::from("warming up"),
+        };
+        println!(
+            "Symbol: {}, High: {:.4}, Low: {:.4}, Close: {:.4}, Volume: {:.2}, MFI: {}",
+            symbol, high, low, close, volume, mfi
+        );
+
+        storage::create_ticker(&self.path, ticker);
+    }
+}
+
+/// The last `history` klines of the bot's pair, oldest first: the stored
+/// ones, or the exchange's when fewer than that are stored, so indicators
+/// start warmed up.
+fn warm_up_klines(
+    ctx: &Context,
+    bot: &result::Bot,
+    interval: &str,
+    platform: &str,
+    history: u64,
+) -> Vec<result::Ticker> {
+    let mut stored: Vec<result::Ticker> = storage::get_tickers(ctx, &bot.pair, history)
+        .into_iter()
+        .map(|ticker| ticker.unwrap())
+        .collect();
+    stored.reverse();
 
-    let endpoints: Vec<String> = bots
-        .iter()
-        .map(|bot| format!("{}@kline_1m", bot.pair.to_lowercase()))
+    if stored.len() as u64 >= history {
+        return stored;
+    }
+
+    let fetched = if platform == bybit::PLATFORM {
+        bybit::get_klines(&bot.pair, interval, history)
+    } else {
+        let config = conn_binance::config(&bind::environment(ctx, &bot.account()));
+        conn_binance::Connector::public(&config).get_klines(&bot.pair, interval, history as u16)
+    };
+
+    match fetched {
+        Ok(fetched) if fetched.len() > stored.len() => fetched,
+        Ok(_) => stored,
+        Err(e) => {
+            println!("Error: {}: {}", bot.pair, e);
+            stored
+        }
+    }
+}
+
+/// Listens to the `kline` feed of `platform` for the active bots trading
+/// there and stores every closed kline in `ctx.data_path`. Bots on other
+/// platforms than Bybit, paper bots included, follow Binance prices, from
+/// Spot Testnet for bots on testnet accounts.
+pub fn collect(ctx: &Context, kline: &str, platform: &str) {
+    migration::migrate(&ctx.data_path, migration::TICKER).expect("Cannot migrate database");
+
+    let bots: Vec<result::Bot> = bot::active(ctx)
+        .unwrap()
+        .into_iter()
+        .filter(|bot| (bot.platform == bybit::PLATFORM) == (platform == bybit::PLATFORM))
         .collect();
+    // Bots on a pair share its stream.
+    let mut pairs: Vec<String> = bots.iter().map(|bot| bot.pair.clone()).collect();
+    pairs.sort();
+    pairs.dedup();
+    let mut collector = Collector::new(ctx, &bots, kline, platform);
+
+    if platform == bybit::PLATFORM {
+        return stream_bybit(&pairs, kline, &mut collector);
+    }
+
+    let mut environments: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for bot in bots.iter() {
+        let streams = environments
+            .entry(bind::environment(ctx, &bot.account()))
+            .or_default();
+        let stream = format!("{}@kline_{}", bot.pair.to_lowercase(), kline);
+        if !streams.contains(&stream) {
+            streams.push(stream);
+        }
+    }
+
+    let collector = Mutex::new(collector);
+    thread::scope(|scope| {
+        for (environment, streams) in environments.iter() {
+            let collector = &collector;
+            scope.spawn(move || stream_binance(environment, streams, collector));
+        }
+    });
+}
+
+/// Feeds closed klines of the pairs to the collector, reconnecting whenever
+/// the connection drops. Reconnects back off up to `MAX_RECONNECT_DELAY`
+/// while nothing comes through.
+fn stream_bybit(pairs: &[String], kline: &str, collector: &mut Collector) {
+    let mut delay = RECONNECT_DELAY;
 
-    web_socket.connect_multiple_streams(&endpoints).unwrap();
+    loop {
+        let mut received = false;
+        let streamed = bybit::stream_klines(pairs, kline, |kline| {
+            received = true;
+            if kline.confirm {
+                collector.close(
+                    &kline.symbol,
+                    kline.open.parse::<f64>().unwrap_or(0.0),
+                    kline.high.parse::<f64>().unwrap_or(0.0),
+                    kline.low.parse::<f64>().unwrap_or(0.0),
+                    kline.close.parse::<f64>().unwrap_or(0.0),
+                    kline.volume.parse::<f64>().unwrap_or(0.0),
+                );
+            }
+        });
+
+        if let Err(e) = streamed {
+            println!("Error: {}", e);
+        }
+
+        if received {
+            delay = RECONNECT_DELAY;
+        }
+        thread::sleep(delay);
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Feeds closed klines of the streams to the collector, reconnecting
+/// whenever the connection drops.
+fn stream_binance(environment: &str, streams: &[String], collector: &Mutex<Collector>) {
+    let (config, subscription) = conn_binance::combined_streams(environment, streams);
+    let keep_running = AtomicBool::new(true);
 
-    if let Err(e) = web_socket.event_loop(&keep_running) {
-        println!("Error: {:?}", e);
-        run(path);
+    loop {
+        let mut web_socket: WebSockets = WebSockets::new(|event: WebsocketEvent| {
+            if let WebsocketEvent::Kline(kline_event) = event {
+                let kline = kline_event.kline;
+
+                if kline.is_final_bar {
+                    collector.lock().unwrap().close(
+                        &kline.symbol,
+                        kline.open.parse::<f64>().unwrap_or(0.0),
+                        kline.high.parse::<f64>().unwrap_or(0.0),
+                        kline.low.parse::<f64>().unwrap_or(0.0),
+                        kline.close.parse::<f64>().unwrap_or(0.0),
+                        kline.volume.parse::<f64>().unwrap_or(0.0),
+                    );
+                }
+            }
+
+            Ok(())
+        });
+
+        web_socket
+            .connect_with_config(&subscription, &config)
+            .unwrap();
+
+        if let Err(e) = web_socket.event_loop(&keep_running) {
+            println!("Error: {:?}", e);
+        }
     }
 }
@@ -1,4 +1,5 @@
 use crate::model::bot;
+use crate::model::context::Context;
 use crate::model::result::Bot;
 use crate::model::storage;
 use reqwest::blocking::Client;
@@ -8,10 +9,10 @@ use std::collections::HashMap;
 use std::thread;
 use std::time::{Duration, Instant};
 
-pub fn run(token: String, chat_id: u64, duration: u64) {
+pub fn run(ctx: &Context, token: String, chat_id: u64, duration: u64) {
     let interval = Duration::from_secs(duration);
     let mut last_execution_time = Instant::now();
-    let bots = bot::active().unwrap();
+    let bots = bot::active(ctx).unwrap();
     let mut trackers: HashMap<String, i32> = HashMap::new();
 
     for bot in bots.clone() {
@@ -22,23 +23,29 @@ pub fn run(token: String, chat_id: u64, duration: u64) {
 
     loop {
         if last_execution_time.elapsed() >= interval {
-            perform_task(&mut trackers, &bots, &token, chat_id);
+            perform_task(ctx, &mut trackers, &bots, &token, chat_id);
             last_execution_time = Instant::now();
         }
         thread::sleep(Duration::from_millis(100));
     }
 }
 
-fn perform_task(trackers: &mut HashMap<String, i32>, bots: &Vec<Bot>, token: &str, chat_id: u64) {
+fn perform_task(
+    ctx: &Context,
+    trackers: &mut HashMap<String, i32>,
+    bots: &Vec<Bot>,
+    token: &str,
+    chat_id: u64,
+) {
     let mut table = String::from("```\n");
     table.push_str("| Name      | Cycle   | PNL        |\n");
     table.push_str("|-----------|---------|------------|\n");
 
     let mut is_send = false;
     for bot in bots {
-        let pnl = storage::get_latest_pnl_trade(&bot.platform, &bot.pair).unwrap();
+        let pnl = storage::get_latest_pnl_trade(ctx, bot.id).unwrap();
 
-        let cycle = match trackers.get(&pnl.pair) {
+        let cycle = match trackers.get(&bot.title) {
             Some(&number) => number,
             _ => 0,
         };
@@ -46,13 +53,13 @@ fn perform_task(trackers: &mut HashMap<String, i32>, bots: &Vec<Bot>, token: &st
         if pnl.cycle > cycle as u64 {
             table.push_str(&format!(
                 "| {:<8}  | {:<7} | {:<7.3}    |\n",
-                pnl.pair,
+                bot.title,
                 pnl.cycle,
                 pnl.pnl - 0.05,
             ));
 
             is_send = true;
-            trackers.insert(String::from(&pnl.pair), pnl.cycle as i32);
+            trackers.insert(bot.title.clone(), pnl.cycle as i32);
         }
     }
 
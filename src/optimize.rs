@@ -0,0 +1,203 @@
+use crate::backtest;
+use crate::model::{args, result};
+use rand::Rng;
+use std::cmp::Ordering;
+
+enum Dimension {
+    FirstBuyIn,
+    EntryPriceChangeBelow,
+    TakeProfitPriceChangeAbove,
+    MfiBelow,
+    AmountRatio(usize),
+}
+
+#[derive(Debug)]
+pub struct Outcome {
+    pub config: result::Bot,
+    pub cycles: usize,
+    pub pnl: f64,
+    pub max_drawdown: f64,
+    pub capital: f64,
+}
+
+fn steps(range: &args::Range) -> usize {
+    if range.step <= 0.0 || range.max <= range.min {
+        return 0;
+    }
+
+    ((range.max - range.min) / range.step + 1e-9).floor() as usize
+}
+
+fn values(range: &args::Range) -> Vec<f64> {
+    (0..=steps(range))
+        .map(|i| range.min + i as f64 * range.step)
+        .collect()
+}
+
+fn sample(range: &args::Range, rng: &mut impl Rng) -> f64 {
+    range.min + rng.gen_range(0..=steps(range)) as f64 * range.step
+}
+
+fn dimensions(sweep: &args::Sweep) -> Vec<(Dimension, &args::Range)> {
+    let mut dimensions = Vec::new();
+
+    if let Some(range) = &sweep.first_buy_in {
+        dimensions.push((Dimension::FirstBuyIn, range));
+    }
+    if let Some(range) = &sweep.entry_price_change_below {
+        dimensions.push((Dimension::EntryPriceChangeBelow, range));
+    }
+    if let Some(range) = &sweep.take_profit_price_change_above {
+        dimensions.push((Dimension::TakeProfitPriceChangeAbove, range));
+    }
+    if let Some(range) = &sweep.mfi_below {
+        dimensions.push((Dimension::MfiBelow, range));
+    }
+    for (level, range) in sweep.amount_ratio.iter().enumerate() {
+        dimensions.push((Dimension::AmountRatio(level), range));
+    }
+
+    dimensions
+}
+
+fn apply(config: &mut result::Bot, dimension: &Dimension, value: f64) {
+    match dimension {
+        Dimension::FirstBuyIn => config.parameters.first_buy_in = value,
+        Dimension::EntryPriceChangeBelow => config.parameters.entry.price_change_below = value,
+        Dimension::TakeProfitPriceChangeAbove => {
+            config.parameters.take_profit.price_change_above = value
+        }
+        Dimension::MfiBelow => {
+            config.parameters.entry.mfi_below = value;
+            for margin in config.margin.margin_configuration.iter_mut() {
+                margin.mfi_below = value;
+            }
+        }
+        Dimension::AmountRatio(level) => {
+            if let Some(margin) = config.margin.margin_configuration.get_mut(*level) {
+                margin.amount_ratio = value;
+            }
+        }
+    }
+}
+
+/// Every combination of the swept values in grid mode, or `sweep.samples`
+/// random draws otherwise. Parameters without a range keep the bot's value.
+pub fn candidates(config: &result::Bot, sweep: &args::Sweep) -> Vec<result::Bot> {
+    let dimensions = dimensions(sweep);
+    let mut candidates = Vec::new();
+
+    if sweep.mode == "random" {
+        let mut rng = rand::thread_rng();
+        for _ in 0..sweep.samples {
+            let mut candidate = config.clone();
+            for (dimension, range) in dimensions.iter() {
+                apply(&mut candidate, dimension, sample(range, &mut rng));
+            }
+            candidates.push(candidate);
+        }
+
+        return candidates;
+    }
+
+    let grid: Vec<Vec<f64>> = dimensions.iter().map(|(_, range)| values(range)).collect();
+    let mut indexes = vec![0; grid.len()];
+    loop {
+        let mut candidate = config.clone();
+        for (i, (dimension, _)) in dimensions.iter().enumerate() {
+            apply(&mut candidate, dimension, grid[i][indexes[i]]);
+        }
+        candidates.push(candidate);
+
+        let mut i = 0;
+        while i < indexes.len() {
+            indexes[i] += 1;
+            if indexes[i] < grid[i].len() {
+                break;
+            }
+            indexes[i] = 0;
+            i += 1;
+        }
+
+        if i == indexes.len() {
+            break;
+        }
+    }
+
+    candidates
+}
+
+fn compare(a: &Outcome, b: &Outcome, rank_by: &str) -> Ordering {
+    let pnl = b.pnl.partial_cmp(&a.pnl).unwrap_or(Ordering::Equal);
+    let drawdown = a
+        .max_drawdown
+        .partial_cmp(&b.max_drawdown)
+        .unwrap_or(Ordering::Equal);
+    let capital = a.capital.partial_cmp(&b.capital).unwrap_or(Ordering::Equal);
+
+    match rank_by {
+        "drawdown" => drawdown.then(pnl).then(capital),
+        "capital" => capital.then(pnl).then(drawdown),
+        _ => pnl.then(drawdown).then(capital),
+    }
+}
+
+/// Sorts the outcomes best first by `rank_by`, `pnl`, `drawdown` or
+/// `capital`, breaking ties on the other two.
+pub fn rank(outcomes: &mut [Outcome], rank_by: &str) {
+    outcomes.sort_by(|a, b| compare(a, b, rank_by));
+}
+
+pub fn run(
+    config: &result::Bot,
+    tickers: &[result::Ticker],
+    sweep: &args::Sweep,
+    fee: f64,
+) -> Result<Vec<Outcome>, String> {
+    let candidates = candidates(config, sweep);
+    let total = candidates.len();
+    let mut outcomes: Vec<Outcome> = Vec::new();
+
+    for (i, candidate) in candidates.into_iter().enumerate() {
+        let capital = backtest::required_capital(&candidate);
+        let reports = backtest::run(&candidate, tickers, capital, fee)?;
+
+        let outcome = Outcome {
+            cycles: reports.len(),
+            pnl: reports.iter().map(|report| report.pnl).sum(),
+            max_drawdown: reports
+                .iter()
+                .map(|report| report.max_drawdown)
+                .fold(0.0, f64::max),
+            capital: reports
+                .iter()
+                .map(|report| report.capital_locked)
+                .fold(0.0, f64::max),
+            config: candidate,
+        };
+
+        println!(
+            "[{}/{}] PnL: {:.4}, Max DD: {:.4}, Capital: {:.4}",
+            i + 1,
+            total,
+            outcome.pnl,
+            outcome.max_drawdown,
+            outcome.capital
+        );
+        outcomes.push(outcome);
+    }
+
+    rank(&mut outcomes, &sweep.rank_by);
+
+    Ok(outcomes)
+}
+
+/// Bot configuration in the same format `apply` reads.
+pub fn to_toml(config: &result::Bot) -> String {
+    let mut value = toml::Value::try_from(config).unwrap();
+    if let Some(table) = value.as_table_mut() {
+        table.insert(String::from("kind"), toml::Value::from("bot"));
+    }
+
+    toml::to_string(&value).unwrap()
+}
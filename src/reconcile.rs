@@ -0,0 +1,182 @@
+use crate::model::context::Context;
+use crate::model::{result, storage};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+
+/// Relative difference tolerated between local and exchange amounts.
+const TOLERANCE: f64 = 1e-6;
+/// Seconds between a legacy row (recorded without order id) and the exchange fill.
+const LEGACY_WINDOW: u64 = 120;
+
+#[derive(Debug)]
+pub enum Discrepancy {
+    /// Exchange order with no local row.
+    Missing(result::Trade),
+    /// Local row repeating an order id already recorded.
+    Duplicated(result::Trade),
+    /// Local row whose fills disagree with the exchange.
+    Mismatched {
+        local: result::Trade,
+        exchange: result::Trade,
+    },
+}
+
+fn differs(a: f64, b: f64) -> bool {
+    (a - b).abs() > TOLERANCE * a.abs().max(b.abs()).max(1.0)
+}
+
+fn is_buy(trade: &result::Trade) -> bool {
+    trade.status == "OPEN"
+}
+
+/// Sums fills per order. Buys become OPEN trades and sells CLOSE trades, with
+/// the price volume-weighted over the fills and the timestamp in seconds.
+pub fn orders_from_fills(fills: &[result::Fill], since: u64) -> Vec<result::Trade> {
+    let mut orders: BTreeMap<u64, result::Trade> = BTreeMap::new();
+
+    for fill in fills.iter().filter(|fill| fill.timestamp / 1000 >= since) {
+        let order = orders
+            .entry(fill.order_id)
+            .or_insert_with(|| result::Trade {
+                pair: fill.pair.clone(),
+                status: String::from(if fill.is_buyer { "OPEN" } else { "CLOSE" }),
+                timestamp: fill.timestamp / 1000,
+                order_id: fill.order_id,
+                commission_asset: fill.commission_asset.clone(),
+                ..Default::default()
+            });
+
+        order.qty += fill.qty;
+        order.quote_qty += fill.qty * fill.price;
+        if fill.commission_asset == order.commission_asset {
+            order.commission += fill.commission;
+        }
+    }
+
+    let mut orders: Vec<result::Trade> = orders.into_values().collect();
+    for order in orders.iter_mut() {
+        order.price = order.quote_qty / order.qty;
+    }
+    orders.sort_by_key(|order| order.timestamp);
+
+    orders
+}
+
+/// Compares the bot's local trades of every cycle with the exchange's fills
+/// since its first recorded trade. Orders recorded by other bots on the same
+/// pair are left out.
+pub fn run(ctx: &Context, config: &result::Bot, fills: &[result::Fill]) -> Vec<Discrepancy> {
+    let trades: Vec<result::Trade> = storage::get_trades(ctx, config.id)
+        .into_iter()
+        .map(|trade| trade.unwrap())
+        .collect();
+    let others: HashSet<u64> = storage::get_pair_trades(ctx, &config.platform, &config.pair)
+        .into_iter()
+        .map(|trade| trade.unwrap())
+        .filter(|trade| trade.bot_id != config.id && trade.order_id != 0)
+        .map(|trade| trade.order_id)
+        .collect();
+
+    let since = match trades.first() {
+        Some(trade) => trade.timestamp,
+        None => return Vec::new(),
+    };
+
+    let mut discrepancies: Vec<Discrepancy> = Vec::new();
+    let mut recorded: BTreeMap<u64, &result::Trade> = BTreeMap::new();
+    for trade in trades.iter().filter(|trade| trade.order_id != 0) {
+        match recorded.entry(trade.order_id) {
+            Entry::Occupied(_) => discrepancies.push(Discrepancy::Duplicated(trade.clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(trade);
+            }
+        }
+    }
+
+    let mut matched: HashSet<u64> = HashSet::new();
+    for mut order in orders_from_fills(fills, since)
+        .into_iter()
+        .filter(|order| !others.contains(&order.order_id))
+    {
+        let local = recorded.get(&order.order_id).copied().or_else(|| {
+            trades.iter().find(|trade| {
+                trade.order_id == 0
+                    && trade.status != "WAIT"
+                    && !matched.contains(&trade.id)
+                    && is_buy(trade) == is_buy(&order)
+                    && trade.timestamp.abs_diff(order.timestamp) <= LEGACY_WINDOW
+            })
+        });
+
+        match local {
+            Some(local) => {
+                matched.insert(local.id);
+
+                if local.order_id != order.order_id
+                    || differs(local.qty, order.qty)
+                    || differs(local.quote_qty, order.quote_qty)
+                    || differs(local.commission, order.commission)
+                {
+                    order.cycle = local.cycle;
+                    order.platform = config.platform.clone();
+                    order.bot_id = config.id;
+                    discrepancies.push(Discrepancy::Mismatched {
+                        local: local.clone(),
+                        exchange: order,
+                    });
+                }
+            }
+            None => {
+                order.cycle = trades
+                    .iter()
+                    .rev()
+                    .find(|trade| trade.timestamp <= order.timestamp)
+                    .map_or(0, |trade| trade.cycle);
+                order.platform = config.platform.clone();
+                order.bot_id = config.id;
+                discrepancies.push(Discrepancy::Missing(order));
+            }
+        }
+    }
+
+    discrepancies
+}
+
+/// Replaces the local rows with the exchange's version after confirmation.
+pub fn import(ctx: &Context, discrepancies: Vec<Discrepancy>) {
+    if !Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Import the exchange's version?")
+        .default(false)
+        .interact()
+        .unwrap()
+    {
+        println!("Ok, nevermind then");
+        return;
+    }
+
+    println!("{} discrepancies imported!", apply(ctx, discrepancies));
+}
+
+/// Writes the exchange's version of every discrepancy in one transaction, so
+/// an interrupted import leaves the trades as they were. Returns how many
+/// were written.
+pub fn apply(ctx: &Context, discrepancies: Vec<Discrepancy>) -> usize {
+    let total = discrepancies.len();
+    let _ = ctx.transaction(|| {
+        for discrepancy in discrepancies {
+            match discrepancy {
+                Discrepancy::Missing(trade) => storage::create_trade(ctx, trade),
+                Discrepancy::Duplicated(trade) => storage::delete_trade(ctx, trade.id),
+                Discrepancy::Mismatched { local, exchange } => {
+                    storage::update_trade_fill(ctx, local.id, &exchange)
+                }
+            }
+        }
+
+        Ok::<(), Infallible>(())
+    });
+
+    total
+}
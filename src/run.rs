@@ -1,6 +1,7 @@
-use crate::bot::{BotBuilder, BotInfo};
-use crate::connector::binance as conn_binance;
+use crate::bot::{Bot, BotBuilder, BotInfo};
+use crate::connector::{self, binance as conn_binance, bybit};
 use crate::model;
+use crate::model::context::Context;
 use crate::model::{result, storage, Exchange, Strategy};
 use crate::strategy;
 use binance::websockets::*;
@@ -12,77 +13,88 @@ use std::thread;
 use std::time::{Duration, Instant};
 use std::{fs, panic};
 
-pub fn run(bot: Arc<super::bot::Bot<impl Exchange, impl Strategy>>, duration: u64) {
+pub fn run(bot: Arc<Bot<impl Exchange, impl Strategy>>, duration: u64) {
     let keep_running = AtomicBool::new(true);
     let mut last_block_time = Instant::now();
     let block_interval = Duration::from_secs(duration);
+    let ctx = bot.context.as_ref().unwrap();
     let info = bot.info.as_ref().unwrap();
 
-    let mut web_socket: WebSockets<'_> = WebSockets::new(|event: WebsocketEvent| {
-        if let WebsocketEvent::DayTicker(ticker_event) = event {
-            print!("\x1B[2J\x1B[1;1H");
+    let mut on_price = |price: f64| {
+        print!("\x1B[2J\x1B[1;1H");
 
-            let state = match storage::get_latest_state(&info.platform, &info.pair) {
-                Ok(state) => state,
-                Err(_error) => Default::default(),
-            };
+        let state = match storage::get_latest_state(ctx, info.id) {
+            Ok(state) => state,
+            Err(_error) => Default::default(),
+        };
 
-            let wallet = storage::get_wallet(&info.quote);
-            let avg_price = storage::get_avg_price(&info.platform, &info.pair, state.cycle);
-            let avg_percent_change = strategy::calculate_percent_change(
-                avg_price,
-                ticker_event.current_close.parse().unwrap(),
-            );
-            let mfi = storage::get_latest_mfi(&info.pair);
-            let mfi_dir = if mfi[0] > mfi[1] {
-                "UP".to_string()
-            } else {
-                "DOWN".to_string()
-            };
+        let wallet = storage::get_wallet(ctx, &info.account, &info.quote);
+        let avg_price = storage::get_avg_price(ctx, info.id, state.cycle);
+        let avg_percent_change = strategy::calculate_percent_change(avg_price, price);
+        let mfi = storage::get_latest_mfi(ctx, &info.pair, info.mfi_period);
+        let (mfi, mfi_dir) = display_mfi(mfi);
 
-            let mut table = Table::new();
-            table.set_header(vec![
-                "MFI",
-                "Pair",
-                "Price",
-                "AVG",
-                "P.Change",
-                "T.Price",
-                "B.Price",
-                "B.MFI",
-                "MFI Dir",
-                "Wallet",
-                "Cycle",
-                "M.Position",
-            ]);
-            table.add_row(vec![
-                &format!("{:.4}", mfi[0]),
-                &info.pair,
-                &format!("{:.4}", ticker_event.current_close),
-                &format!("{:.4}", avg_price),
-                &format!("{:.2}%", avg_percent_change),
-                &format!("{:.4}", state.top_price),
-                &format!("{:.4}", state.bottom_price),
-                &format!("{:.4}", state.bottom_mfi),
-                &format!("{}", mfi_dir),
-                &format!("{:.4}", wallet),
-                &format!("{}", state.cycle),
-                &format!("{}", state.margin_position),
-            ]);
+        let mut table = Table::new();
+        table.set_header(vec![
+            "MFI",
+            "Pair",
+            "Price",
+            "AVG",
+            "P.Change",
+            "T.Price",
+            "B.Price",
+            "B.MFI",
+            "MFI Dir",
+            "Wallet",
+            "Cycle",
+            "M.Position",
+        ]);
+        table.add_row(vec![
+            &mfi,
+            &info.pair,
+            &format!("{:.4}", price),
+            &format!("{:.4}", avg_price),
+            &format!("{:.2}%", avg_percent_change),
+            &format!("{:.4}", state.top_price),
+            &format!("{:.4}", state.bottom_price),
+            &format!("{:.4}", state.bottom_mfi),
+            &format!("{}", mfi_dir),
+            &format!("{:.4}", wallet),
+            &format!("{}", state.cycle),
+            &format!("{}", state.margin_position),
+        ]);
+
+        println!("{}", table);
+
+        if last_block_time.elapsed() >= block_interval {
+            bot.update(price);
+            last_block_time = Instant::now();
+        }
+    };
 
-            println!("{}", table);
+    if info.platform == bybit::PLATFORM {
+        let streamed = bybit::stream_klines(std::slice::from_ref(&info.pair), "1m", |kline| {
+            on_price(kline.close.parse().unwrap_or(0.0))
+        });
 
-            if last_block_time.elapsed() >= block_interval {
-                bot.update(ticker_event.current_close.parse().unwrap());
-                last_block_time = Instant::now();
-            }
+        if let Err(e) = streamed {
+            println!("Error: {}", e);
+            run(bot.clone(), duration);
+        }
+        return;
+    }
+
+    let mut web_socket: WebSockets<'_> = WebSockets::new(|event: WebsocketEvent| {
+        if let WebsocketEvent::DayTicker(ticker_event) = event {
+            on_price(ticker_event.current_close.parse().unwrap());
         }
 
         Ok(())
     });
 
+    let config = conn_binance::config(&model::bind::environment(ctx, &info.account));
     web_socket
-        .connect(&format!("{}@ticker", &info.pair.to_lowercase()))
+        .connect_with_config(&format!("{}@ticker", &info.pair.to_lowercase()), &config)
         .unwrap();
 
     if let Err(e) = web_socket.event_loop(&keep_running) {
@@ -93,7 +105,7 @@ pub fn run(bot: Arc<super::bot::Bot<impl Exchange, impl Strategy>>, duration: u6
     web_socket.disconnect().unwrap();
 }
 
-pub fn run_all(bots: Vec<result::Bot>, duration: u64) {
+pub fn run_all(ctx: &Context, bots: Vec<result::Bot>, duration: u64) {
     if let Err(e) = setup_logger() {
         eprintln!("Failed to set up logger: {}", e);
         return;
@@ -108,6 +120,10 @@ pub fn run_all(bots: Vec<result::Bot>, duration: u64) {
         error!("Application panicked: {}", panic_message);
     }));
 
+    // Each bot keeps its connector across ticks, and with it the exchange
+    // data it caches, such as Binance symbol filters.
+    let built: Vec<_> = bots.iter().map(|val| build(ctx, val)).collect();
+
     loop {
         print!("{esc}c", esc = 27 as char);
 
@@ -127,50 +143,34 @@ pub fn run_all(bots: Vec<result::Bot>, duration: u64) {
             "M.Position",
         ]);
 
-        for val in bots.iter() {
-            let strategy = strategy::helldiver::HellDiverStrategy {
-                first_buy_in: val.parameters.first_buy_in,
-                entry: val.parameters.entry.clone(),
-                take_profit: val.parameters.take_profit.clone(),
-                margin_configuration: val.margin.margin_configuration.clone(),
-            };
-            let credential = model::bind::get(&val.platform);
-            let account =
-                conn_binance::Connector::from_credential(credential.api, credential.secret);
-            let bot =
-                BotBuilder::<conn_binance::Connector, strategy::helldiver::HellDiverStrategy>::new(
-                )
-                .with_info(BotInfo {
-                    platform: val.platform.clone(),
-                    pair: val.pair.clone(),
-                    base: val.base.clone(),
-                    quote: val.quote.clone(),
-                })
-                .with_strategy(strategy)
-                .with_connector(account)
-                .build();
-
-            let ticker = storage::get_latest_price("ticker1m.db", &val.pair);
-
-            bot.update(ticker.close);
-
-            let state = match storage::get_latest_state(&val.platform, &val.pair) {
+        for (val, bot) in bots.iter().zip(built.iter()) {
+            let ticker = storage::get_latest_price(&ctx.ticker_path("1m"), &val.pair);
+
+            let active = model::bot::get(ctx, &val.title).is_ok_and(|bot| bot.status == "ACTIVE");
+
+            if active {
+                match bot {
+                    Ok(bot) => bot.update(ticker.close),
+                    Err(e) => error!("{}: {}", val.title, e),
+                }
+            }
+
+            let state = match storage::get_latest_state(ctx, val.id) {
                 Ok(state) => state,
                 Err(_error) => Default::default(),
             };
 
-            let wallet = storage::get_wallet(&val.quote);
-            let avg_price = storage::get_avg_price(&val.platform, &val.pair, state.cycle);
+            let wallet = storage::get_wallet(ctx, &val.account(), &val.quote);
+            let avg_price = storage::get_avg_price(ctx, val.id, state.cycle);
             let avg_percent_change = strategy::calculate_percent_change(avg_price, ticker.close);
-            let mfi = storage::get_latest_mfi1m(&val.pair);
-            let mfi_dir = if mfi[0] > mfi[1] {
-                "UP".to_string()
-            } else {
-                "DOWN".to_string()
-            };
+            let (mfi, mfi_dir) = display_mfi(storage::get_latest_mfi1m(
+                ctx,
+                &val.pair,
+                val.parameters.mfi_period,
+            ));
 
             table.add_row(vec![
-                &format!("{:.4}", &mfi[0]),
+                &mfi,
                 &val.pair,
                 &format!("{:.4}", ticker.close),
                 &format!("{:.4}", avg_price),
@@ -191,6 +191,52 @@ pub fn run_all(bots: Vec<result::Bot>, duration: u64) {
     }
 }
 
+/// Latest MFI and its direction as shown in the tables, `-` while the MFI is
+/// warming up.
+fn display_mfi(mfi: Option<[f64; 2]>) -> (String, String) {
+    match mfi {
+        Some([latest, previous]) => (
+            format!("{:.4}", latest),
+            String::from(if latest > previous { "UP" } else { "DOWN" }),
+        ),
+        None => (String::from("-"), String::from("-")),
+    }
+}
+
+/// Bot with the connector of its account and the strategy it names.
+pub fn build(
+    ctx: &Context,
+    config: &result::Bot,
+) -> Result<Arc<Bot<impl Exchange, impl Strategy>>, String> {
+    let connector = connector::build(ctx, &config.account(), &config.base, &config.quote)?;
+
+    build_bot(ctx, config, connector)
+}
+
+pub fn build_bot<T: Exchange>(
+    ctx: &Context,
+    config: &result::Bot,
+    connector: T,
+) -> Result<Arc<Bot<impl Exchange, impl Strategy>>, String> {
+    let strategy = strategy::build(config)?;
+
+    Ok(BotBuilder::<T, Box<dyn Strategy>>::new()
+        .with_info(BotInfo {
+            id: config.id,
+            platform: config.platform.clone(),
+            account: config.account(),
+            pair: config.pair.clone(),
+            base: config.base.clone(),
+            quote: config.quote.clone(),
+            mfi_period: config.parameters.mfi_period,
+            indicators: config.indicators.clone(),
+        })
+        .with_context(ctx.clone())
+        .with_strategy(strategy)
+        .with_connector(connector)
+        .build())
+}
+
 fn setup_logger() -> Result<(), Box<dyn std::error::Error>> {
     let log_file_path = "error.log";
 
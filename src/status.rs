@@ -0,0 +1,63 @@
+use crate::backtest;
+use crate::model::context::Context;
+use crate::model::{result, storage};
+
+#[derive(Debug, Default)]
+pub struct Status {
+    pub cycle: u64,
+    pub trade_status: String,
+    pub price: f64,
+    pub avg_price: f64,
+    pub position: f64,
+    pub cost: f64,
+    pub margin_used: u64,
+    pub margin_available: u64,
+    pub top_price: f64,
+    pub bottom_price: f64,
+    pub bottom_mfi: f64,
+    pub unrealized_pnl: f64,
+    pub capital_needed: f64,
+}
+
+/// Live state of a bot's current cycle, valued at the latest collected ticker.
+pub fn get(ctx: &Context, config: &result::Bot) -> Status {
+    let state = storage::get_latest_state(ctx, config.id).unwrap_or_default();
+    let trade = storage::get_latest_trade(ctx, config.id).unwrap_or_default();
+    let ticker = storage::get_latest_price(&ctx.ticker_path("1m"), &config.pair);
+    let avg_price = storage::get_avg_price(ctx, config.id, state.cycle);
+
+    let (cost, position) = if trade.status == "OPEN" {
+        storage::get_position(ctx, config.id, state.cycle)
+    } else {
+        (0.0, 0.0)
+    };
+
+    let margin_available = config.margin.margin_configuration.len() as u64;
+    let margin_used = state.margin_position.min(margin_available);
+    let remaining: f64 = config.margin.margin_configuration[margin_used as usize..]
+        .iter()
+        .map(|margin| margin.amount_ratio * config.parameters.first_buy_in)
+        .sum();
+
+    let capital_needed = if trade.status == "OPEN" {
+        remaining
+    } else {
+        backtest::required_capital(config)
+    };
+
+    Status {
+        cycle: state.cycle,
+        trade_status: trade.status,
+        price: ticker.close,
+        avg_price,
+        position,
+        cost,
+        margin_used,
+        margin_available,
+        top_price: state.top_price,
+        bottom_price: state.bottom_price,
+        bottom_mfi: state.bottom_mfi,
+        unrealized_pnl: position * ticker.close - cost,
+        capital_needed,
+    }
+}
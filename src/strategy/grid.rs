@@ -0,0 +1,88 @@
+use crate::model::{result, BotCommand, Session, Strategy};
+
+pub const NAME: &str = "grid";
+
+/// Range-bound ladder between the lower and upper price. A level buys
+/// `first_buy_in` worth of base once the price is at or below it and sells
+/// that base once the price reaches the next level up, so every round trip
+/// gains at least one level's spacing.
+#[derive(Debug)]
+pub struct GridStrategy {
+    pub amount: f64,
+    pub prices: Vec<f64>,
+}
+
+impl GridStrategy {
+    pub fn from_config(config: &result::Bot) -> Result<Self, String> {
+        let grid = config
+            .parameters
+            .grid
+            .as_ref()
+            .ok_or_else(|| String::from("grid strategy needs [parameters.grid]"))?;
+
+        if grid.levels < 2 {
+            return Err(format!("grid needs at least 2 levels, got {}", grid.levels));
+        }
+        if grid.lower_price <= 0.0 || grid.upper_price <= grid.lower_price {
+            return Err(format!(
+                "grid lower price {} has to be above 0 and below the upper price {}",
+                grid.lower_price, grid.upper_price
+            ));
+        }
+
+        Ok(Self {
+            amount: config.parameters.first_buy_in,
+            prices: levels(grid),
+        })
+    }
+}
+
+/// Prices of the grid's levels, from the lower to the upper price.
+pub fn levels(grid: &result::Grid) -> Vec<f64> {
+    let steps = (grid.levels - 1) as f64;
+
+    (0..grid.levels)
+        .map(|level| {
+            let position = level as f64 / steps;
+            match grid.spacing {
+                result::Spacing::Arithmetic => {
+                    grid.lower_price + (grid.upper_price - grid.lower_price) * position
+                }
+                result::Spacing::Geometric => {
+                    grid.lower_price * (grid.upper_price / grid.lower_price).powf(position)
+                }
+            }
+        })
+        .collect()
+}
+
+impl Strategy for GridStrategy {
+    /// One order per tick: the highest level ready to sell first, otherwise
+    /// the lowest empty level at or above the price. Nothing is bought below
+    /// the range, and the top level only ever sells into.
+    fn run(&self, price: f64, session: Session) -> BotCommand {
+        let held = |level: usize| {
+            session
+                .grid_levels
+                .iter()
+                .any(|held| held.level == level as u64 && held.qty > 0.0)
+        };
+        let buy_levels = self.prices.len() - 1;
+
+        if let Some(level) = (0..buy_levels)
+            .rev()
+            .find(|&level| held(level) && price >= self.prices[level + 1])
+        {
+            return BotCommand::GridSell(level as u64);
+        }
+
+        if price < self.prices[0] {
+            return BotCommand::Pause();
+        }
+
+        match (0..buy_levels).find(|&level| !held(level) && price <= self.prices[level]) {
+            Some(level) => BotCommand::GridBuy(level as u64, self.amount),
+            None => BotCommand::Pause(),
+        }
+    }
+}
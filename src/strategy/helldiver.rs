@@ -1,5 +1,7 @@
-use super::calculate_percent_change;
-use crate::model::{result, BotCommand, Session, Strategy};
+use super::{calculate_percent_change, conditions_met};
+use crate::model::{result, BotCommand, Exit, Session, Strategy};
+
+pub const NAME: &str = "helldiver";
 
 #[derive(Debug)]
 pub struct HellDiverStrategy {
@@ -7,6 +9,19 @@ pub struct HellDiverStrategy {
     pub entry: result::OpenCriteria,
     pub take_profit: result::CloseCriteria,
     pub margin_configuration: Vec<result::OpenCriteria>,
+    pub stop_loss: Option<result::StopLoss>,
+}
+
+impl HellDiverStrategy {
+    pub fn from_config(config: &result::Bot) -> Self {
+        Self {
+            first_buy_in: config.parameters.first_buy_in,
+            entry: config.parameters.entry.clone(),
+            take_profit: config.parameters.take_profit.clone(),
+            margin_configuration: config.margin.margin_configuration.clone(),
+            stop_loss: config.parameters.stop_loss.clone(),
+        }
+    }
 }
 
 impl Strategy for HellDiverStrategy {
@@ -14,13 +29,25 @@ impl Strategy for HellDiverStrategy {
         let avg_percent_change = calculate_percent_change(session.avg_price, price);
         let top_percent_change = calculate_percent_change(session.top_price, price);
         let bottom_percent_change = calculate_percent_change(session.bottom_price, price);
-        let mfi_bottom_change = session.mfi - session.bottom_mfi;
+        let peak_percent_change = calculate_percent_change(session.peak_price, price);
+        // Entries and margin buys wait for a warmed-up MFI, exits don't.
+        let mfi = session.mfi.map(|mfi| (mfi, mfi - session.bottom_mfi));
 
         if session.status == "OPEN" {
             let margin_len = self.margin_configuration.len() as u64;
 
-            if self.is_sell_signal(top_percent_change, avg_percent_change) {
-                return BotCommand::Sell();
+            if !self.take_profit.limit_order
+                && self.is_sell_signal(top_percent_change, avg_percent_change)
+            {
+                return BotCommand::Sell(Exit::TakeProfit);
+            }
+
+            if self.is_stop_loss_signal(
+                avg_percent_change,
+                peak_percent_change,
+                session.timestamp.saturating_sub(session.opened_at),
+            ) {
+                return BotCommand::Sell(Exit::StopLoss);
             }
 
             if session.margin_position < margin_len
@@ -29,11 +56,18 @@ impl Strategy for HellDiverStrategy {
                     bottom_percent_change,
                     session.margin_position as usize,
                 )
-                && self.is_mfi_approved(
-                    session.mfi,
-                    mfi_bottom_change,
-                    &session.mfi_dir,
-                    session.margin_position as usize,
+                && mfi.is_some_and(|(mfi, mfi_bottom_change)| {
+                    self.is_mfi_approved(
+                        mfi,
+                        mfi_bottom_change,
+                        &session.mfi_dir,
+                        session.margin_position as usize,
+                    )
+                })
+                && conditions_met(
+                    &self.margin_configuration[session.margin_position as usize],
+                    price,
+                    &session,
                 )
             {
                 return BotCommand::Buy(
@@ -41,9 +75,18 @@ impl Strategy for HellDiverStrategy {
                         * self.first_buy_in,
                 );
             }
+
+            if self.take_profit.limit_order && session.take_profit_order_id == 0 {
+                return BotCommand::LimitSell(
+                    session.avg_price * (1.0 + self.take_profit.price_change_above / 100.0),
+                );
+            }
         } else if session.status == "WAIT" {
             if self.is_entry_signal(top_percent_change, bottom_percent_change)
-                && self.is_entry_mfi_approved(session.mfi, mfi_bottom_change, &session.mfi_dir)
+                && mfi.is_some_and(|(mfi, mfi_bottom_change)| {
+                    self.is_entry_mfi_approved(mfi, mfi_bottom_change, &session.mfi_dir)
+                })
+                && conditions_met(&self.entry, price, &session)
             {
                 return BotCommand::Entry(self.first_buy_in);
             }
@@ -51,7 +94,9 @@ impl Strategy for HellDiverStrategy {
 
         BotCommand::Pause()
     }
+}
 
+impl HellDiverStrategy {
     fn is_entry_signal(&self, top_percent_change: f64, bottom_percent_change: f64) -> bool {
         top_percent_change < self.entry.price_change_below
             && bottom_percent_change > self.entry.price_callback
@@ -87,4 +132,26 @@ impl Strategy for HellDiverStrategy {
             && mfi_bottom_change > self.margin_configuration[margin_position].mfi_callback
             && mfi_dir == "UP"
     }
+
+    fn is_stop_loss_signal(
+        &self,
+        avg_percent_change: f64,
+        peak_percent_change: f64,
+        cycle_age: u64,
+    ) -> bool {
+        let stop_loss = match &self.stop_loss {
+            Some(stop_loss) => stop_loss,
+            None => return false,
+        };
+
+        stop_loss
+            .price_change_below
+            .map_or(false, |below| avg_percent_change < below)
+            || stop_loss
+                .top_price_change_below
+                .map_or(false, |below| peak_percent_change < below)
+            || stop_loss
+                .max_cycle_hours
+                .map_or(false, |hours| cycle_age as f64 > hours * 3600.0)
+    }
 }
@@ -1,5 +1,34 @@
+use crate::model::{result, Session, Strategy};
+
+pub mod grid;
 pub mod helldiver;
 
+/// Strategies a bot can run, by the name used in `strategy`.
+pub const STRATEGIES: [&str; 2] = [helldiver::NAME, grid::NAME];
+
+pub fn check(name: &str) -> Result<(), String> {
+    if STRATEGIES.contains(&name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unknown strategy `{}`, expected one of {}",
+            name,
+            STRATEGIES.join(", ")
+        ))
+    }
+}
+
+/// Strategy of the bot, set up from its parameters.
+pub fn build(config: &result::Bot) -> Result<Box<dyn Strategy>, String> {
+    check(&config.strategy)?;
+
+    if config.strategy == grid::NAME {
+        return Ok(Box::new(grid::GridStrategy::from_config(config)?));
+    }
+
+    Ok(Box::new(helldiver::HellDiverStrategy::from_config(config)))
+}
+
 pub fn calculate_percent_change(old_value: f64, new_value: f64) -> f64 {
     if old_value == 0.0 {
         return 0.0;
@@ -7,3 +36,48 @@ pub fn calculate_percent_change(old_value: f64, new_value: f64) -> f64 {
 
     ((new_value - old_value) / old_value) * 100.0
 }
+
+/// Whether the indicator conditions of `criteria` hold for the session, all
+/// of them or any one depending on `combine`. Criteria without conditions
+/// always pass, and a condition on an indicator still warming up, or not
+/// collected, never does.
+pub fn conditions_met(criteria: &result::OpenCriteria, price: f64, session: &Session) -> bool {
+    if criteria.conditions.is_empty() {
+        return true;
+    }
+
+    let mut met = criteria
+        .conditions
+        .iter()
+        .map(|condition| condition_met(condition, price, session));
+
+    match criteria.combine {
+        result::Combine::All => met.all(|met| met),
+        result::Combine::Any => met.any(|met| met),
+    }
+}
+
+fn condition_met(condition: &result::Condition, price: f64, session: &Session) -> bool {
+    let ticker = &session.ticker;
+
+    match condition {
+        result::Condition::RsiBelow { value } => ticker.rsi.is_some_and(|rsi| rsi < *value),
+        result::Condition::BelowLowerBand => {
+            ticker.bb_lower.is_some_and(|lower| ticker.close < lower)
+        }
+        result::Condition::BelowEma { percent } => ticker
+            .ema
+            .is_some_and(|ema| calculate_percent_change(ema, price) < -percent),
+        result::Condition::VolumeSpike { ratio } => ticker
+            .volume_avg
+            .is_some_and(|avg| avg > 0.0 && ticker.volume >= avg * ratio),
+        result::Condition::ValueBelow { name, value } => session
+            .indicators
+            .get(name)
+            .is_some_and(|current| current < value),
+        result::Condition::ValueAbove { name, value } => session
+            .indicators
+            .get(name)
+            .is_some_and(|current| current > value),
+    }
+}
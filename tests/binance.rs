@@ -0,0 +1,825 @@
+mod mock;
+
+use assert_cmd::Command;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use mock::{Auth, MockExchange};
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use ta::indicators::MoneyFlowIndex;
+use ta::{DataItem, Next};
+
+/// Binary left running against the mock, killed when dropped.
+struct Running(Child);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn workdir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Databases with the bot of `config` trading on the mock.
+fn setup(dir: &Path, mock: &MockExchange, config: &Path, status: &str) -> Connection {
+    for args in [
+        vec!["setup"],
+        vec!["setup", "--name", "data", "--path", "ticker1m.db"],
+        vec!["setup", "--name", "data", "--path", "spearmint_data.db"],
+        vec!["apply", "-f", config.to_str().unwrap()],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(dir)
+            .env("SPEARMINT_BINANCE_URL", &mock.url)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    let conn = Connection::open(dir.join("spearmint.db")).unwrap();
+    conn.busy_timeout(Duration::from_secs(5)).unwrap();
+    conn.execute("UPDATE bots SET status=?1", [status]).unwrap();
+    conn
+}
+
+fn dogeusdt() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml")
+}
+
+/// `configs/bot.dogeusdt.toml` moved to `platform`, written to the workdir.
+fn dogeusdt_on(dir: &Path, platform: &str) -> PathBuf {
+    let content = std::fs::read_to_string(dogeusdt()).unwrap();
+    let path = dir.join(format!("{}.toml", platform));
+    std::fs::write(
+        &path,
+        content.replace(
+            "platform = \"binance\"",
+            &format!("platform = \"{}\"", platform),
+        ),
+    )
+    .unwrap();
+    path
+}
+
+fn spawn(dir: &Path, mock: &MockExchange, bin: &str, args: &[&str]) -> Running {
+    Running(
+        std::process::Command::new(assert_cmd::cargo::cargo_bin(bin))
+            .current_dir(dir)
+            .env("SPEARMINT_BINANCE_URL", &mock.url)
+            .env("SPEARMINT_BINANCE_STREAM_URL", &mock.stream_url)
+            .env("SPEARMINT_BINANCE_API_KEY", "key")
+            .env("SPEARMINT_BINANCE_SECRET_KEY", "secret")
+            .env("SPEARMINT_BYBIT_URL", &mock.url)
+            .env("SPEARMINT_BYBIT_API_KEY", "key")
+            .env("SPEARMINT_BYBIT_SECRET_KEY", "secret")
+            .env("SPEARMINT_OKX_URL", &mock.url)
+            .env("SPEARMINT_OKX_API_KEY", "key")
+            .env("SPEARMINT_OKX_SECRET_KEY", "secret")
+            .env("SPEARMINT_OKX_API_PASSPHRASE", "okxpassphrase")
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    )
+}
+
+/// Polls until `found` returns something, for at most 30 seconds.
+fn wait_for<T>(mut found: impl FnMut() -> Option<T>) -> T {
+    let started = Instant::now();
+    loop {
+        if let Some(value) = found() {
+            return value;
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(30),
+            "timed out waiting on the mock exchange"
+        );
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+fn seed_tickers(dir: &Path, tickers: &[(f64, f64)]) {
+    let conn = Connection::open(dir.join("ticker1m.db")).unwrap();
+    for (i, (close, mfi)) in tickers.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO tickers (pair, timestamp, open, high, low, close, volume, mfi)
+            VALUES ('DOGEUSDT', ?1, ?2, ?2, ?2, ?2, 1000.0, ?3)",
+            params![1735689600 + i as u64 * 60, close, mfi],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO indicator_values (pair, interval, timestamp, name, value)
+            VALUES ('DOGEUSDT', '1m', ?1, 'mfi_14', ?2)",
+            params![1735689600 + i as u64 * 60, mfi],
+        )
+        .unwrap();
+    }
+}
+
+fn seed_trade(conn: &Connection, status: &str, price: f64, qty: f64) {
+    conn.execute(
+        "INSERT INTO trades (pair, cycle, price, qty, platform, status, timestamp, order_id,
+            quote_qty, commission, commission_asset, bot_id)
+        VALUES ('DOGEUSDT', 1, ?1, ?2, 'binance', ?3, 1735689600, 0, ?4, 0.0, '', 1)",
+        params![price, qty, status, price * qty],
+    )
+    .unwrap();
+}
+
+fn seed_state(conn: &Connection, top_price: f64, bottom_price: f64, bottom_mfi: f64) {
+    conn.execute(
+        "INSERT INTO bot_states (pair, cycle, margin_position, top_price, bottom_price,
+            bottom_mfi, platform, timestamp, take_profit_order_id, bot_id)
+        VALUES ('DOGEUSDT', 1, 0, ?1, ?2, ?3, 'binance', 1735689600, 0, 1)",
+        params![top_price, bottom_price, bottom_mfi],
+    )
+    .unwrap();
+}
+
+/// First request whose line starts with `prefix`, e.g. `POST /v5/order/create`.
+fn received(mock: &MockExchange, prefix: &str) -> mock::Received {
+    mock.received()
+        .into_iter()
+        .find(|request| request.line.starts_with(prefix))
+        .unwrap()
+}
+
+/// Checks the Bybit signature: the hex HMAC of timestamp, API key, receive
+/// window and payload, keyed with the secret.
+fn assert_bybit_signed(request: &mock::Received) {
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+    mac.update(request.header("x-bapi-timestamp").as_bytes());
+    mac.update(b"key");
+    mac.update(request.header("x-bapi-recv-window").as_bytes());
+    mac.update(request.body.as_bytes());
+
+    assert_eq!(request.header("x-bapi-api-key"), "key");
+    assert_eq!(
+        request.header("x-bapi-sign"),
+        hex::encode(mac.finalize().into_bytes())
+    );
+}
+
+/// Checks the OKX signature: the base64 HMAC of timestamp, method, request
+/// path and body, keyed with the secret.
+fn assert_okx_signed(request: &mock::Received) {
+    let mut parts = request.line.split_whitespace();
+    let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+    mac.update(request.header("ok-access-timestamp").as_bytes());
+    mac.update(method.as_bytes());
+    mac.update(path.as_bytes());
+    mac.update(request.body.as_bytes());
+
+    assert_eq!(request.header("ok-access-key"), "key");
+    assert_eq!(
+        request.header("ok-access-sign"),
+        STANDARD.encode(mac.finalize().into_bytes())
+    );
+}
+
+/// Status, cycle, order id and quantity of the bot's trades.
+fn trades(conn: &Connection) -> Vec<(String, u64, u64, f64)> {
+    let mut stmt = conn
+        .prepare("SELECT status, cycle, order_id, qty FROM trades WHERE bot_id=1 ORDER BY id")
+        .unwrap();
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap();
+
+    rows.map(|row| row.unwrap()).collect()
+}
+
+fn state_cycles(conn: &Connection) -> Vec<u64> {
+    let mut stmt = conn
+        .prepare("SELECT cycle FROM bot_states WHERE bot_id=1 ORDER BY id")
+        .unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+
+    rows.map(|row| row.unwrap()).collect()
+}
+
+#[test]
+fn run_enters_on_ticker_stream() {
+    let dir = workdir("spearmint_mock_run");
+    let mock = MockExchange::start(
+        mock::routes(),
+        vec![(
+            "dogeusdt@ticker",
+            vec![mock::day_ticker("DOGEUSDT", 0.31); 3],
+        )],
+    );
+    let conn = setup(&dir, &mock, &dogeusdt(), "PAUSED");
+    seed_tickers(&dir, &[(0.31, 15.0), (0.31, 20.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_state(&conn, 0.40, 0.30, 5.0);
+
+    let _bot = spawn(
+        &dir,
+        &mock,
+        "spearmint",
+        &["run", "--name", "DOGEUSDT", "--duration", "0"],
+    );
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 2));
+
+    assert_eq!(
+        trades,
+        vec![
+            (String::from("WAIT"), 1, 0, 0.0),
+            (String::from("OPEN"), 1, 8124015571, 32.0),
+        ]
+    );
+    assert_eq!(state_cycles(&conn), vec![1]);
+    assert!(mock.requests().iter().any(|request| {
+        request.starts_with("POST /api/v3/order")
+            && request.contains("side=BUY")
+            && request.contains("quoteOrderQty=10")
+    }));
+}
+
+#[test]
+fn run_all_takes_profit_and_starts_next_cycle() {
+    let dir = workdir("spearmint_mock_run_all");
+    let mock = MockExchange::start(mock::routes(), vec![]);
+    let conn = setup(&dir, &mock, &dogeusdt(), "ACTIVE");
+    seed_tickers(&dir, &[(0.32, 30.0), (0.315, 40.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_trade(&conn, "OPEN", 0.30, 32.0);
+    seed_state(&conn, 0.32, 0.30, 30.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let cycles = wait_for(|| Some(state_cycles(&conn)).filter(|cycles| cycles.len() >= 2));
+
+    assert_eq!(cycles, vec![1, 2]);
+    assert_eq!(
+        trades(&conn),
+        vec![
+            (String::from("WAIT"), 1, 0, 0.0),
+            (String::from("OPEN"), 1, 0, 32.0),
+            (String::from("CLOSE"), 1, 8124015572, 32.0),
+            (String::from("WAIT"), 2, 0, 0.0),
+        ]
+    );
+    assert!(mock.requests().iter().any(|request| {
+        request.starts_with("POST /api/v3/order")
+            && request.contains("side=SELL")
+            && request.contains("quantity=32")
+    }));
+}
+
+#[test]
+fn run_all_records_partly_filled_take_profit() {
+    let dir = workdir("spearmint_mock_partial");
+    let mut routes = vec![
+        (
+            "GET /api/v3/order",
+            mock::fixture("binance/order-canceled.json"),
+        ),
+        (
+            "GET /api/v3/myTrades",
+            mock::fixture("binance/my-trades.json"),
+        ),
+    ];
+    routes.extend(mock::routes());
+    let mock = MockExchange::start(routes, vec![]);
+    let conn = setup(&dir, &mock, &dogeusdt(), "ACTIVE");
+    seed_tickers(&dir, &[(0.32, 30.0), (0.315, 40.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_trade(&conn, "OPEN", 0.30, 32.0);
+    seed_state(&conn, 0.32, 0.30, 30.0);
+    conn.execute("UPDATE bot_states SET take_profit_order_id=8124015573", [])
+        .unwrap();
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 4));
+
+    // The cancelled take profit had sold 12, so only the other 20 are left.
+    assert_eq!(
+        trades[..4],
+        [
+            (String::from("WAIT"), 1, 0, 0.0),
+            (String::from("OPEN"), 1, 0, 32.0),
+            (String::from("SELL"), 1, 8124015573, 12.0),
+            (String::from("CLOSE"), 1, 8124015572, 32.0),
+        ]
+    );
+    assert!(mock.requests().iter().any(|request| {
+        request.starts_with("POST /api/v3/order")
+            && request.contains("side=SELL")
+            && request.contains("quantity=20")
+    }));
+}
+
+#[test]
+fn run_all_stops_loss_from_peak_since_entry() {
+    let dir = workdir("spearmint_mock_stop_loss");
+    let mock = MockExchange::start(mock::routes(), vec![]);
+    let content = std::fs::read_to_string(dogeusdt()).unwrap();
+    std::fs::write(
+        dir.join("stop.toml"),
+        content + "\n[parameters.stop_loss]\ntop_price_change_below = -10.0\n",
+    )
+    .unwrap();
+    let conn = setup(&dir, &mock, &dir.join("stop.toml"), "ACTIVE");
+    seed_tickers(&dir, &[(0.32, 30.0), (0.315, 40.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_trade(&conn, "OPEN", 0.33, 32.0);
+    // The top was reset to the average price on the way down, 0.36 stays the
+    // peak since entry.
+    seed_state(&conn, 0.33, 0.30, 30.0);
+    conn.execute("UPDATE bot_states SET peak_price=0.36", [])
+        .unwrap();
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 3));
+
+    assert_eq!(
+        trades[..3],
+        [
+            (String::from("WAIT"), 1, 0, 0.0),
+            (String::from("OPEN"), 1, 0, 32.0),
+            (String::from("STOPPED"), 1, 8124015572, 32.0),
+        ]
+    );
+}
+
+#[test]
+fn run_all_buys_quote_amount_on_bybit() {
+    let dir = workdir("spearmint_mock_bybit_buy");
+    let mut routes = vec![(
+        "GET /v5/order/realtime",
+        mock::fixture("bybit/order-buy.json"),
+    )];
+    routes.extend(mock::bybit_routes());
+    let mock = MockExchange::with_auth(Auth::All("x-bapi-sign:"), routes, vec![]);
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "bybit"), "ACTIVE");
+    seed_tickers(&dir, &[(0.31, 15.0), (0.31, 20.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_state(&conn, 0.40, 0.30, 5.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 2));
+
+    assert_eq!(trades[1], (String::from("OPEN"), 1, 1780349287350, 32.2));
+
+    let order = received(&mock, "POST /v5/order/create");
+    assert_bybit_signed(&order);
+    let body: Value = serde_json::from_str(&order.body).unwrap();
+    assert_eq!(body["side"], "Buy");
+    assert_eq!(body["orderType"], "Market");
+    assert_eq!(body["marketUnit"], "quoteCoin");
+    assert_eq!(body["qty"], "10");
+}
+
+#[test]
+fn run_all_sells_lot_size_on_bybit() {
+    let dir = workdir("spearmint_mock_bybit_sell");
+    let mut routes = vec![(
+        "GET /v5/order/realtime",
+        mock::fixture("bybit/order-sell.json"),
+    )];
+    routes.extend(mock::bybit_routes());
+    let mock = MockExchange::with_auth(Auth::All("x-bapi-sign:"), routes, vec![]);
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "bybit"), "ACTIVE");
+    seed_tickers(&dir, &[(0.32, 30.0), (0.315, 40.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_trade(&conn, "OPEN", 0.30, 32.2567);
+    seed_state(&conn, 0.32, 0.30, 30.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 3));
+
+    assert_eq!(trades[2], (String::from("CLOSE"), 1, 1780349287350, 32.2));
+
+    // Rounded down to the 0.1 base precision of the instrument.
+    let order = received(&mock, "POST /v5/order/create");
+    assert_bybit_signed(&order);
+    let body: Value = serde_json::from_str(&order.body).unwrap();
+    assert_eq!(body["side"], "Sell");
+    assert_eq!(body["marketUnit"], "baseCoin");
+    assert_eq!(body["qty"], "32.2");
+}
+
+#[test]
+fn run_all_skips_bybit_order_cancelled_without_fills() {
+    let dir = workdir("spearmint_mock_bybit_cancelled");
+    let mut routes = vec![(
+        "GET /v5/order/realtime",
+        mock::fixture("bybit/order-cancelled.json"),
+    )];
+    routes.extend(mock::bybit_routes());
+    let mock = MockExchange::with_auth(Auth::All("x-bapi-sign:"), routes, vec![]);
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "bybit"), "ACTIVE");
+    seed_tickers(&dir, &[(0.31, 15.0), (0.31, 20.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_state(&conn, 0.40, 0.30, 5.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    wait_for(|| {
+        let orders = mock
+            .requests()
+            .into_iter()
+            .filter(|request| request.starts_with("POST /v5/order/create"))
+            .count();
+        Some(()).filter(|_| orders >= 2)
+    });
+
+    // Each tick's order is skipped instead of opening on an empty fill.
+    assert_eq!(trades(&conn), [(String::from("WAIT"), 1, 0, 0.0)]);
+
+    // The instrument's precisions are fetched once for both orders.
+    let instruments = mock
+        .requests()
+        .into_iter()
+        .filter(|request| request.starts_with("GET /v5/market/instruments-info"))
+        .count();
+    assert_eq!(instruments, 1);
+}
+
+#[test]
+fn run_all_buys_quote_amount_on_okx() {
+    let dir = workdir("spearmint_mock_okx_buy");
+    let mut routes = vec![(
+        "GET /api/v5/trade/order",
+        mock::fixture("okx/order-buy.json"),
+    )];
+    routes.extend(mock::okx_routes());
+    let mock = MockExchange::with_auth(
+        Auth::All("ok-access-passphrase: okxpassphrase"),
+        routes,
+        vec![],
+    );
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "okx"), "ACTIVE");
+    seed_tickers(&dir, &[(0.31, 15.0), (0.31, 20.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_state(&conn, 0.40, 0.30, 5.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 2));
+
+    assert_eq!(
+        trades[1],
+        (String::from("OPEN"), 1, 590908157585625112, 32.2)
+    );
+
+    let order = received(&mock, "POST /api/v5/trade/order");
+    assert_okx_signed(&order);
+    let body: Value = serde_json::from_str(&order.body).unwrap();
+    assert_eq!(body["instId"], "DOGE-USDT");
+    assert_eq!(body["side"], "buy");
+    assert_eq!(body["ordType"], "market");
+    assert_eq!(body["tgtCcy"], "quote_ccy");
+    assert_eq!(body["sz"], "10");
+}
+
+#[test]
+fn run_all_skips_okx_order_canceled_without_fills() {
+    let dir = workdir("spearmint_mock_okx_canceled");
+    let mut routes = vec![(
+        "GET /api/v5/trade/order",
+        mock::fixture("okx/order-canceled.json"),
+    )];
+    routes.extend(mock::okx_routes());
+    let mock = MockExchange::with_auth(
+        Auth::All("ok-access-passphrase: okxpassphrase"),
+        routes,
+        vec![],
+    );
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "okx"), "ACTIVE");
+    seed_tickers(&dir, &[(0.32, 30.0), (0.315, 40.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_trade(&conn, "OPEN", 0.30, 32.2567);
+    seed_state(&conn, 0.32, 0.30, 30.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    wait_for(|| {
+        let orders = mock
+            .requests()
+            .into_iter()
+            .filter(|request| request.starts_with("POST /api/v5/trade/order"))
+            .count();
+        Some(()).filter(|_| orders >= 2)
+    });
+
+    // Each tick's order is skipped instead of closing on an empty fill.
+    assert_eq!(
+        trades(&conn),
+        [
+            (String::from("WAIT"), 1, 0, 0.0),
+            (String::from("OPEN"), 1, 0, 32.2567),
+        ]
+    );
+
+    // The instrument's lot size is fetched once for both orders.
+    let instruments = mock
+        .requests()
+        .into_iter()
+        .filter(|request| request.starts_with("GET /api/v5/public/instruments"))
+        .count();
+    assert_eq!(instruments, 1);
+}
+
+#[test]
+fn run_all_sells_lot_size_on_okx() {
+    let dir = workdir("spearmint_mock_okx_sell");
+    let mut routes = vec![(
+        "GET /api/v5/trade/order",
+        mock::fixture("okx/order-sell.json"),
+    )];
+    routes.extend(mock::okx_routes());
+    let mock = MockExchange::with_auth(
+        Auth::All("ok-access-passphrase: okxpassphrase"),
+        routes,
+        vec![],
+    );
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "okx"), "ACTIVE");
+    seed_tickers(&dir, &[(0.32, 30.0), (0.315, 40.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_trade(&conn, "OPEN", 0.30, 32.2567);
+    seed_state(&conn, 0.32, 0.30, 30.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 3));
+
+    assert_eq!(
+        trades[2],
+        (String::from("CLOSE"), 1, 590908157585625112, 32.2)
+    );
+
+    // Rounded down to the 0.1 lot size of the instrument.
+    let order = received(&mock, "POST /api/v5/trade/order");
+    assert_okx_signed(&order);
+    let body: Value = serde_json::from_str(&order.body).unwrap();
+    assert_eq!(body["side"], "sell");
+    assert_eq!(body["tgtCcy"], "base_ccy");
+    assert_eq!(body["sz"], "32.2");
+}
+
+#[test]
+fn ticker_stores_closed_klines() {
+    let dir = workdir("spearmint_mock_ticker");
+    let mock = MockExchange::start(
+        mock::routes(),
+        vec![(
+            "dogeusdt@kline_1m",
+            vec![
+                mock::kline("DOGEUSDT", 1735689600000, 0.311, true),
+                mock::kline("DOGEUSDT", 1735689660000, 0.312, false),
+                mock::kline("DOGEUSDT", 1735689660000, 0.314, true),
+                mock::kline("DOGEUSDT", 1735689720000, 0.313, true),
+            ],
+        )],
+    );
+    setup(&dir, &mock, &dogeusdt(), "ACTIVE");
+
+    let _collector = spawn(&dir, &mock, "ticker", &[]);
+    let conn = Connection::open(dir.join("ticker1m.db")).unwrap();
+    let closes: Vec<f64> = wait_for(|| {
+        let mut stmt = conn
+            .prepare("SELECT close FROM tickers WHERE pair='DOGEUSDT' ORDER BY id")
+            .unwrap();
+        let closes: Vec<f64> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|close| close.unwrap())
+            .collect();
+
+        Some(closes).filter(|closes| closes.len() >= 3)
+    });
+
+    assert_eq!(closes, vec![0.311, 0.314, 0.313]);
+
+    // Two fetched and three streamed klines are too few for the MFI.
+    let warmed: u64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM tickers WHERE mfi IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(warmed, 0);
+}
+
+#[test]
+fn run_all_fills_grid_levels_above_price() {
+    let dir = workdir("spearmint_mock_grid");
+    let mock = MockExchange::start(mock::routes(), vec![]);
+    std::fs::write(
+        dir.join("grid.toml"),
+        "kind = \"bot\"\ntitle = \"DOGEUSDT-GRID\"\npair = \"DOGEUSDT\"\nbase = \"DOGE\"\nquote = \"USDT\"\nplatform = \"binance\"\nstrategy = \"grid\"\nstatus = \"PAUSED\"\n\n[parameters]\ncycle = \"repeat\"\nfirst_buy_in = 10.0\n\n[parameters.grid]\nlower_price = 0.30\nupper_price = 0.34\nlevels = 5\n",
+    )
+    .unwrap();
+    let conn = setup(&dir, &mock, &dir.join("grid.toml"), "ACTIVE");
+    seed_tickers(&dir, &[(0.315, 40.0)]);
+    let applied = mock.requests().len();
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 3));
+
+    assert_eq!(
+        trades,
+        vec![
+            (String::from("WAIT"), 1, 0, 0.0),
+            (String::from("BUY"), 1, 8124015571, 32.0),
+            (String::from("BUY"), 1, 8124015571, 32.0),
+        ]
+    );
+
+    let mut stmt = conn
+        .prepare("SELECT level, qty FROM grid_levels WHERE bot_id=1 ORDER BY level")
+        .unwrap();
+    let levels: Vec<(u64, f64)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|level| level.unwrap())
+        .collect();
+    assert_eq!(
+        levels.iter().map(|(level, _)| *level).collect::<Vec<u64>>(),
+        vec![2, 3]
+    );
+    assert!(levels.iter().all(|(_, qty)| (qty - 31.968).abs() < 1e-9));
+
+    // Both buys, a tick apart, check the filters fetched for the first one.
+    let fetched = mock.requests()[applied..]
+        .iter()
+        .filter(|request| request.starts_with("GET /api/v3/exchangeInfo"))
+        .count();
+    assert_eq!(fetched, 1);
+}
+
+#[test]
+fn run_all_fills_paper_orders() {
+    let dir = workdir("spearmint_mock_paper");
+    let mock = MockExchange::start(mock::routes(), vec![]);
+    let conn = setup(&dir, &mock, &dogeusdt_on(&dir, "paper"), "ACTIVE");
+    let account = Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/config.paper.example.toml");
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["apply", "-f", account.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("USDT"));
+    seed_tickers(&dir, &[(0.31, 15.0), (0.31, 20.0)]);
+    seed_trade(&conn, "WAIT", 0.0, 0.0);
+    seed_state(&conn, 0.40, 0.30, 5.0);
+
+    let _bot = spawn(&dir, &mock, "spearmint", &["run-all", "--duration", "1"]);
+    let trades = wait_for(|| Some(trades(&conn)).filter(|trades| trades.len() >= 2));
+
+    // Filled at the last close plus the 0.05% slippage, the 0.1% fee taken
+    // from the base bought.
+    let qty = 10.0 / (0.31 * 1.0005);
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades[1].0, "OPEN");
+    assert!((trades[1].3 - qty).abs() < 1e-9);
+
+    let balance = |asset: &str| -> f64 {
+        conn.query_row(
+            "SELECT free FROM paper_balances WHERE asset=?1",
+            [asset],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+    assert!((balance("USDT") - 990.0).abs() < 1e-9);
+    assert!((balance("DOGE") - qty * 0.999).abs() < 1e-9);
+}
+
+#[test]
+fn ticker_stores_configured_indicators() {
+    let dir = workdir("spearmint_mock_indicators");
+    // No klines to warm up from, everything is computed on the stream.
+    let mut routes = vec![("GET /api/v3/klines", String::from("[]"))];
+    routes.extend(mock::routes());
+    let mock = MockExchange::start(
+        routes,
+        vec![(
+            "dogeusdt@kline_1m",
+            vec![
+                mock::kline("DOGEUSDT", 1735689600000, 0.311, true),
+                mock::kline("DOGEUSDT", 1735689660000, 0.314, true),
+                mock::kline("DOGEUSDT", 1735689720000, 0.313, true),
+            ],
+        )],
+    );
+    let content = std::fs::read_to_string(dogeusdt()).unwrap();
+    std::fs::write(
+        dir.join("indicators.toml"),
+        content + "\n[[indicators]]\nname = \"sma2\"\nkind = \"sma\"\nperiod = 2\n",
+    )
+    .unwrap();
+    setup(&dir, &mock, &dir.join("indicators.toml"), "ACTIVE");
+
+    let _collector = spawn(&dir, &mock, "ticker", &[]);
+    let conn = Connection::open(dir.join("ticker1m.db")).unwrap();
+    let values: Vec<(String, String, f64)> = wait_for(|| {
+        let mut stmt = conn
+            .prepare("SELECT interval, name, value FROM indicator_values WHERE pair='DOGEUSDT' ORDER BY id")
+            .unwrap();
+        let values: Vec<(String, String, f64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|value| value.unwrap())
+            .collect();
+
+        Some(values).filter(|values| values.len() >= 2)
+    });
+
+    // The first kline only warms the average up.
+    assert_eq!(values.len(), 2);
+    assert!(values
+        .iter()
+        .all(|(interval, name, _)| interval == "1m" && name == "sma2"));
+    assert!((values[0].2 - 0.3125).abs() < 1e-9);
+    assert!((values[1].2 - 0.3135).abs() < 1e-9);
+}
+
+#[test]
+fn ticker_warms_up_from_rest_klines() {
+    let dir = workdir("spearmint_mock_warm_up");
+    let closes: Vec<f64> = (0..30).map(|i| 0.30 + 0.001 * (i % 4) as f64).collect();
+    let mut routes = vec![(
+        "GET /api/v3/klines",
+        mock::rest_klines(1735689600000, &closes),
+    )];
+    routes.extend(mock::routes());
+    let mock = MockExchange::start(
+        routes,
+        vec![(
+            "dogeusdt@kline_1m",
+            vec![mock::kline("DOGEUSDT", 1735691400000, 0.312, true)],
+        )],
+    );
+    let content = std::fs::read_to_string(dogeusdt()).unwrap();
+    std::fs::write(
+        dir.join("mfi.toml"),
+        content.replace("first_buy_in = 10.0", "first_buy_in = 10.0\nmfi_period = 7"),
+    )
+    .unwrap();
+    let main = setup(&dir, &mock, &dir.join("mfi.toml"), "ACTIVE");
+    std::fs::write(
+        dir.join("slow.toml"),
+        content.replace("title = \"DOGEUSDT\"", "title = \"DOGEUSDT-SLOW\""),
+    )
+    .unwrap();
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .env("SPEARMINT_BINANCE_URL", &mock.url)
+        .args(["apply", "-f", "slow.toml"])
+        .assert()
+        .success();
+    main.execute("UPDATE bots SET status='ACTIVE'", []).unwrap();
+
+    let _collector = spawn(&dir, &mock, "ticker", &[]);
+    let conn = Connection::open(dir.join("ticker1m.db")).unwrap();
+    let mfi = |period: usize| -> Option<f64> {
+        conn.query_row(
+            "SELECT value FROM indicator_values WHERE pair='DOGEUSDT' AND name=?1
+            ORDER BY id LIMIT 1",
+            [format!("mfi_{}", period)],
+            |row| row.get(0),
+        )
+        .ok()
+    };
+    wait_for(|| mfi(7).and(mfi(14)));
+
+    // Each bot's MFI is stored over its own period, warmed up on the REST
+    // klines.
+    for period in [7, 14] {
+        let mut expected = MoneyFlowIndex::new(period).unwrap();
+        let mut value = 0.0;
+        for close in closes.iter().chain([0.312].iter()) {
+            let di = DataItem::builder()
+                .high(*close)
+                .low(*close)
+                .close(*close)
+                .open(*close)
+                .volume(1000.0)
+                .build()
+                .unwrap();
+            value = expected.next(&di);
+        }
+        assert!((mfi(period).unwrap() - value).abs() < 1e-9);
+    }
+    assert!(mock
+        .requests()
+        .iter()
+        .any(|request| request.starts_with("GET /api/v3/klines")));
+}
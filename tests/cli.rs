@@ -1,26 +1,53 @@
+mod mock;
+
 use assert_cmd::Command;
+use mock::{Auth, MockExchange};
 use predicates::prelude::*;
 
 #[test]
 fn apply() {
-    let mut cmd = Command::cargo_bin("spearmint").unwrap();
-    cmd.args([
-        "apply",
-        "-f",
-        "D:\\projects\\Trading\\spearmint\\config.example.toml",
-    ])
-    .assert()
-    .success()
-    .stdout(predicate::str::contains("Margin"));
+    let dir = std::env::temp_dir().join("spearmint_apply");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/config.bot.example.toml");
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["apply", "-f", config.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Margin"));
 }
 
 #[test]
 fn status() {
-    let mut cmd = Command::cargo_bin("spearmint").unwrap();
-    cmd.args(["status", "ftm_btc"])
+    let dir = std::env::temp_dir().join("spearmint_status");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+
+    for args in [
+        vec!["setup"],
+        vec!["setup", "--name", "data", "--path", "ticker1m.db"],
+        vec!["apply", "-f", config.to_str().unwrap()],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["status", "--name", "DOGEUSDT"])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Status"));
+        .stdout(predicate::str::contains("Capital Needed"));
 }
 
 #[test]
@@ -31,3 +58,680 @@ fn list() {
         .success()
         .stdout(predicate::str::contains("Title"));
 }
+
+#[test]
+fn backtest() {
+    let dir = std::env::temp_dir().join("spearmint_backtest");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+
+    for args in [
+        vec!["setup"],
+        vec!["setup", "--name", "data", "--path", "ticker1m.db"],
+        vec!["apply", "-f", config.to_str().unwrap()],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    let conn = rusqlite::Connection::open(dir.join("ticker1m.db")).unwrap();
+    for i in 0..3000u64 {
+        let price = 0.3 * (1.0 + 0.08 * (i as f64 / 150.0).sin());
+        let mfi = (10 + (i * 7) % 60) as f64;
+        conn.execute(
+            "INSERT INTO tickers (pair, timestamp, open, high, low, close, volume, mfi)
+            VALUES ('DOGEUSDT', ?1, ?2, ?2, ?2, ?2, 1000.0, ?3)",
+            rusqlite::params![1735689600 + i * 60, price, mfi],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO indicator_values (pair, interval, timestamp, name, value)
+            VALUES ('DOGEUSDT', '1m', ?1, 'mfi_14', ?2)",
+            rusqlite::params![1735689600 + i * 60, mfi],
+        )
+        .unwrap();
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args([
+            "backtest",
+            "--name",
+            "DOGEUSDT",
+            "--from",
+            "2025-01-01",
+            "--to",
+            "2025-01-03 12:00:00",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cycles: 3"));
+
+    // The replay runs against its own database, not the bot's history.
+    let conn = rusqlite::Connection::open(dir.join("spearmint.db")).unwrap();
+    for table in ["trades", "bot_states"] {
+        let rows: u64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(rows, 0, "backtest wrote to {}", table);
+    }
+}
+
+#[test]
+fn database() {
+    let dir = std::env::temp_dir().join("spearmint_database");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for args in [
+        vec!["--database", "custom.db", "setup"],
+        vec!["--database", "custom.db", "list"],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    assert!(dir.join("custom.db").exists());
+    assert!(!dir.join("spearmint.db").exists());
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["setup", "--name", "data", "--path", "custom_data.db"])
+        .assert()
+        .success();
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args([
+            "--database",
+            "custom.db",
+            "--data-database",
+            "custom_data.db",
+            "db",
+            "migrate",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("custom_data.db"));
+}
+
+#[test]
+fn migrate() {
+    let dir = std::env::temp_dir().join("spearmint_migrate");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for args in [vec!["setup"], vec!["setup"], vec!["db", "migrate"]] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    let conn = rusqlite::Connection::open(dir.join("spearmint.db")).unwrap();
+    let tokens: u64 = conn
+        .query_row("SELECT COUNT(*) FROM tokens", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(tokens, 3);
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["db", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("PENDING").not());
+}
+
+#[test]
+fn same_pair() {
+    let dir = std::env::temp_dir().join("spearmint_same_pair");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+    let content = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(
+        dir.join("aggressive.toml"),
+        content.replace("title = \"DOGEUSDT\"", "title = \"DOGEUSDT-AGGRESSIVE\""),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("fast.toml"),
+        content
+            .replace("title = \"DOGEUSDT\"", "title = \"DOGEUSDT-FAST\"")
+            .replace("first_buy_in = 10.0", "first_buy_in = 10.0\nmfi_period = 7"),
+    )
+    .unwrap();
+
+    // Bots on a pair may trade on MFIs of different periods.
+    for args in [
+        vec!["apply", "-f", config.to_str().unwrap()],
+        vec!["apply", "-f", "aggressive.toml"],
+        vec!["apply", "-f", "fast.toml"],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("error").not());
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DOGEUSDT-AGGRESSIVE"))
+        .stdout(predicate::str::contains("DOGEUSDT-FAST"))
+        .stdout(predicate::str::contains("DOGEUSDT "));
+}
+
+#[test]
+fn bind() {
+    let dir = std::env::temp_dir().join("spearmint_bind");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("bind.toml"),
+        "kind = \"bind\"\napi_key = \"ABCDEFGHIJKLMNOP\"\nsecret_key = \"supersecretvalue\"\nplatform = \"binance\"\n",
+    )
+    .unwrap();
+
+    for args in [vec!["apply", "-f", "bind.toml"], vec!["bind", "list"]] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .env("SPEARMINT_PASSPHRASE", "passphrase")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("ABCD********MNOP"))
+            .stdout(predicate::str::contains("supersecretvalue").not());
+    }
+
+    std::fs::write(
+        dir.join("sub1.toml"),
+        "kind = \"bind\"\napi_key = \"QRSTUVWXYZ123456\"\nsecret_key = \"othersecretvalue\"\nplatform = \"binance\"\nname = \"sub1\"\n",
+    )
+    .unwrap();
+
+    for args in [vec!["apply", "-f", "sub1.toml"], vec!["bind", "list"]] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .env("SPEARMINT_PASSPHRASE", "passphrase")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("binance:sub1"));
+    }
+
+    let conn = rusqlite::Connection::open(dir.join("spearmint.db")).unwrap();
+    let secret: String = conn
+        .query_row(
+            "SELECT secret_key FROM bindings WHERE name='main'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(secret.starts_with("enc:v1:"));
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .env("SPEARMINT_PASSPHRASE", "wrong")
+        .args(["account", "--platform", "binance"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("error: invalid passphrase"));
+}
+
+#[test]
+fn testnet() {
+    let dir = std::env::temp_dir().join("spearmint_testnet");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("binance.toml"),
+        "kind = \"bind\"\napi_key = \"ABCDEFGHIJKLMNOP\"\nsecret_key = \"supersecretvalue\"\nplatform = \"binance\"\nenvironment = \"testnet\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("bybit.toml"),
+        "kind = \"bind\"\napi_key = \"ABCDEFGHIJKLMNOP\"\nsecret_key = \"supersecretvalue\"\nplatform = \"bybit\"\nenvironment = \"testnet\"\n",
+    )
+    .unwrap();
+
+    for args in [vec!["apply", "-f", "binance.toml"], vec!["bind", "list"]] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .env("SPEARMINT_PASSPHRASE", "passphrase")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("testnet"));
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .env("SPEARMINT_PASSPHRASE", "passphrase")
+        .args(["apply", "-f", "bybit.toml"])
+        .assert()
+        .stdout(predicate::str::contains("bybit has no testnet"));
+}
+
+/// Bot config of `configs/bot.dogeusdt.toml` moved to another platform.
+fn write_bot(dir: &std::path::Path, platform: &str) {
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+    std::fs::write(
+        dir.join("bot.toml"),
+        std::fs::read_to_string(config).unwrap().replace(
+            "platform = \"binance\"",
+            &format!("platform = \"{}\"", platform),
+        ),
+    )
+    .unwrap();
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(dir)
+        .args(["apply", "-f", "bot.toml"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn bybit() {
+    let dir = std::env::temp_dir().join("spearmint_bybit");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    write_bot(&dir, "bybit");
+    let mock = MockExchange::with_auth(
+        Auth::All("x-bapi-sign:"),
+        vec![
+            (
+                "/v5/account/wallet-balance",
+                mock::fixture("bybit/wallet-balance.json"),
+            ),
+            (
+                "/v5/order/realtime",
+                mock::fixture("bybit/order-realtime.json"),
+            ),
+        ],
+        vec![],
+    );
+
+    for (args, expected) in [
+        (vec!["account", "--platform", "bybit"], "100.5"),
+        (vec!["orders", "--name", "DOGEUSDT"], "1780349287349"),
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .env("SPEARMINT_BYBIT_URL", &mock.url)
+            .env("SPEARMINT_BYBIT_API_KEY", "key")
+            .env("SPEARMINT_BYBIT_SECRET_KEY", "secret")
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected))
+            .stdout(predicate::str::contains("DOGE ").not());
+    }
+}
+
+#[test]
+fn okx() {
+    let dir = std::env::temp_dir().join("spearmint_okx");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    write_bot(&dir, "okx");
+    std::fs::write(
+        dir.join("bind.toml"),
+        "kind = \"bind\"\napi_key = \"OKXAPIKEY0000001\"\nsecret_key = \"okxsecretvalue\"\nplatform = \"okx\"\npassphrase = \"okxpassphrase\"\n",
+    )
+    .unwrap();
+    let mock = MockExchange::with_auth(
+        Auth::All("ok-access-passphrase: okxpassphrase"),
+        vec![
+            ("/api/v5/account/balance", mock::fixture("okx/balance.json")),
+            (
+                "/api/v5/trade/orders-pending",
+                mock::fixture("okx/orders-pending.json"),
+            ),
+        ],
+        vec![],
+    );
+
+    for (args, expected) in [
+        (vec!["apply", "-f", "bind.toml"], "okx:main"),
+        (vec!["account", "--platform", "okx"], "245.8"),
+        (vec!["orders", "--name", "DOGEUSDT"], "590908157585625111"),
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .env("SPEARMINT_PASSPHRASE", "passphrase")
+            .env("SPEARMINT_OKX_URL", &mock.url)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    let conn = rusqlite::Connection::open(dir.join("spearmint.db")).unwrap();
+    let passphrase: String = conn
+        .query_row(
+            "SELECT passphrase FROM bindings WHERE platform='okx'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert!(passphrase.starts_with("enc:v1:"));
+}
+
+#[test]
+fn registry() {
+    let dir = std::env::temp_dir().join("spearmint_registry");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+    let content = std::fs::read_to_string(config).unwrap();
+
+    for (name, content, expected) in [
+        (
+            "platform.toml",
+            content.replace("platform = \"binance\"", "platform = \"kraken\""),
+            "unknown platform `kraken`",
+        ),
+        (
+            "strategy.toml",
+            content.replace("strategy = \"helldiver\"", "strategy = \"moonshot\""),
+            "unknown strategy `moonshot`",
+        ),
+        (
+            "grid.toml",
+            content.replace("strategy = \"helldiver\"", "strategy = \"grid\""),
+            "grid strategy needs [parameters.grid]",
+        ),
+    ] {
+        std::fs::write(dir.join(name), content).unwrap();
+
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(["apply", "-f", name])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DOGEUSDT").not());
+}
+
+#[test]
+fn grid() {
+    let dir = std::env::temp_dir().join("spearmint_grid");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.btcusdt.grid.toml");
+    let content = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(
+        dir.join("narrow.toml"),
+        content.replace("levels = 11", "levels = 1"),
+    )
+    .unwrap();
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["apply", "-f", "narrow.toml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("grid needs at least 2 levels"));
+
+    for args in [
+        vec!["apply", "-f", config.to_str().unwrap()],
+        vec!["bot", "--name", "BTCUSDT-GRID"],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Geometric"));
+    }
+
+    for (args, expected) in [
+        (vec!["setup", "--name", "data", "--path", "ticker1m.db"], ""),
+        (
+            vec![
+                "backtest",
+                "--name",
+                "BTCUSDT-GRID",
+                "--from",
+                "2025-01-01",
+                "--to",
+                "2025-01-02",
+            ],
+            "backtests don't support the `grid` strategy",
+        ),
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+}
+
+#[test]
+fn entry_conditions() {
+    let dir = std::env::temp_dir().join("spearmint_entry_conditions");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+    let content = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(
+        dir.join("rsi.toml"),
+        content + "\n[[parameters.entry.conditions]]\nindicator = \"rsi_below\"\nvalue = 30.0\n",
+    )
+    .unwrap();
+
+    for args in [
+        vec!["setup"],
+        vec!["setup", "--name", "data", "--path", "ticker1m.db"],
+        vec!["apply", "-f", "rsi.toml"],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["bot", "--name", "DOGEUSDT"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("RsiBelow"));
+
+    let conn = rusqlite::Connection::open(dir.join("ticker1m.db")).unwrap();
+    for i in 0..3000u64 {
+        let price = 0.3 * (1.0 + 0.08 * (i as f64 / 150.0).sin());
+        let mfi = (10 + (i * 7) % 60) as f64;
+        conn.execute(
+            "INSERT INTO tickers (pair, timestamp, open, high, low, close, volume, mfi)
+            VALUES ('DOGEUSDT', ?1, ?2, ?2, ?2, ?2, 1000.0, ?3)",
+            rusqlite::params![1735689600 + i * 60, price, mfi],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO indicator_values (pair, interval, timestamp, name, value)
+            VALUES ('DOGEUSDT', '1m', ?1, 'mfi_14', ?2)",
+            rusqlite::params![1735689600 + i * 60, mfi],
+        )
+        .unwrap();
+    }
+
+    let backtest = [
+        "backtest",
+        "--name",
+        "DOGEUSDT",
+        "--from",
+        "2025-01-01",
+        "--to",
+        "2025-01-03 12:00:00",
+    ];
+
+    // Without an RSI the condition never holds.
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(backtest)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cycles: 0"));
+
+    conn.execute("UPDATE tickers SET rsi=20.0", []).unwrap();
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(backtest)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cycles: 3"));
+}
+
+#[test]
+fn indicators() {
+    let dir = std::env::temp_dir().join("spearmint_indicators");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+    let content = std::fs::read_to_string(&config).unwrap();
+    let indicator =
+        "\n[[indicators]]\nname = \"rsi7\"\nkind = \"rsi\"\nperiod = 7\ninterval = \"1m\"\n";
+    std::fs::write(dir.join("rsi.toml"), content.clone() + indicator).unwrap();
+    std::fs::write(
+        dir.join("twice.toml"),
+        content.clone() + indicator + indicator,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("empty.toml"),
+        content + &indicator.replace("period = 7", "period = 0"),
+    )
+    .unwrap();
+
+    for (file, expected) in [
+        ("twice.toml", "indicator `rsi7` is defined twice"),
+        ("empty.toml", "error: indicator `rsi7`"),
+        ("rsi.toml", "Rsi"),
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(["apply", "-f", file])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["bot", "--name", "DOGEUSDT"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rsi7"));
+}
+
+#[test]
+fn mfi_warm_up() {
+    let dir = std::env::temp_dir().join("spearmint_mfi_warm_up");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+
+    for args in [
+        vec!["setup"],
+        vec!["setup", "--name", "data", "--path", "ticker1m.db"],
+        vec!["apply", "-f", config.to_str().unwrap()],
+    ] {
+        Command::cargo_bin("spearmint")
+            .unwrap()
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success();
+    }
+
+    // Tickers stored while the MFI was warming up have no `mfi_14` value.
+    let conn = rusqlite::Connection::open(dir.join("ticker1m.db")).unwrap();
+    for i in 0..3000u64 {
+        let price = 0.3 * (1.0 + 0.08 * (i as f64 / 150.0).sin());
+        conn.execute(
+            "INSERT INTO tickers (pair, timestamp, open, high, low, close, volume, mfi)
+            VALUES ('DOGEUSDT', ?1, ?2, ?2, ?2, ?2, 1000.0, NULL)",
+            rusqlite::params![1735689600 + i * 60, price],
+        )
+        .unwrap();
+    }
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args([
+            "backtest",
+            "--name",
+            "DOGEUSDT",
+            "--from",
+            "2025-01-01",
+            "--to",
+            "2025-01-03 12:00:00",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cycles: 0"));
+
+    Command::cargo_bin("spearmint")
+        .unwrap()
+        .current_dir(&dir)
+        .args(["bot", "--name", "DOGEUSDT"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("MFI Period"));
+}
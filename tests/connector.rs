@@ -0,0 +1,27 @@
+use spearmint::connector::round_to_step;
+
+#[test]
+fn round_to_step_floors_to_multiples_of_the_step() {
+    assert_eq!(round_to_step(32.2567, 0.1, f64::floor), 32.2);
+    assert_eq!(round_to_step(0.123456789, 0.00001, f64::floor), 0.12345);
+    assert_eq!(round_to_step(10.0, 0.000001, f64::floor), 10.0);
+}
+
+#[test]
+fn round_to_step_handles_steps_that_are_not_powers_of_ten() {
+    assert_eq!(round_to_step(7.3, 0.5, f64::floor), 7.0);
+    assert_eq!(round_to_step(7.8, 0.5, f64::floor), 7.5);
+    assert_eq!(round_to_step(37.0, 5.0, f64::floor), 35.0);
+    assert_eq!(round_to_step(0.0374, 0.0025, f64::floor), 0.035);
+}
+
+#[test]
+fn round_to_step_rounds_prices_to_the_nearest_tick() {
+    assert_eq!(round_to_step(0.312349, 0.00001, f64::round), 0.31235);
+    assert_eq!(round_to_step(7.3, 0.5, f64::round), 7.5);
+}
+
+#[test]
+fn round_to_step_keeps_values_without_a_step() {
+    assert_eq!(round_to_step(32.2567, 0.0, f64::floor), 32.2567);
+}
@@ -0,0 +1,117 @@
+use binance::errors::{BinanceContentError, Error, ErrorKind};
+use spearmint::model::ExchangeError;
+use std::mem::discriminant;
+
+fn binance_error(code: i16, msg: &str) -> ExchangeError {
+    Error::from_kind(ErrorKind::BinanceError(BinanceContentError {
+        code,
+        msg: String::from(msg),
+    }))
+    .into()
+}
+
+#[test]
+fn only_errors_that_clear_up_are_transient() {
+    let msg = String::from;
+
+    for (err, transient) in [
+        (ExchangeError::InsufficientBalance(msg("")), false),
+        (ExchangeError::MinNotional(msg("")), false),
+        (ExchangeError::LotSize(msg("")), false),
+        (ExchangeError::RateLimited(msg("")), true),
+        (ExchangeError::Timestamp(msg("")), true),
+        (ExchangeError::Network(msg("")), true),
+        (ExchangeError::InvalidSymbol(msg("")), false),
+        (ExchangeError::Rejected(msg("")), false),
+        (ExchangeError::Other(msg("")), false),
+    ] {
+        assert_eq!(err.is_transient(), transient, "{:?}", err);
+    }
+}
+
+#[test]
+fn binance_error_codes_map_to_their_kind() {
+    let msg = String::from;
+
+    for (code, text, expected) in [
+        (
+            -2010,
+            "Account has insufficient balance for requested action.",
+            ExchangeError::InsufficientBalance(msg("")),
+        ),
+        (
+            -2010,
+            "Duplicate order sent.",
+            ExchangeError::Rejected(msg("")),
+        ),
+        (
+            -1013,
+            "Filter failure: NOTIONAL",
+            ExchangeError::MinNotional(msg("")),
+        ),
+        (
+            -1013,
+            "Filter failure: PRICE_FILTER",
+            ExchangeError::Rejected(msg("")),
+        ),
+        (
+            -1003,
+            "Too many requests.",
+            ExchangeError::RateLimited(msg("")),
+        ),
+        (
+            -1015,
+            "Too many new orders.",
+            ExchangeError::RateLimited(msg("")),
+        ),
+        (
+            -1021,
+            "Timestamp for this request is outside of the recvWindow.",
+            ExchangeError::Timestamp(msg("")),
+        ),
+        (-1001, "Internal error.", ExchangeError::Network(msg(""))),
+        (
+            -1007,
+            "Timeout waiting for response.",
+            ExchangeError::Network(msg("")),
+        ),
+        (
+            -1121,
+            "Invalid symbol.",
+            ExchangeError::InvalidSymbol(msg("")),
+        ),
+        (
+            -2011,
+            "Unknown order sent.",
+            ExchangeError::Rejected(msg("")),
+        ),
+        (
+            -2013,
+            "Order does not exist.",
+            ExchangeError::Rejected(msg("")),
+        ),
+        (
+            -1100,
+            "Illegal characters found.",
+            ExchangeError::Other(msg("")),
+        ),
+    ] {
+        let err = binance_error(code, text);
+        assert_eq!(
+            discriminant(&err),
+            discriminant(&expected),
+            "{} {}: {:?}",
+            code,
+            text,
+            err
+        );
+    }
+}
+
+#[test]
+fn unknown_binance_errors_keep_their_code() {
+    assert_eq!(
+        binance_error(-1100, "Illegal characters found.").to_string(),
+        "-1100: Illegal characters found."
+    );
+}
@@ -0,0 +1,77 @@
+use spearmint::model::indicator::Resampled;
+use spearmint::model::result::{Indicator, IndicatorKind, Ticker};
+
+fn sma(name: &str, interval: &str) -> Indicator {
+    Indicator {
+        name: String::from(name),
+        kind: IndicatorKind::Sma,
+        period: 1,
+        interval: String::from(interval),
+        fast: None,
+        signal: None,
+        multiplier: None,
+    }
+}
+
+fn minute(i: u64) -> Ticker {
+    let close = 1.0 + i as f64;
+    Ticker {
+        pair: String::from("DOGEUSDT"),
+        timestamp: 1735689600 + i * 60,
+        open: close,
+        high: close,
+        low: close,
+        close,
+        volume: 1.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn merges_minutes_into_interval_klines() {
+    let mut pipelines =
+        Resampled::by_interval(&[sma("sma_1m", "1m"), sma("sma_5m", "5m")]).unwrap();
+    assert_eq!(pipelines.len(), 2);
+
+    let mut values: Vec<(u64, String, f64)> = Vec::new();
+    for i in 0..10 {
+        for pipeline in pipelines.iter_mut() {
+            for (name, value) in pipeline.next(&minute(i)) {
+                values.push((i, name, value));
+            }
+        }
+    }
+
+    // The 5m kline closes with its fifth minute, at that minute's close.
+    let five: Vec<(u64, f64)> = values
+        .iter()
+        .filter(|(_, name, _)| name == "sma_5m")
+        .map(|(i, _, value)| (*i, *value))
+        .collect();
+    assert_eq!(five, vec![(4, 5.0), (9, 10.0)]);
+    assert_eq!(
+        values
+            .iter()
+            .filter(|(_, name, _)| name == "sma_1m")
+            .count(),
+        10
+    );
+}
+
+#[test]
+fn feeds_kline_closed_by_a_gap() {
+    let mut pipeline = Resampled::new("5m", &[sma("sma_5m", "5m")]).unwrap();
+
+    // The last minutes of the first kline are missing.
+    assert!(pipeline.next(&minute(0)).is_empty());
+    assert!(pipeline.next(&minute(1)).is_empty());
+    assert_eq!(
+        pipeline.next(&minute(6)),
+        vec![(String::from("sma_5m"), 2.0)]
+    );
+}
+
+#[test]
+fn rejects_monthly_klines() {
+    assert!(Resampled::new("1M", &[sma("sma_1mo", "1M")]).is_err());
+}
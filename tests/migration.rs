@@ -0,0 +1,90 @@
+use rusqlite::{params, Connection};
+use spearmint::connector::simulator;
+use spearmint::model::migration::{self, MAIN};
+use std::path::PathBuf;
+
+fn database(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    dir.join("spearmint.db")
+}
+
+fn count(conn: &Connection, sql: &str) -> u64 {
+    conn.query_row(sql, [], |row| row.get(0)).unwrap()
+}
+
+#[test]
+fn backtest_rows_from_before_bot_ids_are_removed() {
+    let path = database("spearmint_migration_backtest");
+    let path = path.to_str().unwrap();
+    migration::migrate(path, &MAIN[..6]).unwrap();
+
+    let conn = Connection::open(path).unwrap();
+    conn.execute(
+        "INSERT INTO bots (title, pair, base, quote, platform, status)
+        VALUES ('DOGEUSDT', 'DOGEUSDT', 'DOGE', 'USDT', 'binance', 'ACTIVE')",
+        [],
+    )
+    .unwrap();
+    for platform in ["binance", simulator::PLATFORM] {
+        conn.execute(
+            "INSERT INTO trades (pair, cycle, price, qty, platform, status, timestamp)
+            VALUES ('DOGEUSDT', 1, 0.3, 32.0, ?1, 'OPEN', 1735689600)",
+            params![platform],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO bot_states (pair, cycle, margin_position, top_price, bottom_price,
+                bottom_mfi, platform, timestamp)
+            VALUES ('DOGEUSDT', 1, 0, 0.3, 0.3, 50.0, ?1, 1735689600)",
+            params![platform],
+        )
+        .unwrap();
+    }
+
+    let applied = migration::migrate(path, MAIN).unwrap();
+    assert_eq!(applied.first(), Some(&7));
+
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT COUNT(*) FROM trades WHERE platform='backtest'"
+        ),
+        0
+    );
+    assert_eq!(
+        count(
+            &conn,
+            "SELECT COUNT(*) FROM bot_states WHERE platform='backtest'"
+        ),
+        0
+    );
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM trades WHERE bot_id=1"),
+        1
+    );
+    assert_eq!(
+        count(&conn, "SELECT COUNT(*) FROM bot_states WHERE bot_id=1"),
+        1
+    );
+}
+
+#[test]
+fn backtest_rows_left_after_bot_ids_are_removed() {
+    let path = database("spearmint_migration_backtest_left");
+    let path = path.to_str().unwrap();
+    migration::migrate(path, &MAIN[..14]).unwrap();
+
+    let conn = Connection::open(path).unwrap();
+    conn.execute(
+        "INSERT INTO trades (pair, cycle, price, qty, platform, status, timestamp)
+        VALUES ('DOGEUSDT', 1, 0.3, 32.0, ?1, 'OPEN', 1735689600)",
+        params![simulator::PLATFORM],
+    )
+    .unwrap();
+
+    assert_eq!(migration::migrate(path, MAIN).unwrap(), [15]);
+    assert_eq!(count(&conn, "SELECT COUNT(*) FROM trades"), 0);
+}
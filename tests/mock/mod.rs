@@ -0,0 +1,322 @@
+//! In-process stand-in for the exchange REST APIs and the Binance websocket
+//! streams, so the binaries can be driven end to end with e.g.
+//! `SPEARMINT_BINANCE_URL` and `SPEARMINT_BINANCE_STREAM_URL` pointing at it.
+// Each test crate uses its own part of the mock.
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::{accept_hdr, Message};
+
+/// Header, lowercase and possibly with its value, that requests need to be
+/// answered.
+#[derive(Clone, Copy)]
+pub enum Auth {
+    /// Only requests with a `signature` parameter, as Binance signs them.
+    Signed(&'static str),
+    /// Every request.
+    All(&'static str),
+}
+
+/// REST request as the mock received it.
+#[derive(Clone, Debug)]
+pub struct Received {
+    pub line: String,
+    /// Names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Received {
+    pub fn header(&self, name: &str) -> &str {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    }
+}
+
+pub struct MockExchange {
+    pub url: String,
+    pub stream_url: String,
+    requests: Arc<Mutex<Vec<Received>>>,
+}
+
+impl MockExchange {
+    /// Binance, refusing signed requests without the `X-MBX-APIKEY` header.
+    /// Websocket clients get the messages of the first stream named in their
+    /// path, and the connection is then held open.
+    pub fn start(
+        routes: Vec<(&'static str, String)>,
+        streams: Vec<(&'static str, Vec<String>)>,
+    ) -> Self {
+        Self::with_auth(Auth::Signed("x-mbx-apikey:"), routes, streams)
+    }
+
+    /// REST requests are answered by the first route whose words all appear
+    /// in the request line, e.g. `POST /api/v3/order side=BUY`, once they
+    /// pass `auth`.
+    pub fn with_auth(
+        auth: Auth,
+        routes: Vec<(&'static str, String)>,
+        streams: Vec<(&'static str, Vec<String>)>,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                respond(stream.unwrap(), auth, &routes, &recorded);
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream_url = format!("ws://{}", listener.local_addr().unwrap());
+        let streams = Arc::new(streams);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let streams = streams.clone();
+                thread::spawn(move || publish(stream.unwrap(), &streams));
+            }
+        });
+
+        Self {
+            url,
+            stream_url,
+            requests,
+        }
+    }
+
+    /// Request lines received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.received()
+            .into_iter()
+            .map(|request| request.line)
+            .collect()
+    }
+
+    /// Requests received so far with their headers and body, in order.
+    pub fn received(&self) -> Vec<Received> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn respond(
+    mut stream: TcpStream,
+    auth: Auth,
+    routes: &[(&'static str, String)],
+    requests: &Mutex<Vec<Received>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request = String::new();
+    let mut headers = Vec::new();
+    let mut signed = false;
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+            break;
+        }
+        let header = line.to_lowercase();
+        signed |= match auth {
+            Auth::Signed(name) | Auth::All(name) => header.starts_with(name),
+        };
+        if let Some(value) = header.strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap_or(0);
+        }
+        if request.is_empty() {
+            request = String::from(line.trim_end());
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.to_lowercase(), String::from(value.trim())));
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    requests.lock().unwrap().push(Received {
+        line: request.clone(),
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    });
+
+    let (status, body) = match routes
+        .iter()
+        .find(|(route, _)| route.split_whitespace().all(|word| request.contains(word)))
+    {
+        _ if !signed && matches!(auth, Auth::All(_)) => ("401 Unauthorized", String::from("{}")),
+        _ if !signed && request.contains("signature=") => (
+            "401 Unauthorized",
+            String::from(
+                "{\"code\":-2015,\"msg\":\"Invalid API-key, IP, or permissions for action.\"}",
+            ),
+        ),
+        Some((_, body)) => ("200 OK", body.clone()),
+        None => ("404 Not Found", String::from("{}")),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+    .unwrap();
+}
+
+fn publish(stream: TcpStream, streams: &[(&'static str, Vec<String>)]) {
+    let mut path = String::new();
+    let mut web_socket = accept_hdr(stream, |request: &Request, response: Response| {
+        path = request.uri().to_string();
+        Ok::<Response, ErrorResponse>(response)
+    })
+    .unwrap();
+
+    if let Some((_, messages)) = streams.iter().find(|(name, _)| path.contains(name)) {
+        for message in messages {
+            thread::sleep(Duration::from_millis(100));
+            if web_socket.send(Message::text(message.clone())).is_err() {
+                return;
+            }
+        }
+    }
+
+    while web_socket.read().is_ok() {}
+}
+
+/// Canned response under `tests/fixtures`, e.g. `binance/account.json`.
+pub fn fixture(name: &str) -> String {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    std::fs::read_to_string(path).unwrap()
+}
+
+/// Spot endpoints used by the connector, answered from `tests/fixtures/binance`.
+pub fn routes() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "GET /api/v3/exchangeInfo",
+            fixture("binance/exchange-info.json"),
+        ),
+        ("GET /api/v3/account", fixture("binance/account.json")),
+        (
+            "GET /api/v3/ticker/price",
+            fixture("binance/ticker-price.json"),
+        ),
+        ("GET /api/v3/klines", fixture("binance/klines.json")),
+        (
+            "POST /api/v3/order side=BUY",
+            fixture("binance/order-buy.json"),
+        ),
+        (
+            "POST /api/v3/order side=SELL",
+            fixture("binance/order-sell.json"),
+        ),
+    ]
+}
+
+/// Bybit spot endpoints used by the connector, answered from
+/// `tests/fixtures/bybit`. Orders are looked up on `GET /v5/order/realtime`,
+/// which each test answers with the fill it expects.
+pub fn bybit_routes() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "GET /v5/market/instruments-info",
+            fixture("bybit/instruments-info.json"),
+        ),
+        (
+            "GET /v5/account/wallet-balance coin=DOGE",
+            fixture("bybit/wallet-balance-doge.json"),
+        ),
+        (
+            "GET /v5/account/wallet-balance",
+            fixture("bybit/wallet-balance.json"),
+        ),
+        (
+            "GET /v5/execution/list",
+            fixture("bybit/execution-list.json"),
+        ),
+        ("POST /v5/order/create", fixture("bybit/order-create.json")),
+    ]
+}
+
+/// OKX spot endpoints used by the connector, answered from
+/// `tests/fixtures/okx`. Orders are looked up on `GET /api/v5/trade/order`,
+/// which each test answers with the fill it expects.
+pub fn okx_routes() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "GET /api/v5/public/instruments",
+            fixture("okx/instruments.json"),
+        ),
+        (
+            "GET /api/v5/account/balance ccy=DOGE",
+            fixture("okx/balance-doge.json"),
+        ),
+        ("GET /api/v5/account/balance", fixture("okx/balance.json")),
+        ("POST /api/v5/trade/order", fixture("okx/order-create.json")),
+    ]
+}
+
+/// `GET /api/v3/klines` body of `closes.len()` closed 1m klines from
+/// `open_time` on.
+pub fn rest_klines(open_time: u64, closes: &[f64]) -> String {
+    let klines: Vec<String> = closes
+        .iter()
+        .enumerate()
+        .map(|(i, close)| {
+            let open_time = open_time + i as u64 * 60_000;
+            format!(
+                "[{open_time},\"{close}\",\"{close}\",\"{close}\",\"{close}\",\"1000.0\",{close_time},\"310.0\",100,\"500.0\",\"155.0\",\"0\"]",
+                open_time = open_time,
+                close_time = open_time + 59_999,
+                close = close
+            )
+        })
+        .collect();
+
+    format!("[{}]", klines.join(","))
+}
+
+/// `<symbol>@ticker` event closing at `close`.
+pub fn day_ticker(symbol: &str, close: f64) -> String {
+    format!(
+        concat!(
+            "{{\"e\":\"24hrTicker\",\"E\":1735689600000,\"s\":\"{symbol}\",\"p\":\"0.00100000\",",
+            "\"P\":\"0.323\",\"w\":\"{close}\",\"x\":\"{close}\",\"c\":\"{close}\",\"Q\":\"100.00000000\",",
+            "\"b\":\"{close}\",\"B\":\"1000.00000000\",\"a\":\"{close}\",\"A\":\"1000.00000000\",",
+            "\"o\":\"{close}\",\"h\":\"{close}\",\"l\":\"{close}\",\"v\":\"1000000.00000000\",",
+            "\"q\":\"310000.00000000\",\"O\":1735603200000,\"C\":1735689600000,\"F\":1,\"L\":1000,\"n\":1000}}"
+        ),
+        symbol = symbol,
+        close = close
+    )
+}
+
+/// `<symbol>@kline_1m` event of the combined stream, final once the candle
+/// has closed.
+pub fn kline(symbol: &str, open_time: u64, close: f64, is_final: bool) -> String {
+    format!(
+        concat!(
+            "{{\"stream\":\"{stream}@kline_1m\",\"data\":{{\"e\":\"kline\",\"E\":{open_time},",
+            "\"s\":\"{symbol}\",\"k\":{{\"t\":{open_time},\"T\":{close_time},\"s\":\"{symbol}\",",
+            "\"i\":\"1m\",\"f\":100,\"L\":200,\"o\":\"{close}\",\"c\":\"{close}\",\"h\":\"{close}\",",
+            "\"l\":\"{close}\",\"v\":\"1000.00000000\",\"n\":100,\"x\":{is_final},",
+            "\"q\":\"310.00000000\",\"V\":\"500.00000000\",\"Q\":\"155.00000000\",\"B\":\"0\"}}}}}}"
+        ),
+        stream = symbol.to_lowercase(),
+        symbol = symbol,
+        open_time = open_time,
+        close_time = open_time + 59_999,
+        close = close,
+        is_final = is_final
+    )
+}
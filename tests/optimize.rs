@@ -0,0 +1,95 @@
+use spearmint::model::{args, result};
+use spearmint::optimize::{self, Outcome};
+use std::collections::HashSet;
+use std::path::Path;
+
+fn dogeusdt() -> result::Bot {
+    let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+
+    toml::from_str(&std::fs::read_to_string(config).unwrap()).unwrap()
+}
+
+fn sweep(content: &str) -> args::Sweep {
+    toml::from_str(content).unwrap()
+}
+
+fn outcome(cycles: usize, pnl: f64, max_drawdown: f64, capital: f64) -> Outcome {
+    Outcome {
+        config: dogeusdt(),
+        cycles,
+        pnl,
+        max_drawdown,
+        capital,
+    }
+}
+
+#[test]
+fn grid_tries_every_combination_of_the_swept_values() {
+    let sweep = sweep(
+        "[first_buy_in]\nmin = 10.0\nmax = 20.0\nstep = 5.0\n
+        [mfi_below]\nmin = 20.0\nmax = 30.0\nstep = 10.0\n
+        [[amount_ratio]]\nmin = 1.0\nmax = 2.0\nstep = 0.5\n",
+    );
+
+    let candidates = optimize::candidates(&dogeusdt(), &sweep);
+
+    assert_eq!(candidates.len(), 3 * 2 * 3);
+    let combinations: HashSet<String> = candidates
+        .iter()
+        .map(|candidate| {
+            format!(
+                "{} {} {}",
+                candidate.parameters.first_buy_in,
+                candidate.parameters.entry.mfi_below,
+                candidate.margin.margin_configuration[0].amount_ratio
+            )
+        })
+        .collect();
+    assert_eq!(combinations.len(), candidates.len());
+    // Levels without a range keep the bot's value.
+    assert!(candidates
+        .iter()
+        .all(|candidate| candidate.margin.margin_configuration[1].amount_ratio == 15.0));
+}
+
+#[test]
+fn grid_without_ranges_tries_the_bot_as_is() {
+    let candidates = optimize::candidates(&dogeusdt(), &sweep(""));
+
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].parameters.first_buy_in, 10.0);
+}
+
+#[test]
+fn random_draws_the_requested_samples_on_the_steps() {
+    let sweep = sweep(
+        "mode = \"random\"\nsamples = 7\n
+        [first_buy_in]\nmin = 10.0\nmax = 20.0\nstep = 5.0\n",
+    );
+
+    let candidates = optimize::candidates(&dogeusdt(), &sweep);
+
+    assert_eq!(candidates.len(), 7);
+    assert!(candidates
+        .iter()
+        .all(|candidate| [10.0, 15.0, 20.0].contains(&candidate.parameters.first_buy_in)));
+}
+
+#[test]
+fn outcomes_are_ranked_best_first() {
+    let ranked = |rank_by: &str| -> Vec<usize> {
+        let mut outcomes = vec![
+            outcome(1, 5.0, 2.0, 10.0),
+            outcome(2, 5.0, 1.0, 20.0),
+            outcome(3, 3.0, 0.5, 5.0),
+        ];
+        optimize::rank(&mut outcomes, rank_by);
+
+        outcomes.iter().map(|outcome| outcome.cycles).collect()
+    };
+
+    // Equal PnL is broken by the smaller drawdown.
+    assert_eq!(ranked("pnl"), vec![2, 1, 3]);
+    assert_eq!(ranked("drawdown"), vec![3, 2, 1]);
+    assert_eq!(ranked("capital"), vec![3, 1, 2]);
+}
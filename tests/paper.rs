@@ -0,0 +1,172 @@
+use spearmint::connector::paper::PaperExchange;
+use spearmint::model::context::Context;
+use spearmint::model::{args, migration, paper, result, storage, Exchange, ExchangeError};
+use std::collections::HashMap;
+
+fn context(name: &str, balances: &[(&str, f64)]) -> Context {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |file: &str| Some(dir.join(file).to_str().unwrap().to_string());
+
+    let ctx = Context::load(
+        path("spearmint.toml"),
+        path("spearmint.db"),
+        path("spearmint_data.db"),
+        path("ticker1m.db"),
+    );
+    migration::migrate(&ctx.db_path, migration::MAIN).unwrap();
+    migration::migrate(&ctx.ticker_path("1m"), migration::TICKER).unwrap();
+    paper::save(
+        &ctx,
+        args::PaperAccount {
+            taker_fee: 0.1,
+            slippage: 0.0,
+            balances: balances
+                .iter()
+                .map(|(asset, free)| (asset.to_string(), *free))
+                .collect::<HashMap<String, f64>>(),
+        },
+    );
+
+    ctx
+}
+
+fn close(ctx: &Context, timestamp: u64, price: f64) {
+    storage::create_ticker(
+        &ctx.ticker_path("1m"),
+        result::Ticker {
+            pair: String::from("DOGEUSDT"),
+            timestamp,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: 1000.0,
+            ..Default::default()
+        },
+    );
+}
+
+fn free(exchange: &PaperExchange, asset: &str) -> f64 {
+    exchange.get_balance(String::from(asset)).unwrap().free
+}
+
+#[test]
+fn market_buy_debits_the_quote_and_credits_the_base_after_fees() {
+    let ctx = context("spearmint_paper_buy", &[("USDT", 100.0)]);
+    close(&ctx, 1735689600, 0.25);
+    let exchange = PaperExchange::new(&ctx, "DOGE", "USDT");
+
+    let transaction = exchange
+        .market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.0)
+        .unwrap();
+
+    assert_eq!(transaction.price, 0.25);
+    assert_eq!(transaction.qty, 40.0);
+    assert!((transaction.commission - 0.04).abs() < 1e-9);
+    assert_eq!(transaction.commission_asset, "DOGE");
+    assert!((free(&exchange, "USDT") - 90.0).abs() < 1e-9);
+    assert!((free(&exchange, "DOGE") - 39.96).abs() < 1e-9);
+}
+
+#[test]
+fn market_sell_debits_the_base_and_credits_the_quote_after_fees() {
+    let ctx = context("spearmint_paper_sell", &[("DOGE", 40.0)]);
+    close(&ctx, 1735689600, 0.25);
+    let exchange = PaperExchange::new(&ctx, "DOGE", "USDT");
+
+    let transaction = exchange
+        .market_sell(String::from("DOGEUSDT"), 40.0)
+        .unwrap();
+
+    assert!((transaction.commission - 0.01).abs() < 1e-9);
+    assert_eq!(transaction.commission_asset, "USDT");
+    assert_eq!(free(&exchange, "DOGE"), 0.0);
+    assert!((free(&exchange, "USDT") - 9.99).abs() < 1e-9);
+}
+
+#[test]
+fn limit_buy_fills_once_the_close_crosses_its_price() {
+    let ctx = context("spearmint_paper_limit", &[("USDT", 100.0)]);
+    close(&ctx, 1735689600, 0.25);
+    let exchange = PaperExchange::new(&ctx, "DOGE", "USDT");
+
+    let order = exchange
+        .limit_buy(String::from("DOGEUSDT"), 40.0, 0.24)
+        .unwrap();
+    assert!((free(&exchange, "USDT") - 90.4).abs() < 1e-9);
+    assert_eq!(
+        exchange
+            .get_order(String::from("DOGEUSDT"), order.order_id)
+            .unwrap()
+            .status,
+        "NEW"
+    );
+
+    close(&ctx, 1735689660, 0.23);
+    let order = exchange
+        .get_order(String::from("DOGEUSDT"), order.order_id)
+        .unwrap();
+    assert_eq!(order.status, "FILLED");
+    assert!((order.commission - 0.04).abs() < 1e-9);
+    assert!((free(&exchange, "DOGE") - 39.96).abs() < 1e-9);
+}
+
+#[test]
+fn orders_beyond_the_balance_are_refused() {
+    let ctx = context("spearmint_paper_balance", &[("USDT", 10.0)]);
+    close(&ctx, 1735689600, 0.25);
+    let exchange = PaperExchange::new(&ctx, "DOGE", "USDT");
+
+    assert!(matches!(
+        exchange.market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.5),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+    assert!(matches!(
+        exchange.limit_buy(String::from("DOGEUSDT"), 50.0, 0.24),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+    assert!(matches!(
+        exchange.limit_sell(String::from("DOGEUSDT"), 1.0, 0.30),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+    assert_eq!(free(&exchange, "USDT"), 10.0);
+    assert_eq!(free(&exchange, "DOGE"), 0.0);
+}
+
+#[test]
+fn market_orders_without_a_price_are_refused() {
+    let ctx = context(
+        "spearmint_paper_no_price",
+        &[("USDT", 100.0), ("DOGE", 40.0)],
+    );
+    let exchange = PaperExchange::new(&ctx, "DOGE", "USDT");
+
+    for refused in [
+        exchange.market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.0),
+        exchange.market_sell(String::from("DOGEUSDT"), 40.0),
+    ] {
+        let err = refused.unwrap_err();
+        assert!(!err.is_transient(), "{} is transient", err);
+    }
+    assert_eq!(free(&exchange, "USDT"), 100.0);
+    assert_eq!(free(&exchange, "DOGE"), 40.0);
+}
+
+#[test]
+fn market_orders_without_a_ticker_database_are_refused() {
+    let mut ctx = context("spearmint_paper_no_tickers", &[("USDT", 100.0)]);
+    let missing = std::env::temp_dir().join("spearmint_paper_no_tickers/missing");
+    ctx.ticker_paths.insert(
+        String::from("1m"),
+        missing.join("ticker1m.db").to_str().unwrap().to_string(),
+    );
+    let exchange = PaperExchange::new(&ctx, "DOGE", "USDT");
+
+    let err = exchange
+        .market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.0)
+        .unwrap_err();
+    assert!(!err.is_transient(), "{} is transient", err);
+    assert_eq!(free(&exchange, "USDT"), 100.0);
+}
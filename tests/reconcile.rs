@@ -0,0 +1,171 @@
+use spearmint::model::context::Context;
+use spearmint::model::{bot, result, storage};
+use spearmint::reconcile::{self, Discrepancy};
+use std::path::Path;
+
+fn dogeusdt(ctx: &Context) -> result::Bot {
+    let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/bot.dogeusdt.toml");
+    let content = std::fs::read_to_string(config).unwrap();
+
+    bot::save(ctx, toml::from_str(&content).unwrap())
+}
+
+fn fill(order_id: u64, is_buyer: bool, qty: f64, price: f64, timestamp: u64) -> result::Fill {
+    result::Fill {
+        order_id,
+        pair: String::from("DOGEUSDT"),
+        price,
+        qty,
+        commission: if is_buyer {
+            qty * 0.001
+        } else {
+            qty * price * 0.001
+        },
+        commission_asset: String::from(if is_buyer { "DOGE" } else { "USDT" }),
+        is_buyer,
+        timestamp: timestamp * 1000,
+    }
+}
+
+/// Local row recorded from the exchange's version of the fill.
+fn record(ctx: &Context, config: &result::Bot, cycle: u64, fill: &result::Fill) {
+    storage::create_trade(
+        ctx,
+        result::Trade {
+            pair: fill.pair.clone(),
+            cycle,
+            price: fill.price,
+            qty: fill.qty,
+            platform: config.platform.clone(),
+            status: String::from(if fill.is_buyer { "OPEN" } else { "CLOSE" }),
+            timestamp: fill.timestamp / 1000,
+            order_id: fill.order_id,
+            quote_qty: fill.qty * fill.price,
+            commission: fill.commission,
+            commission_asset: fill.commission_asset.clone(),
+            bot_id: config.id,
+            ..Default::default()
+        },
+    );
+}
+
+fn trades(ctx: &Context, config: &result::Bot) -> Vec<result::Trade> {
+    storage::get_trades(ctx, config.id)
+        .into_iter()
+        .map(|trade| trade.unwrap())
+        .collect()
+}
+
+/// Local trades of three cycles next to the exchange's fills: order 11 in sync
+/// and recorded twice, order 12 recorded with the wrong quantity, order 13
+/// recorded without order id close to the fill, and order 14 never recorded,
+/// with the nearest legacy row too far off to stand for it.
+fn seed(ctx: &Context, config: &result::Bot) -> Vec<result::Fill> {
+    let fills = vec![
+        fill(11, true, 40.0, 0.25, 1000),
+        fill(12, false, 39.96, 0.30, 2000),
+        fill(13, true, 40.0, 0.25, 3100),
+        fill(14, true, 40.0, 0.25, 4200),
+    ];
+
+    record(ctx, config, 1, &fills[0]);
+    record(ctx, config, 1, &fill(11, true, 40.0, 0.25, 1001));
+    record(ctx, config, 1, &fill(12, false, 40.0, 0.30, 2000));
+    record(ctx, config, 2, &fill(0, true, 40.0, 0.25, 3000));
+    record(ctx, config, 3, &fill(0, true, 40.0, 0.25, 4000));
+
+    fills
+}
+
+#[test]
+fn orders_from_fills_sums_the_fills_of_each_order() {
+    let mut fills = vec![
+        fill(2, true, 10.0, 0.30, 1000),
+        fill(2, true, 30.0, 0.32, 1000),
+        fill(1, false, 5.0, 0.40, 900),
+        fill(3, true, 5.0, 0.40, 100),
+    ];
+    fills[1].timestamp += 400;
+    let mut bnb = fill(2, true, 20.0, 0.31, 1001);
+    bnb.commission_asset = String::from("BNB");
+    fills.push(bnb);
+
+    let orders = reconcile::orders_from_fills(&fills, 500);
+
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[0].order_id, 1);
+    assert_eq!(orders[0].status, "CLOSE");
+    assert_eq!(orders[0].timestamp, 900);
+
+    let order = &orders[1];
+    assert_eq!(order.order_id, 2);
+    assert_eq!(order.status, "OPEN");
+    assert_eq!(order.timestamp, 1000);
+    assert!((order.qty - 60.0).abs() < 1e-9);
+    assert!((order.quote_qty - 18.8).abs() < 1e-9);
+    assert!((order.price - 18.8 / 60.0).abs() < 1e-9);
+    // Commissions in another asset than the first fill's are left out.
+    assert!((order.commission - 0.04).abs() < 1e-9);
+    assert_eq!(order.commission_asset, "DOGE");
+}
+
+#[test]
+fn run_matches_fills_by_order_id_then_by_time() {
+    let ctx = Context::in_memory();
+    let config = dogeusdt(&ctx);
+    let fills = seed(&ctx, &config);
+
+    match &reconcile::run(&ctx, &config, &fills)[..] {
+        [Discrepancy::Duplicated(duplicated), Discrepancy::Mismatched {
+            local: wrong_qty,
+            exchange: filled,
+        }, Discrepancy::Mismatched {
+            local: legacy,
+            exchange: matched,
+        }, Discrepancy::Missing(missing)] => {
+            assert_eq!(duplicated.order_id, 11);
+            assert_eq!(duplicated.timestamp, 1001);
+
+            assert_eq!(wrong_qty.order_id, 12);
+            assert!((filled.qty - 39.96).abs() < 1e-9);
+
+            assert_eq!((legacy.order_id, legacy.timestamp), (0, 3000));
+            assert_eq!(matched.order_id, 13);
+            assert_eq!(matched.cycle, 2);
+
+            assert_eq!(missing.order_id, 14);
+            assert_eq!(missing.cycle, 3);
+            assert_eq!(missing.bot_id, config.id);
+            assert_eq!(missing.platform, config.platform);
+        }
+        other => panic!("unexpected discrepancies: {:?}", other),
+    }
+}
+
+#[test]
+fn apply_imports_the_exchange_version() {
+    let ctx = Context::in_memory();
+    let config = dogeusdt(&ctx);
+    let fills = seed(&ctx, &config);
+
+    let discrepancies = reconcile::run(&ctx, &config, &fills);
+    assert_eq!(reconcile::apply(&ctx, discrepancies), 4);
+
+    let imported: Vec<(u64, u64, f64)> = trades(&ctx, &config)
+        .iter()
+        .map(|trade| (trade.cycle, trade.order_id, trade.qty))
+        .collect();
+    assert_eq!(
+        imported,
+        vec![
+            (1, 11, 40.0),
+            (1, 12, 39.96),
+            (2, 13, 40.0),
+            (3, 0, 40.0),
+            (3, 14, 40.0),
+        ]
+    );
+
+    // The legacy row left unmatched isn't a discrepancy of its own.
+    assert!(reconcile::run(&ctx, &config, &fills).is_empty());
+}
@@ -0,0 +1,66 @@
+use spearmint::connector::simulator::Connector;
+use spearmint::model::{Exchange, ExchangeError};
+
+fn connector(capital: f64) -> Connector {
+    let connector = Connector::new("DOGE", "USDT", capital, 0.1);
+    connector.set_market(0.25, 1735689600);
+
+    connector
+}
+
+#[test]
+fn market_buy_beyond_the_quote_balance_is_refused() {
+    let connector = connector(10.0);
+
+    assert!(matches!(
+        connector.market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.5),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+    assert_eq!(
+        connector.get_balance(String::from("USDT")).unwrap().free,
+        10.0
+    );
+
+    let transaction = connector
+        .market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.0)
+        .unwrap();
+    assert_eq!(transaction.qty, 40.0);
+    assert_eq!(connector.position(), 40.0 * (1.0 - 0.1 / 100.0));
+}
+
+#[test]
+fn market_sell_beyond_the_position_is_refused() {
+    let connector = connector(10.0);
+    connector
+        .market_buy_using_quote_quantity(String::from("DOGEUSDT"), 10.0)
+        .unwrap();
+
+    assert!(matches!(
+        connector.market_sell(String::from("DOGEUSDT"), 40.0),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+
+    let position = connector.position();
+    connector
+        .market_sell(String::from("DOGEUSDT"), position)
+        .unwrap();
+    assert_eq!(connector.position(), 0.0);
+}
+
+#[test]
+fn limit_orders_beyond_the_balance_are_refused() {
+    let connector = connector(10.0);
+
+    assert!(matches!(
+        connector.limit_buy(String::from("DOGEUSDT"), 50.0, 0.24),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+    assert!(matches!(
+        connector.limit_sell(String::from("DOGEUSDT"), 1.0, 0.30),
+        Err(ExchangeError::InsufficientBalance(_))
+    ));
+    assert!(connector
+        .get_open_orders(String::from("DOGEUSDT"))
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,92 @@
+use spearmint::model::{result, BotCommand, Exit, Initial, Session, Strategy};
+use spearmint::strategy::helldiver::HellDiverStrategy;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+fn helldiver(stop_loss: result::StopLoss) -> HellDiverStrategy {
+    HellDiverStrategy {
+        first_buy_in: 10.0,
+        entry: result::OpenCriteria::default(),
+        take_profit: result::CloseCriteria {
+            price_change_above: 50.0,
+            price_callback: 1.0,
+            limit_order: false,
+        },
+        margin_configuration: vec![],
+        stop_loss: Some(stop_loss),
+    }
+}
+
+fn open_session(avg_price: f64, top_price: f64, peak_price: f64, age: u64) -> Session {
+    Session::<Initial> {
+        status: String::from("OPEN"),
+        avg_price,
+        top_price,
+        bottom_price: avg_price,
+        peak_price,
+        margin_position: 0,
+        mfi: Some(50.0),
+        mfi_dir: String::from("DOWN"),
+        bottom_mfi: 50.0,
+        opened_at: 1_000,
+        timestamp: 1_000 + age,
+        take_profit_order_id: 0,
+        grid_levels: vec![],
+        ticker: result::Ticker::default(),
+        indicators: HashMap::new(),
+        phantom: PhantomData,
+    }
+}
+
+fn assert_stopped(command: BotCommand) {
+    match command {
+        BotCommand::Sell(exit @ Exit::StopLoss) => assert_eq!(exit.status(), "STOPPED"),
+        other => panic!("expected a stop loss, got {:?}", other),
+    }
+}
+
+#[test]
+fn stop_loss_below_average_price() {
+    let strategy = helldiver(result::StopLoss {
+        price_change_below: Some(-20.0),
+        ..Default::default()
+    });
+
+    assert_stopped(strategy.run(0.79, open_session(1.0, 1.0, 1.0, 60)));
+
+    assert!(matches!(
+        strategy.run(0.81, open_session(1.0, 1.0, 1.0, 60)),
+        BotCommand::Pause()
+    ));
+}
+
+#[test]
+fn stop_loss_trails_peak_since_entry() {
+    let strategy = helldiver(result::StopLoss {
+        top_price_change_below: Some(-10.0),
+        ..Default::default()
+    });
+
+    // Under the average price the top is reset to it, the peak is kept.
+    assert_stopped(strategy.run(1.05, open_session(1.1, 1.1, 1.2, 60)));
+
+    assert!(matches!(
+        strategy.run(1.1, open_session(1.1, 1.1, 1.2, 60)),
+        BotCommand::Pause()
+    ));
+}
+
+#[test]
+fn stop_loss_after_max_cycle_hours() {
+    let strategy = helldiver(result::StopLoss {
+        max_cycle_hours: Some(2.0),
+        ..Default::default()
+    });
+
+    assert_stopped(strategy.run(1.0, open_session(1.0, 1.0, 1.0, 2 * 3600 + 1)));
+
+    assert!(matches!(
+        strategy.run(1.0, open_session(1.0, 1.0, 1.0, 2 * 3600)),
+        BotCommand::Pause()
+    ));
+}